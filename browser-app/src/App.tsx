@@ -21,6 +21,8 @@ type SdpMessage = {
   medias: SdpMedia[];
 };
 
+type Setup = "active" | "passive" | "actpass" | "holdconn";
+
 type SdpMedia = {
   mediaId: string;
   mediaType: MediaType;
@@ -31,7 +33,9 @@ type SdpMedia = {
   pwd: string;
   fingerprintType: FingerprintType;
   fingerprintHash: string;
+  setup?: Setup;
   candidates: SdpMediaCandidate[];
+  endOfCandidates?: "end-of-candidates";
   payloads: string;
   rtp: Rtp[];
   rtcpMux?: "rtcp-mux";
@@ -47,20 +51,28 @@ type Rtp = {
 };
 
 type SdpMediaCandidate = {
+  foundation: string;
+  component: number;
   ip: string;
   port: number;
   candidateType: CandidateType;
   transportType: TransportType;
+  priority: number;
+  relatedAddress?: string;
+  relatedPort?: number;
+  generation?: number;
 };
 
 type MediaType = "audio" | "video";
 type MediaDirection = "sendrecv" | "sendonly" | "recvonly" | "inactive";
-type CandidateType = "host";
+type CandidateType = "host" | "srflx" | "prflx" | "relay";
 type TransportType = "udp" | "tcp";
 type FingerprintType = "sha-256";
 
 async function fetchSdpOffer(): Promise<SdpMessage> {
-  const resp = await fetch(`${signalingServerUrl}/`);
+  const resp = await fetch(`${signalingServerUrl}/local-offer`, {
+    method: "POST",
+  });
   if (!resp.ok) {
     throw new Error("failed to fetch offer");
   }
@@ -91,17 +103,25 @@ async function sendSdpAnswer(
           pwd: m.icePwd!,
           fingerprintType: m.fingerprint!.type as FingerprintType,
           fingerprintHash: m.fingerprint!.hash,
+          setup: m.setup as Setup | undefined,
           candidates:
             m.candidates
               ?.filter((c) => isValidIPv4(c.ip))
               .map((c) => ({
+                foundation: String(c.foundation),
+                component: c.component,
                 ip: c.ip,
                 port: c.port,
-                candidateType: "host" as CandidateType,
-                transportType: c.transport as TransportType,
+                candidateType: c.type as CandidateType,
+                transportType: c.transport.toLowerCase() as TransportType,
+                priority: Number(c.priority),
+                relatedAddress: c.raddr,
+                relatedPort: c.rport,
+                generation: c.generation,
               })) ?? [],
           payloads: "",
           rtp: [],
+          rtcpMux: m.rtcpMux ? "rtcp-mux" : undefined,
           protocol: m.protocol,
         };
       }) ?? [],
@@ -126,34 +146,46 @@ async function sendSdpAnswer(
   return json;
 }
 
-async function waitForIceGatheringComplete(
-  pc: RTCPeerConnection,
-  timeoutMs = 5000,
-): Promise<void> {
-  if (pc.iceGatheringState === "complete") {
-    return;
+async function sendIceCandidate(candidate: RTCIceCandidateInit): Promise<void> {
+  const resp = await fetch(`${signalingServerUrl}/candidate`, {
+    headers: {
+      "Content-Type": "application/json",
+    },
+    method: "POST",
+    body: JSON.stringify(candidate),
+  });
+  if (!resp.ok) {
+    const responseBody = await resp.text();
+    throw new Error(
+      `failed to post candidate: ${resp.status} ${resp.statusText} ${responseBody}`,
+    );
   }
+  console.log("sent ice candidate", candidate);
+}
 
-  await new Promise<void>((resolve) => {
-    const onStateChange = () => {
-      if (pc.iceGatheringState === "complete") {
-        cleanup();
-        resolve();
+// the server trickles the candidates it gathers after the offer; poll them until
+// end-of-candidates.
+async function receiveServerCandidates(pc: RTCPeerConnection): Promise<void> {
+  const received = new Set<string>();
+  while (pc.signalingState !== "closed") {
+    const resp = await fetch(`${signalingServerUrl}/candidates`);
+    if (!resp.ok) {
+      throw new Error(`failed to fetch candidates: ${resp.status}`);
+    }
+    const candidates = (await resp.json()) as RTCIceCandidateInit[];
+    for (const candidate of candidates) {
+      if (received.has(candidate.candidate ?? "")) {
+        continue;
       }
-    };
-
-    const timeoutId = window.setTimeout(() => {
-      cleanup();
-      resolve();
-    }, timeoutMs);
-
-    const cleanup = () => {
-      window.clearTimeout(timeoutId);
-      pc.removeEventListener("icegatheringstatechange", onStateChange);
-    };
-
-    pc.addEventListener("icegatheringstatechange", onStateChange);
-  });
+      received.add(candidate.candidate ?? "");
+      await pc.addIceCandidate(candidate);
+      console.log("received ice candidate", candidate);
+      if (!candidate.candidate) {
+        return;
+      }
+    }
+    await new Promise((resolve) => setTimeout(resolve, 500));
+  }
 }
 
 function App() {
@@ -232,14 +264,18 @@ function App() {
           hash: media.fingerprintHash,
         },
         candidates: media.candidates.map((candidate) => ({
-          foundation: "0",
-          component: 1,
+          foundation: candidate.foundation,
+          component: candidate.component,
           transport: candidate.transportType,
-          priority: 2113667327,
+          priority: candidate.priority,
           ip: candidate.ip,
           port: candidate.port,
           type: candidate.candidateType,
+          raddr: candidate.relatedAddress,
+          rport: candidate.relatedPort,
+          generation: candidate.generation,
         })),
+        endOfCandidates: media.endOfCandidates,
         rtp:
           media.rtp.length > 0
             ? [
@@ -301,9 +337,28 @@ function App() {
     //   console.log(sdpTransform.parse(answer.sdp!));
     // }
 
+    // trickle local candidates once the server has accepted the answer.
+    const pendingCandidates: RTCIceCandidateInit[] = [];
+    let answerSent = false;
+    pc.onicecandidate = ({ candidate }) => {
+      if (candidate) {
+        setIceCandidates((value) => [...value, candidate]);
+      }
+      // a null candidate means gathering is complete; signal end-of-candidates.
+      const init = candidate?.toJSON() ?? {
+        candidate: "",
+        sdpMid: offer.medias[0]?.mediaId,
+        sdpMLineIndex: 0,
+      };
+      if (answerSent) {
+        sendIceCandidate(init).catch(console.error);
+      } else {
+        pendingCandidates.push(init);
+      }
+    };
+
     const answer = await pc.createAnswer();
     await pc.setLocalDescription(answer);
-    await waitForIceGatheringComplete(pc);
 
     if (!pc.localDescription?.sdp) {
       throw new Error("missing localDescription SDP after createAnswer");
@@ -323,12 +378,11 @@ function App() {
     }
     console.log({ sdpAnswer });
     await sendSdpAnswer(sdpAnswer);
-
-    pc.onicecandidate = ({ candidate }) => {
-      if (candidate) {
-        setIceCandidates((value) => [...value, candidate]);
-      }
-    };
+    answerSent = true;
+    for (const candidate of pendingCandidates.splice(0)) {
+      await sendIceCandidate(candidate);
+    }
+    receiveServerCandidates(pc).catch(console.error);
 
     pc.onconnectionstatechange = (ev) => {
       console.log("connection state changed", ev);
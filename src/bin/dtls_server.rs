@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::env;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use mini_webrtc_rs::dtls::{DtlsEvent, DtlsState, manager::DtlsManager, session::SessionCache};
+use mini_webrtc_rs::rtc_certificate::{RtcCertificate, RtcCertificateParams};
+use mini_webrtc_rs::sdp::FingerprintType;
+use tokio::net::UdpSocket;
+use tokio::select;
+use tracing::{info, warn};
+
+const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1:4433";
+
+/// Usage: `dtls_server [bind_address] [certificate_pem]`; echoes the application data of
+/// every DTLS client back to it. Binds 127.0.0.1:4433 by default; the certificate is
+/// generated, and saved when `certificate_pem` does not exist yet.
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let bind_address = env::args()
+        .nth(1)
+        .unwrap_or(DEFAULT_BIND_ADDRESS.to_string());
+    let params = RtcCertificateParams::default();
+    let certificate = match env::args().nth(2) {
+        Some(path) => RtcCertificate::load_or_generate(path, &params)?,
+        None => RtcCertificate::generate(&params)?,
+    };
+
+    let socket = UdpSocket::bind(&bind_address)
+        .await
+        .with_context(|| format!("bind dtls server; {bind_address}"))?;
+    info!(
+        "dtls server listening on {}; fingerprint=sha-256 {}",
+        socket.local_addr()?,
+        certificate.fingerprint(FingerprintType::Sha256).fingerprint
+    );
+
+    // one endpoint per client; resumed sessions are shared among them.
+    let session_cache = Arc::new(Mutex::new(SessionCache::new()));
+    let mut managers = HashMap::<SocketAddr, DtlsManager>::new();
+    let mut buf = vec![0u8; 65535];
+    loop {
+        let next_timeout = managers
+            .values()
+            .filter_map(|manager| manager.next_timeout())
+            .min();
+        select! {
+            result = socket.recv_from(&mut buf) => {
+                let (len, peer_addr) = result?;
+                let manager = match managers.entry(peer_addr) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        let mut manager = DtlsManager::new(vec![certificate.clone()]);
+                        manager.session_cache = session_cache.clone();
+                        entry.insert(manager)
+                    }
+                };
+                let _ = manager
+                    .handle_inbound_packet(&buf[..len], peer_addr, Instant::now())
+                    .inspect_err(|err| warn!("{err:?}"));
+                // a client is kept once its ClientHello returns the cookie of the stateless
+                // HelloVerifyRequest, which proves its address and starts the handshake deadline.
+                if manager.state == DtlsState::New
+                    && let Some(mut manager) = managers.remove(&peer_addr)
+                {
+                    send_transmits(&socket, &mut manager).await;
+                }
+            }
+            _ = sleep_until_timeout(next_timeout) => {
+                for manager in managers.values_mut() {
+                    let _ = manager
+                        .handle_timeout(Instant::now())
+                        .inspect_err(|err| warn!("{err:?}"));
+                }
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+
+        for (peer_addr, manager) in managers.iter_mut() {
+            while let Some(event) = manager.poll_event() {
+                match event {
+                    DtlsEvent::Connected(_) => info!("dtls connected; peer={peer_addr}"),
+                    DtlsEvent::ApplicationData(message) => {
+                        let _ = manager
+                            .send_application_data(&message.data, Instant::now())
+                            .inspect_err(|err| warn!("{err:?}"));
+                    }
+                }
+            }
+            send_transmits(&socket, manager).await;
+        }
+        managers.retain(|peer_addr, manager| {
+            let finished = matches!(manager.state, DtlsState::Closed | DtlsState::Failed);
+            if finished {
+                info!(
+                    "dtls connection finished; peer={peer_addr}, state={:?}",
+                    manager.state
+                );
+            }
+            !finished
+        });
+    }
+
+    for manager in managers.values_mut() {
+        manager.close(Instant::now())?;
+        send_transmits(&socket, manager).await;
+    }
+    Ok(())
+}
+
+// a datagram that cannot be sent to one client must not stop the others.
+async fn send_transmits(socket: &UdpSocket, manager: &mut DtlsManager) {
+    while let Some(message) = manager.poll_transmit() {
+        if let Err(err) = socket.send_to(&message.data, message.peer_addr).await {
+            warn!(
+                "failed to send datagram; peer={}, {err:?}",
+                message.peer_addr
+            );
+        }
+    }
+}
+
+async fn sleep_until_timeout(timeout: Option<Instant>) {
+    match timeout {
+        Some(timeout) => tokio::time::sleep_until(timeout.into()).await,
+        None => std::future::pending().await,
+    }
+}
@@ -0,0 +1,39 @@
+use std::env;
+
+use anyhow::{Context, Result};
+use mini_webrtc_rs::stun_server::{DEFAULT_REALM, StunServer, StunServerOptions, TurnRelayOptions};
+use tokio::select;
+
+// TURN relay is enabled when both username and password are set.
+const TURN_USERNAME_ENV: &str = "MINI_WEBRTC_TURN_USERNAME";
+const TURN_PASSWORD_ENV: &str = "MINI_WEBRTC_TURN_PASSWORD";
+const TURN_REALM_ENV: &str = "MINI_WEBRTC_TURN_REALM";
+
+/// Usage: `stun_server [bind_address]`; binds 0.0.0.0:3478 by default and `[::]:3478` serves
+/// both IPv4 and IPv6 clients.
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let mut options = StunServerOptions::default();
+    if let Some(bind_address) = env::args().nth(1) {
+        options.bind_address = bind_address
+            .parse()
+            .with_context(|| format!("parse bind address; {bind_address}"))?;
+    }
+    if let (Ok(username), Ok(password)) = (env::var(TURN_USERNAME_ENV), env::var(TURN_PASSWORD_ENV))
+    {
+        options.turn = Some(TurnRelayOptions {
+            realm: env::var(TURN_REALM_ENV).unwrap_or(DEFAULT_REALM.to_string()),
+            relay_ip: options.bind_address.ip(),
+            ..TurnRelayOptions::new(&username, &password)
+        });
+    }
+
+    let server = StunServer::bind(options).await?;
+    select! {
+        result = server.run() => result?,
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    Ok(())
+}
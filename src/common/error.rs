@@ -8,4 +8,6 @@ pub enum MiniWebrtcRsError {
     InvalidEnumVariantError { enum_name: String, value: String },
     #[error("not implemented; {message:?}")]
     NotImplementedError { message: String },
+    #[error("invalid signaling state; cannot {operation} in {state:?}.")]
+    InvalidSignalingStateError { state: String, operation: String },
 }
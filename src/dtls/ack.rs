@@ -0,0 +1,38 @@
+use crate::common::buffer::{BufReader, BufWriter};
+use anyhow::Result;
+
+// https://datatracker.ietf.org/doc/html/rfc9147#section-7
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordNumber {
+    pub epoch: u64,
+    pub sequence_number: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ack {
+    pub record_numbers: Vec<RecordNumber>,
+}
+
+impl Ack {
+    pub fn decode(reader: &mut BufReader) -> Result<Self> {
+        let length = reader.read_u16()? as usize;
+        let mut record_numbers = vec![];
+        for _ in 0..length / 16 {
+            let epoch = ((reader.read_u32()? as u64) << 32) + reader.read_u32()? as u64;
+            let sequence_number = ((reader.read_u32()? as u64) << 32) + reader.read_u32()? as u64;
+            record_numbers.push(RecordNumber {
+                epoch,
+                sequence_number,
+            });
+        }
+        Ok(Self { record_numbers })
+    }
+
+    pub fn encode(&self, writer: &mut BufWriter) {
+        writer.write_u16((self.record_numbers.len() * 16) as u16);
+        for record_number in &self.record_numbers {
+            writer.write_bytes(&record_number.epoch.to_be_bytes());
+            writer.write_bytes(&record_number.sequence_number.to_be_bytes());
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::common::buffer::{BufReader, BufWriter};
+use anyhow::Result;
+use mini_webrtc_derive::FromPrimitive;
+
+// https://datatracker.ietf.org/doc/html/rfc5246#section-7.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[from(type = "u8", default = "Unsupported")]
+pub enum AlertLevel {
+    Warning = 1,
+    Fatal = 2,
+    Unsupported = 255,
+}
+
+impl From<AlertLevel> for u8 {
+    fn from(value: AlertLevel) -> Self {
+        value as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[from(type = "u8", default = "Unsupported")]
+pub enum AlertDescription {
+    CloseNotify = 0,
+    UnexpectedMessage = 10,
+    BadRecordMac = 20,
+    HandshakeFailure = 40,
+    BadCertificate = 42,
+    IllegalParameter = 47,
+    DecodeError = 50,
+    DecryptError = 51,
+    InternalError = 80,
+    Unsupported = 255,
+}
+
+impl From<AlertDescription> for u8 {
+    fn from(value: AlertDescription) -> Self {
+        value as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alert {
+    pub level: AlertLevel,
+    pub description: AlertDescription,
+}
+
+impl Alert {
+    pub fn close_notify() -> Self {
+        Self {
+            level: AlertLevel::Warning,
+            description: AlertDescription::CloseNotify,
+        }
+    }
+
+    pub fn fatal(description: AlertDescription) -> Self {
+        Self {
+            level: AlertLevel::Fatal,
+            description,
+        }
+    }
+
+    pub fn decode(reader: &mut BufReader) -> Result<Self> {
+        let level = AlertLevel::from(reader.read_u8()?);
+        let description = AlertDescription::from(reader.read_u8()?);
+        Ok(Self { level, description })
+    }
+
+    pub fn encode(&self, writer: &mut BufWriter) {
+        writer.write_u8(self.level.into());
+        writer.write_u8(self.description.into());
+    }
+}
@@ -1,10 +1,23 @@
 use mini_webrtc_derive::FromPrimitive;
 
+use crate::dtls::{HashAlgorithm, SignatureAlgorithm};
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive)]
 #[from(type = "u16", default = "Unsupported")]
 pub enum CipherSuiteId {
     TlsEmptyRenegotiationInfoScsv = 0x00ff,
+    // https://datatracker.ietf.org/doc/html/rfc8446#appendix-B.4
+    TlsAes128GcmSha256 = 0x1301,
+    TlsAes256GcmSha384 = 0x1302,
+    TlsChacha20Poly1305Sha256 = 0x1303,
+    // https://datatracker.ietf.org/doc/html/rfc5289#section-3.2
     TlsEcdheEcdsaWithAes128GcmSha256 = 0xc02b,
+    TlsEcdheEcdsaWithAes256GcmSha384 = 0xc02c,
+    TlsEcdheRsaWithAes128GcmSha256 = 0xc02f,
+    TlsEcdheRsaWithAes256GcmSha384 = 0xc030,
+    // https://datatracker.ietf.org/doc/html/rfc7905#section-2
+    TlsEcdheRsaWithChacha20Poly1305Sha256 = 0xcca8,
+    TlsEcdheEcdsaWithChacha20Poly1305Sha256 = 0xcca9,
     Unsupported = 0x0000,
 }
 
@@ -13,3 +26,87 @@ impl From<CipherSuiteId> for u16 {
         value as u16
     }
 }
+
+/// Bulk cipher of the records protected by a cipher suite.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RecordCipherType {
+    Aes128Gcm,
+    Aes256Gcm,
+    Chacha20Poly1305,
+}
+
+impl RecordCipherType {
+    pub fn key_length(&self) -> usize {
+        match self {
+            Self::Aes128Gcm => 16,
+            Self::Aes256Gcm | Self::Chacha20Poly1305 => 32,
+        }
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc5288#section-3
+    // https://datatracker.ietf.org/doc/html/rfc7905#section-2
+    pub fn iv_length(&self) -> usize {
+        match self {
+            Self::Aes128Gcm | Self::Aes256Gcm => 4,
+            Self::Chacha20Poly1305 => 12,
+        }
+    }
+}
+
+impl CipherSuiteId {
+    /// Cipher suites this endpoint implements, in order of preference.
+    pub const SUPPORTED: [CipherSuiteId; 9] = [
+        CipherSuiteId::TlsAes128GcmSha256,
+        CipherSuiteId::TlsChacha20Poly1305Sha256,
+        CipherSuiteId::TlsAes256GcmSha384,
+        CipherSuiteId::TlsEcdheEcdsaWithAes128GcmSha256,
+        CipherSuiteId::TlsEcdheRsaWithAes128GcmSha256,
+        CipherSuiteId::TlsEcdheEcdsaWithChacha20Poly1305Sha256,
+        CipherSuiteId::TlsEcdheRsaWithChacha20Poly1305Sha256,
+        CipherSuiteId::TlsEcdheEcdsaWithAes256GcmSha384,
+        CipherSuiteId::TlsEcdheRsaWithAes256GcmSha384,
+    ];
+
+    /// Whether this is a (D)TLS 1.3 suite, which only names the AEAD and the HKDF hash.
+    pub fn is_dtls13(&self) -> bool {
+        matches!(
+            self,
+            Self::TlsAes128GcmSha256 | Self::TlsAes256GcmSha384 | Self::TlsChacha20Poly1305Sha256
+        )
+    }
+
+    /// Signature algorithm of the server certificate required by a DTLS 1.2 suite.
+    pub fn signature_algorithm(&self) -> SignatureAlgorithm {
+        match self {
+            Self::TlsEcdheEcdsaWithAes128GcmSha256
+            | Self::TlsEcdheEcdsaWithAes256GcmSha384
+            | Self::TlsEcdheEcdsaWithChacha20Poly1305Sha256 => SignatureAlgorithm::Ecdsa,
+            Self::TlsEcdheRsaWithAes128GcmSha256
+            | Self::TlsEcdheRsaWithAes256GcmSha384
+            | Self::TlsEcdheRsaWithChacha20Poly1305Sha256 => SignatureAlgorithm::Rsa,
+            _ => SignatureAlgorithm::Unsupported,
+        }
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc5246#section-5
+    pub fn prf_hash(&self) -> HashAlgorithm {
+        match self {
+            Self::TlsEcdheEcdsaWithAes256GcmSha384
+            | Self::TlsEcdheRsaWithAes256GcmSha384
+            | Self::TlsAes256GcmSha384 => HashAlgorithm::Sha384,
+            _ => HashAlgorithm::Sha256,
+        }
+    }
+
+    pub fn record_cipher_type(&self) -> RecordCipherType {
+        match self {
+            Self::TlsEcdheEcdsaWithAes256GcmSha384
+            | Self::TlsEcdheRsaWithAes256GcmSha384
+            | Self::TlsAes256GcmSha384 => RecordCipherType::Aes256Gcm,
+            Self::TlsEcdheEcdsaWithChacha20Poly1305Sha256
+            | Self::TlsEcdheRsaWithChacha20Poly1305Sha256
+            | Self::TlsChacha20Poly1305Sha256 => RecordCipherType::Chacha20Poly1305,
+            _ => RecordCipherType::Aes128Gcm,
+        }
+    }
+}
@@ -1,40 +1,86 @@
-use aes_gcm::{AeadInPlace, Aes128Gcm, Key, KeyInit, Nonce};
+use aes::cipher::BlockCipherEncrypt;
+use aes::{Aes128, Aes256};
+use aes_gcm::{AeadInPlace, Aes128Gcm, Aes256Gcm, KeyInit, Nonce};
 use anyhow::{Result, anyhow};
-use hmac::digest::array::Array;
-use hmac::{Hmac, KeyInit as HmacKeyInit, Mac, digest::consts::U32};
-use sha2::Sha256;
-use x25519_dalek::SharedSecret;
-
+use chacha20::ChaCha20;
+use chacha20::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use chacha20poly1305::ChaCha20Poly1305;
+use hmac::{Hmac, KeyInit as HmacKeyInit, Mac};
+use p256::ecdsa::signature::hazmat::PrehashVerifier;
+use p256::elliptic_curve::rand_core::OsRng;
+use p256::pkcs8::DecodePublicKey;
+use rcgen::KeyPair;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::{Pkcs1v15Sign, Pss, RsaPrivateKey, RsaPublicKey};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::common::buffer::BufWriter;
+use crate::dtls::cipher_suite::{CipherSuiteId, RecordCipherType};
 use crate::dtls::handshake::random::Random;
-use crate::dtls::record_header::RecordHeader;
+use crate::dtls::record_header::{RecordHeader, UnifiedHeader};
+use crate::dtls::{AlgoPair, HashAlgorithm, SignatureAlgorithm};
 use rand::Rng;
 
-type HmacSha256 = Hmac<Sha256>;
-
 const PRF_EXTENDED_MASTER_SECRET_LABEL: &str = "extended master secret";
 const PRF_MASTER_SECRET_LABEL: &str = "master secret";
 const PRF_KEY_EXPANSION_LABEL: &str = "key expansion";
 const PRF_CLIENT_FINISHED_LABEL: &str = "client finished";
 const PRF_SERVER_FINISHED_LABEL: &str = "server finished";
+// https://datatracker.ietf.org/doc/html/rfc9147#section-5.9
+const HKDF_LABEL_PREFIX: &str = "dtls13";
+
+pub fn digest(hash: HashAlgorithm, data: &[u8]) -> Result<Vec<u8>> {
+    match hash {
+        HashAlgorithm::Sha256 => Ok(Sha256::digest(data).to_vec()),
+        HashAlgorithm::Sha384 => Ok(Sha384::digest(data).to_vec()),
+        HashAlgorithm::Sha512 => Ok(Sha512::digest(data).to_vec()),
+        HashAlgorithm::Intrinsic | HashAlgorithm::Unsupported => {
+            Err(anyhow!("unsupported hash algorithm; {hash:?}"))
+        }
+    }
+}
 
-pub fn hmac_sha(key: &[u8], data: &[u8]) -> Vec<u8> {
-    let mut mac = <HmacSha256>::new_from_slice(key).unwrap();
-    mac.update(data);
-    mac.finalize().into_bytes().to_vec()
+fn hash_length(hash: HashAlgorithm) -> usize {
+    match hash {
+        HashAlgorithm::Sha384 => 48,
+        HashAlgorithm::Sha512 => 64,
+        _ => 32,
+    }
+}
+
+pub fn hmac_sha(hash: HashAlgorithm, key: &[u8], data: &[u8]) -> Vec<u8> {
+    match hash {
+        HashAlgorithm::Sha384 => {
+            let mut mac = <Hmac<Sha384>>::new_from_slice(key).unwrap();
+            mac.update(data);
+            mac.finalize().into_bytes().to_vec()
+        }
+        // the PRF of TLS 1.2 cipher suites is SHA-256 unless stated otherwise.
+        _ => {
+            let mut mac = <Hmac<Sha256>>::new_from_slice(key).unwrap();
+            mac.update(data);
+            mac.finalize().into_bytes().to_vec()
+        }
+    }
 }
 
 // prf (pseudorandom function)
-pub fn prf_p_hash(secret: &[u8], seed: &[u8], requested_bytes: usize) -> Vec<u8> {
+// https://datatracker.ietf.org/doc/html/rfc5246#section-5
+pub fn prf_p_hash(
+    hash: HashAlgorithm,
+    secret: &[u8],
+    seed: &[u8],
+    requested_bytes: usize,
+) -> Vec<u8> {
     let mut last_round = seed.to_vec();
     let mut out: Vec<u8> = vec![];
 
     // key stretching
-    let iterations = (requested_bytes as f64 / (256 / 8) as f64).ceil() as usize;
-    for _ in 0..iterations {
-        last_round = hmac_sha(secret, &last_round);
+    while out.len() < requested_bytes {
+        last_round = hmac_sha(hash, secret, &last_round);
         let mut last_round_with_seed = last_round.clone();
         last_round_with_seed.extend_from_slice(seed);
-        let with_secret = hmac_sha(secret, &last_round_with_seed);
+        let with_secret = hmac_sha(hash, secret, &last_round_with_seed);
         out.extend_from_slice(&with_secret);
     }
 
@@ -43,20 +89,22 @@ pub fn prf_p_hash(secret: &[u8], seed: &[u8], requested_bytes: usize) -> Vec<u8>
 
 // https://datatracker.ietf.org/doc/html/rfc7627#autoid-4
 pub fn generate_extended_master_secret(
-    pre_master_secret: SharedSecret,
-    handshake_hash: Array<u8, U32>,
+    hash: HashAlgorithm,
+    pre_master_secret: &[u8],
+    handshake_hash: &[u8],
 ) -> Vec<u8> {
     let seed = vec![
         PRF_EXTENDED_MASTER_SECRET_LABEL.as_bytes().to_vec(),
         handshake_hash.to_vec(),
     ]
     .concat();
-    prf_p_hash(pre_master_secret.as_bytes(), &seed, 48)
+    prf_p_hash(hash, pre_master_secret, &seed, 48)
 }
 
 // https://datatracker.ietf.org/doc/html/rfc5246#section-8.1
 pub fn generate_master_secret(
-    pre_master_secret: SharedSecret,
+    hash: HashAlgorithm,
+    pre_master_secret: &[u8],
     client_random: &Random,
     server_random: &Random,
 ) -> Vec<u8> {
@@ -66,31 +114,42 @@ pub fn generate_master_secret(
         server_random.to_bytes().to_vec(),
     ]
     .concat();
-    prf_p_hash(pre_master_secret.as_bytes(), &seed, 48)
+    prf_p_hash(hash, pre_master_secret, &seed, 48)
 }
 
-pub struct Aes128GcmEncryptionKeys {
+pub struct EncryptionKeys {
     pub client_write_key: Vec<u8>,
     pub server_write_key: Vec<u8>,
     pub client_write_iv: Vec<u8>,
     pub server_write_iv: Vec<u8>,
 }
 
+// https://datatracker.ietf.org/doc/html/rfc5246#section-6.3
 // https://datatracker.ietf.org/doc/html/rfc5289#section-3.2
 // https://datatracker.ietf.org/doc/html/rfc5288#section-3
 // https://datatracker.ietf.org/doc/html/rfc5116
-impl Aes128GcmEncryptionKeys {
-    pub fn new(master_secret: &[u8], client_random: &Random, server_random: &Random) -> Self {
+impl EncryptionKeys {
+    pub fn new(
+        cipher_suite_id: CipherSuiteId,
+        master_secret: &[u8],
+        client_random: &Random,
+        server_random: &Random,
+    ) -> Self {
         let mut seed = PRF_KEY_EXPANSION_LABEL.as_bytes().to_vec();
         seed.extend_from_slice(&server_random.to_bytes());
         seed.extend_from_slice(&client_random.to_bytes());
 
-        let prf_key_len = 16;
-        let prf_iv_len = 4;
+        let prf_key_len = cipher_suite_id.record_cipher_type().key_length();
+        let prf_iv_len = cipher_suite_id.record_cipher_type().iv_length();
 
-        let key_material = prf_p_hash(master_secret, &seed, 2 * prf_key_len + 2 * prf_iv_len);
+        let key_material = prf_p_hash(
+            cipher_suite_id.prf_hash(),
+            master_secret,
+            &seed,
+            2 * prf_key_len + 2 * prf_iv_len,
+        );
 
-        Aes128GcmEncryptionKeys {
+        EncryptionKeys {
             client_write_key: key_material[..prf_key_len].to_vec(),
             server_write_key: key_material[prf_key_len..prf_key_len * 2].to_vec(),
             client_write_iv: key_material[prf_key_len * 2..prf_key_len * 2 + prf_iv_len].to_vec(),
@@ -106,29 +165,121 @@ const GCM_EXPLICIT_NONCE_LENGTH: usize = 8;
 const GCM_TAG_LENGTH: usize = 16;
 
 #[derive(Clone)]
-pub struct Gcm {
-    local_gcm: Aes128Gcm,
-    remote_gcm: Aes128Gcm,
+enum Aead {
+    Aes128Gcm(Box<Aes128Gcm>),
+    Aes256Gcm(Box<Aes256Gcm>),
+    Chacha20Poly1305(ChaCha20Poly1305),
+}
+
+impl Aead {
+    fn new(cipher_type: RecordCipherType, key: &[u8]) -> Result<Self> {
+        let invalid_key = |_| anyhow!("invalid {cipher_type:?} key length; {}", key.len());
+        Ok(match cipher_type {
+            RecordCipherType::Aes128Gcm => Aead::Aes128Gcm(Box::new(
+                Aes128Gcm::new_from_slice(key).map_err(invalid_key)?,
+            )),
+            RecordCipherType::Aes256Gcm => Aead::Aes256Gcm(Box::new(
+                Aes256Gcm::new_from_slice(key).map_err(invalid_key)?,
+            )),
+            RecordCipherType::Chacha20Poly1305 => {
+                Aead::Chacha20Poly1305(ChaCha20Poly1305::new_from_slice(key).map_err(invalid_key)?)
+            }
+        })
+    }
+
+    fn encrypt_in_place(&self, nonce: &[u8], aad: &[u8], buffer: &mut Vec<u8>) -> Result<()> {
+        let nonce = Nonce::from_slice(nonce);
+        match self {
+            Aead::Aes128Gcm(aead) => aead.encrypt_in_place(nonce, aad, buffer),
+            Aead::Aes256Gcm(aead) => aead.encrypt_in_place(nonce, aad, buffer),
+            Aead::Chacha20Poly1305(aead) => aead.encrypt_in_place(nonce, aad, buffer),
+        }
+        .map_err(|err| anyhow!("failed to encrypt payload: {err:?}"))
+    }
+
+    fn decrypt_in_place(&self, nonce: &[u8], aad: &[u8], buffer: &mut Vec<u8>) -> Result<()> {
+        let nonce = Nonce::from_slice(nonce);
+        match self {
+            Aead::Aes128Gcm(aead) => aead.decrypt_in_place(nonce, aad, buffer),
+            Aead::Aes256Gcm(aead) => aead.decrypt_in_place(nonce, aad, buffer),
+            Aead::Chacha20Poly1305(aead) => aead.decrypt_in_place(nonce, aad, buffer),
+        }
+        .map_err(|err| anyhow!("failed to decrypt dtls message; {err:?}"))
+    }
+
+    // AES-GCM records carry the explicit part of the nonce; ChaCha20-Poly1305 ones derive it
+    // from the sequence number.
+    fn explicit_nonce_length(&self) -> usize {
+        match self {
+            Aead::Aes128Gcm(_) | Aead::Aes256Gcm(_) => GCM_EXPLICIT_NONCE_LENGTH,
+            Aead::Chacha20Poly1305(_) => 0,
+        }
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc5246#section-6.2.3.3
+fn additional_data(record_header: &RecordHeader, plaintext_len: usize) -> Vec<u8> {
+    let mut additional_data = vec![0u8; 13];
+
+    additional_data[..8].copy_from_slice(&record_header.sequence_number.to_be_bytes()); // 48bit
+    additional_data[..2].copy_from_slice(&record_header.epoch.to_be_bytes());
+
+    additional_data[8] = record_header.content_type as u8;
+    let version: u16 = record_header.version.into();
+    additional_data[9..11].copy_from_slice(&version.to_be_bytes());
+    additional_data[11..].copy_from_slice(&(plaintext_len as u16).to_be_bytes());
+
+    additional_data
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7905#section-2
+fn chacha20_poly1305_nonce(write_iv: &[u8], record_header: &RecordHeader) -> Vec<u8> {
+    let mut sequence_number = record_header.sequence_number.to_be_bytes();
+    sequence_number[..2].copy_from_slice(&record_header.epoch.to_be_bytes());
+    let mut nonce = write_iv.to_vec();
+    for (nonce, sequence_number) in nonce[4..].iter_mut().zip(sequence_number) {
+        *nonce ^= sequence_number;
+    }
+    nonce
+}
+
+/// AEAD protecting the records of the negotiated cipher suite.
+#[derive(Clone)]
+pub struct RecordCipher {
+    local_aead: Aead,
+    remote_aead: Aead,
     local_write_iv: Vec<u8>,
     remote_write_iv: Vec<u8>,
 }
 
-impl Gcm {
+impl RecordCipher {
     pub fn new(
+        cipher_suite_id: CipherSuiteId,
         local_key: &[u8],
         local_write_iv: &[u8],
         remote_key: &[u8],
         remote_write_iv: &[u8],
-    ) -> Self {
-        Self {
-            local_gcm: Aes128Gcm::new(Key::<Aes128Gcm>::from_slice(local_key)),
-            remote_gcm: Aes128Gcm::new(Key::<Aes128Gcm>::from_slice(remote_key)),
+    ) -> Result<Self> {
+        let cipher_type = cipher_suite_id.record_cipher_type();
+        Ok(Self {
+            local_aead: Aead::new(cipher_type, local_key)?,
+            remote_aead: Aead::new(cipher_type, remote_key)?,
             local_write_iv: local_write_iv.to_vec(),
             remote_write_iv: remote_write_iv.to_vec(),
-        }
+        })
     }
 
     pub fn encrypt(&self, record_header: RecordHeader, payload: Vec<u8>) -> Result<Vec<u8>> {
+        let additional_data = additional_data(&record_header, payload.len());
+        let mut encrypted_payload = payload;
+
+        if let Aead::Chacha20Poly1305(_) = self.local_aead {
+            let nonce = chacha20_poly1305_nonce(&self.local_write_iv, &record_header);
+            self.local_aead
+                .encrypt_in_place(&nonce, &additional_data, &mut encrypted_payload)?;
+            return Ok(encrypted_payload);
+        }
+
         // https://datatracker.ietf.org/doc/html/rfc5288#section-3
         let implicit_nonce = self.local_write_iv[..4].to_vec();
         let explicit_nonce = {
@@ -139,29 +290,8 @@ impl Gcm {
         };
         let nonce = vec![implicit_nonce, explicit_nonce.clone()].concat();
 
-        let additional_data = {
-            // https://datatracker.ietf.org/doc/html/rfc5246#section-6.2.3.3
-            let mut additional_data = vec![0u8; 13];
-
-            additional_data[..8].copy_from_slice(&record_header.sequence_number.to_be_bytes()); // 48bit
-            additional_data[..2].copy_from_slice(&record_header.epoch.to_be_bytes());
-
-            additional_data[8] = record_header.content_type as u8;
-            let version: u16 = record_header.version.into();
-            additional_data[9..11].copy_from_slice(&version.to_be_bytes());
-            additional_data[11..].copy_from_slice(&(record_header.length).to_be_bytes());
-
-            additional_data
-        };
-
-        let mut encrypted_payload = payload;
-        self.local_gcm
-            .encrypt_in_place(
-                Nonce::from_slice(&nonce),
-                &additional_data,
-                &mut encrypted_payload,
-            )
-            .map_err(|err| anyhow!("failed to encrypt payload: {err:?}"))?;
+        self.local_aead
+            .encrypt_in_place(&nonce, &additional_data, &mut encrypted_payload)?;
         // https://datatracker.ietf.org/doc/html/rfc5246#section-6.2.3.3
         let encrypted_record = vec![explicit_nonce, encrypted_payload].concat();
 
@@ -169,42 +299,31 @@ impl Gcm {
     }
 
     pub fn decrypt(&self, record_header: RecordHeader, payload: &[u8]) -> Result<Vec<u8>> {
-        if payload.len() < GCM_EXPLICIT_NONCE_LENGTH + GCM_TAG_LENGTH {
+        let explicit_nonce_length = self.remote_aead.explicit_nonce_length();
+        if payload.len() < explicit_nonce_length + GCM_TAG_LENGTH {
             anyhow::bail!(
                 "encrypted payload too short; expected at least {} bytes, got {}",
-                GCM_EXPLICIT_NONCE_LENGTH + GCM_TAG_LENGTH,
+                explicit_nonce_length + GCM_TAG_LENGTH,
                 payload.len()
             );
         }
 
-        let implicit_nonce = self.remote_write_iv[..4].to_vec();
-        let explicit_nonce = payload[..GCM_EXPLICIT_NONCE_LENGTH].to_vec();
-        let nonce = vec![implicit_nonce, explicit_nonce].concat();
-        let plaintext_len = payload.len() - GCM_EXPLICIT_NONCE_LENGTH - GCM_TAG_LENGTH;
-
-        let additional_data = {
-            // https://datatracker.ietf.org/doc/html/rfc5246#section-6.2.3.3
-            let mut additional_data = vec![0u8; 13];
-
-            additional_data[..8].copy_from_slice(&record_header.sequence_number.to_be_bytes()); // 48bit
-            additional_data[..2].copy_from_slice(&record_header.epoch.to_be_bytes());
-
-            additional_data[8] = record_header.content_type as u8;
-            let version: u16 = record_header.version.into();
-            additional_data[9..11].copy_from_slice(&version.to_be_bytes());
-            additional_data[11..].copy_from_slice(&(plaintext_len as u16).to_be_bytes());
-
-            additional_data
+        let nonce = match self.remote_aead {
+            Aead::Chacha20Poly1305(_) => {
+                chacha20_poly1305_nonce(&self.remote_write_iv, &record_header)
+            }
+            _ => {
+                let implicit_nonce = self.remote_write_iv[..4].to_vec();
+                let explicit_nonce = payload[..GCM_EXPLICIT_NONCE_LENGTH].to_vec();
+                [implicit_nonce, explicit_nonce].concat()
+            }
         };
+        let plaintext_len = payload.len() - explicit_nonce_length - GCM_TAG_LENGTH;
+        let additional_data = additional_data(&record_header, plaintext_len);
 
-        let mut decrypted_payload = payload[GCM_EXPLICIT_NONCE_LENGTH..].to_vec();
-        self.remote_gcm
-            .decrypt_in_place(
-                Nonce::from_slice(&nonce),
-                &additional_data,
-                &mut decrypted_payload,
-            )
-            .map_err(|err| anyhow!("failed to decrypt dtls message; {err:?}"))?;
+        let mut decrypted_payload = payload[explicit_nonce_length..].to_vec();
+        self.remote_aead
+            .decrypt_in_place(&nonce, &additional_data, &mut decrypted_payload)?;
 
         Ok(decrypted_payload)
     }
@@ -212,10 +331,12 @@ impl Gcm {
 
 // https://datatracker.ietf.org/doc/html/rfc5246#autoid-49
 pub fn generate_client_verify_data(
+    hash: HashAlgorithm,
     master_secret: &[u8],
     handshake_messages_hash: &[u8],
 ) -> Vec<u8> {
     prf_p_hash(
+        hash,
         master_secret,
         &vec![
             PRF_CLIENT_FINISHED_LABEL.as_bytes().to_vec(),
@@ -227,10 +348,12 @@ pub fn generate_client_verify_data(
 }
 
 pub fn generate_server_verify_data(
+    hash: HashAlgorithm,
     master_secret: &[u8],
     handshake_messages_hash: &[u8],
 ) -> Vec<u8> {
     prf_p_hash(
+        hash,
         master_secret,
         &vec![
             PRF_SERVER_FINISHED_LABEL.as_bytes().to_vec(),
@@ -240,3 +363,430 @@ pub fn generate_server_verify_data(
         12,
     )
 }
+
+const TICKET_KEY_NAME_LENGTH: usize = 16;
+
+/// AES-256-GCM key sealing session tickets; its name tells which key sealed a ticket.
+// https://datatracker.ietf.org/doc/html/rfc5077#section-4
+pub struct TicketKey {
+    pub name: [u8; TICKET_KEY_NAME_LENGTH],
+    aead: Aes256Gcm,
+}
+
+impl Default for TicketKey {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TicketKey {
+    pub fn new() -> Self {
+        let mut name = [0u8; TICKET_KEY_NAME_LENGTH];
+        rand::fill(&mut name);
+        Self {
+            name,
+            aead: Aes256Gcm::new(&Aes256Gcm::generate_key(&mut OsRng)),
+        }
+    }
+
+    /// Encrypts the session state into `key_name || nonce || ciphertext`.
+    pub fn seal(&self, state: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; GCM_NONCE_LENGTH];
+        rand::fill(&mut nonce);
+        let mut ciphertext = state.to_vec();
+        self.aead
+            .encrypt_in_place(Nonce::from_slice(&nonce), &self.name, &mut ciphertext)
+            .map_err(|err| anyhow!("failed to seal session ticket; {err:?}"))?;
+        Ok([&self.name[..], &nonce, &ciphertext].concat())
+    }
+
+    pub fn open(&self, ticket: &[u8]) -> Result<Vec<u8>> {
+        let state_offset = TICKET_KEY_NAME_LENGTH + GCM_NONCE_LENGTH;
+        if ticket.len() < state_offset + GCM_TAG_LENGTH
+            || ticket[..TICKET_KEY_NAME_LENGTH] != self.name
+        {
+            anyhow::bail!("session ticket of another key.");
+        }
+        let mut state = ticket[state_offset..].to_vec();
+        self.aead
+            .decrypt_in_place(
+                Nonce::from_slice(&ticket[TICKET_KEY_NAME_LENGTH..state_offset]),
+                &self.name,
+                &mut state,
+            )
+            .map_err(|err| anyhow!("failed to open session ticket; {err:?}"))?;
+        Ok(state)
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc5869#section-2.2
+pub fn hkdf_extract(hash: HashAlgorithm, salt: &[u8], input_key_material: &[u8]) -> Vec<u8> {
+    hmac_sha(hash, salt, input_key_material)
+}
+
+// https://datatracker.ietf.org/doc/html/rfc5869#section-2.3
+pub fn hkdf_expand(hash: HashAlgorithm, secret: &[u8], info: &[u8], length: usize) -> Vec<u8> {
+    let mut last_round: Vec<u8> = vec![];
+    let mut out: Vec<u8> = vec![];
+    let mut counter = 1u8;
+    while out.len() < length {
+        last_round = hmac_sha(hash, secret, &[&last_round, info, &[counter]].concat());
+        out.extend_from_slice(&last_round);
+        counter += 1;
+    }
+    out.truncate(length);
+    out
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8446#section-7.1
+pub fn hkdf_expand_label(
+    hash: HashAlgorithm,
+    secret: &[u8],
+    label: &str,
+    context: &[u8],
+    length: usize,
+) -> Vec<u8> {
+    let label = [HKDF_LABEL_PREFIX.as_bytes(), label.as_bytes()].concat();
+    let mut writer = BufWriter::new();
+    writer.write_u16(length as u16);
+    writer.write_u8(label.len() as u8);
+    writer.write_bytes(&label);
+    writer.write_u8(context.len() as u8);
+    writer.write_bytes(context);
+    hkdf_expand(hash, secret, writer.buf_ref(), length)
+}
+
+pub fn derive_secret(
+    hash: HashAlgorithm,
+    secret: &[u8],
+    label: &str,
+    transcript_hash: &[u8],
+) -> Vec<u8> {
+    hkdf_expand_label(hash, secret, label, transcript_hash, hash_length(hash))
+}
+
+/// Secrets of the DTLS 1.3 key schedule.
+// https://datatracker.ietf.org/doc/html/rfc8446#section-7.1
+pub struct KeySchedule {
+    hash: HashAlgorithm,
+    master_secret: Vec<u8>,
+    pub client_handshake_traffic_secret: Vec<u8>,
+    pub server_handshake_traffic_secret: Vec<u8>,
+    pub client_application_traffic_secret: Vec<u8>,
+    pub server_application_traffic_secret: Vec<u8>,
+    pub exporter_master_secret: Vec<u8>,
+}
+
+impl KeySchedule {
+    /// Derives the handshake traffic secrets from the ECDHE shared secret and the hash of
+    /// ClientHello..ServerHello.
+    pub fn new(hash: HashAlgorithm, shared_secret: &[u8], hello_hash: &[u8]) -> Result<Self> {
+        let zeros = vec![0u8; hash_length(hash)];
+        let empty_hash = digest(hash, &[])?;
+        let early_secret = hkdf_extract(hash, &zeros, &zeros);
+        let handshake_secret = hkdf_extract(
+            hash,
+            &derive_secret(hash, &early_secret, "derived", &empty_hash),
+            shared_secret,
+        );
+        let master_secret = hkdf_extract(
+            hash,
+            &derive_secret(hash, &handshake_secret, "derived", &empty_hash),
+            &zeros,
+        );
+        Ok(Self {
+            hash,
+            master_secret,
+            client_handshake_traffic_secret: derive_secret(
+                hash,
+                &handshake_secret,
+                "c hs traffic",
+                hello_hash,
+            ),
+            server_handshake_traffic_secret: derive_secret(
+                hash,
+                &handshake_secret,
+                "s hs traffic",
+                hello_hash,
+            ),
+            client_application_traffic_secret: vec![],
+            server_application_traffic_secret: vec![],
+            exporter_master_secret: vec![],
+        })
+    }
+
+    /// Derives the application traffic and exporter secrets from the hash of
+    /// ClientHello..server Finished.
+    pub fn derive_application_secrets(&mut self, handshake_hash: &[u8]) {
+        self.client_application_traffic_secret = derive_secret(
+            self.hash,
+            &self.master_secret,
+            "c ap traffic",
+            handshake_hash,
+        );
+        self.server_application_traffic_secret = derive_secret(
+            self.hash,
+            &self.master_secret,
+            "s ap traffic",
+            handshake_hash,
+        );
+        self.exporter_master_secret =
+            derive_secret(self.hash, &self.master_secret, "exp master", handshake_hash);
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8446#section-4.4.4
+    pub fn finished_verify_data(&self, traffic_secret: &[u8], transcript_hash: &[u8]) -> Vec<u8> {
+        let finished_key = hkdf_expand_label(
+            self.hash,
+            traffic_secret,
+            "finished",
+            &[],
+            hash_length(self.hash),
+        );
+        hmac_sha(self.hash, &finished_key, transcript_hash)
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8446#section-7.5
+    pub fn export_keying_material(&self, label: &str, length: usize) -> Result<Vec<u8>> {
+        let empty_hash = digest(self.hash, &[])?;
+        let secret = derive_secret(self.hash, &self.exporter_master_secret, label, &empty_hash);
+        Ok(hkdf_expand_label(
+            self.hash,
+            &secret,
+            "exporter",
+            &empty_hash,
+            length,
+        ))
+    }
+}
+
+const TRAFFIC_IV_LENGTH: usize = 12;
+
+// https://datatracker.ietf.org/doc/html/rfc9147#section-4.2.2
+fn reconstruct_sequence_number(next: u64, low_bits: u16, length: usize) -> u64 {
+    let window = 1u64 << (length * 8);
+    let candidate = (next & !(window - 1)) | low_bits as u64;
+    [
+        candidate.checked_sub(window),
+        Some(candidate),
+        candidate.checked_add(window),
+    ]
+    .into_iter()
+    .flatten()
+    .min_by_key(|sequence_number| sequence_number.abs_diff(next))
+    .unwrap_or(candidate)
+}
+
+enum SequenceNumberKey {
+    Aes128(Box<Aes128>),
+    Aes256(Box<Aes256>),
+    Chacha20(Vec<u8>),
+}
+
+/// AEAD and record number encryption of one direction of a DTLS 1.3 epoch.
+// https://datatracker.ietf.org/doc/html/rfc8446#section-7.3
+pub struct TrafficCipher {
+    aead: Aead,
+    iv: Vec<u8>,
+    sequence_number_key: SequenceNumberKey,
+}
+
+impl TrafficCipher {
+    pub fn new(cipher_suite_id: CipherSuiteId, traffic_secret: &[u8]) -> Result<Self> {
+        let hash = cipher_suite_id.prf_hash();
+        let cipher_type = cipher_suite_id.record_cipher_type();
+        let key_length = cipher_type.key_length();
+        let key = hkdf_expand_label(hash, traffic_secret, "key", &[], key_length);
+        let iv = hkdf_expand_label(hash, traffic_secret, "iv", &[], TRAFFIC_IV_LENGTH);
+        // https://datatracker.ietf.org/doc/html/rfc9147#section-4.2.3
+        let sn_key = hkdf_expand_label(hash, traffic_secret, "sn", &[], key_length);
+        let invalid_key = |_| anyhow!("invalid {cipher_type:?} key length; {}", sn_key.len());
+        let sequence_number_key = match cipher_type {
+            RecordCipherType::Aes128Gcm => SequenceNumberKey::Aes128(Box::new(
+                <Aes128 as aes::cipher::KeyInit>::new_from_slice(&sn_key).map_err(invalid_key)?,
+            )),
+            RecordCipherType::Aes256Gcm => SequenceNumberKey::Aes256(Box::new(
+                <Aes256 as aes::cipher::KeyInit>::new_from_slice(&sn_key).map_err(invalid_key)?,
+            )),
+            RecordCipherType::Chacha20Poly1305 => SequenceNumberKey::Chacha20(sn_key.clone()),
+        };
+        Ok(Self {
+            aead: Aead::new(cipher_type, &key)?,
+            iv,
+            sequence_number_key,
+        })
+    }
+
+    // the epoch is not part of the nonce, unlike DTLS 1.2.
+    fn nonce(&self, sequence_number: u64) -> Vec<u8> {
+        let mut nonce = self.iv.clone();
+        for (nonce, sequence_number) in nonce[TRAFFIC_IV_LENGTH - 8..]
+            .iter_mut()
+            .zip(sequence_number.to_be_bytes())
+        {
+            *nonce ^= sequence_number;
+        }
+        nonce
+    }
+
+    /// Protects an inner plaintext and returns the record with its unified header.
+    // https://datatracker.ietf.org/doc/html/rfc9147#section-4
+    pub fn encrypt(&self, epoch: u16, sequence_number: u64, plaintext: Vec<u8>) -> Result<Vec<u8>> {
+        // the header with the plaintext sequence number is the additional data.
+        let mut header = UnifiedHeader::new(
+            epoch,
+            sequence_number,
+            (plaintext.len() + GCM_TAG_LENGTH) as u16,
+        );
+        let mut additional_data = BufWriter::new();
+        header.encode(&mut additional_data);
+        let mut ciphertext = plaintext;
+        self.aead.encrypt_in_place(
+            &self.nonce(sequence_number),
+            additional_data.buf_ref(),
+            &mut ciphertext,
+        )?;
+
+        header.sequence_number ^= u16::from_be_bytes(self.sequence_number_mask(&ciphertext)?);
+        let mut writer = BufWriter::new();
+        header.encode(&mut writer);
+        writer.write_bytes(&ciphertext);
+        Ok(writer.buf())
+    }
+
+    /// Removes the protection of a record and returns its full sequence number, the one
+    /// closest to `next_sequence_number`, with the inner plaintext.
+    pub fn decrypt(
+        &self,
+        mut header: UnifiedHeader,
+        next_sequence_number: u64,
+        ciphertext: &[u8],
+    ) -> Result<(u64, Vec<u8>)> {
+        let mask = self.sequence_number_mask(ciphertext)?;
+        header.sequence_number ^= match header.sequence_number_length {
+            2 => u16::from_be_bytes(mask),
+            _ => mask[0] as u16,
+        };
+        let sequence_number = reconstruct_sequence_number(
+            next_sequence_number,
+            header.sequence_number,
+            header.sequence_number_length,
+        );
+
+        let mut additional_data = BufWriter::new();
+        header.encode(&mut additional_data);
+        let mut plaintext = ciphertext.to_vec();
+        self.aead.decrypt_in_place(
+            &self.nonce(sequence_number),
+            additional_data.buf_ref(),
+            &mut plaintext,
+        )?;
+        Ok((sequence_number, plaintext))
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc9147#section-4.2.3
+    fn sequence_number_mask(&self, ciphertext: &[u8]) -> Result<[u8; 2]> {
+        if ciphertext.len() < 16 {
+            anyhow::bail!("ciphertext too short; {} bytes", ciphertext.len());
+        }
+        let mut mask = [0u8; 2];
+        match &self.sequence_number_key {
+            SequenceNumberKey::Aes128(cipher) => {
+                let mut block = aes::cipher::Block::<Aes128>::default();
+                block.copy_from_slice(&ciphertext[..16]);
+                cipher.encrypt_block(&mut block);
+                mask.copy_from_slice(&block[..2]);
+            }
+            SequenceNumberKey::Aes256(cipher) => {
+                let mut block = aes::cipher::Block::<Aes256>::default();
+                block.copy_from_slice(&ciphertext[..16]);
+                cipher.encrypt_block(&mut block);
+                mask.copy_from_slice(&block[..2]);
+            }
+            SequenceNumberKey::Chacha20(key) => {
+                // https://datatracker.ietf.org/doc/html/rfc9001#section-5.4.4
+                let counter = u32::from_le_bytes(ciphertext[..4].try_into()?);
+                let mut cipher = ChaCha20::new(
+                    chacha20::Key::from_slice(key),
+                    chacha20::Nonce::from_slice(&ciphertext[4..16]),
+                );
+                cipher.seek(counter as u64 * 64);
+                cipher.apply_keystream(&mut mask);
+            }
+        }
+        Ok(mask)
+    }
+}
+
+/// Verifies a signature over `message` with the public key of `certificate`; ECDSA keys on
+/// secp256r1/secp384r1 and RSA keys with PKCS#1 v1.5 or PSS padding are supported.
+pub fn verify_signature(
+    certificate: &[u8],
+    algo_pair: AlgoPair,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<()> {
+    let (_, x509) = x509_parser::parse_x509_certificate(certificate)?;
+    let public_key = x509.public_key().raw;
+    let hashed = match algo_pair.signature {
+        SignatureAlgorithm::RsaPssRsaeSha256 => digest(HashAlgorithm::Sha256, message)?,
+        _ => digest(algo_pair.hash, message)?,
+    };
+    match algo_pair.signature {
+        SignatureAlgorithm::Ecdsa => {
+            if let Ok(verifying_key) = p256::ecdsa::VerifyingKey::from_public_key_der(public_key) {
+                let signature = p256::ecdsa::Signature::from_der(signature)
+                    .map_err(|err| anyhow!("invalid signature: {err}"))?;
+                verifying_key.verify_prehash(&hashed, &signature)
+            } else {
+                let verifying_key = p384::ecdsa::VerifyingKey::from_public_key_der(public_key)
+                    .map_err(|err| anyhow!("unsupported ecdsa public key: {err}"))?;
+                let signature = p384::ecdsa::Signature::from_der(signature)
+                    .map_err(|err| anyhow!("invalid signature: {err}"))?;
+                verifying_key.verify_prehash(&hashed, &signature)
+            }
+            .map_err(|err| anyhow!("signature verification failed: {err}"))
+        }
+        SignatureAlgorithm::Rsa => {
+            // https://datatracker.ietf.org/doc/html/rfc5246#section-4.7
+            let public_key = RsaPublicKey::from_public_key_der(public_key)
+                .map_err(|err| anyhow!("invalid rsa public key: {err}"))?;
+            let scheme = match algo_pair.hash {
+                HashAlgorithm::Sha256 => Pkcs1v15Sign::new::<rsa::sha2::Sha256>(),
+                HashAlgorithm::Sha384 => Pkcs1v15Sign::new::<rsa::sha2::Sha384>(),
+                HashAlgorithm::Sha512 => Pkcs1v15Sign::new::<rsa::sha2::Sha512>(),
+                HashAlgorithm::Intrinsic | HashAlgorithm::Unsupported => {
+                    unreachable!("digest rejects unsupported hashes")
+                }
+            };
+            public_key
+                .verify(scheme, &hashed, signature)
+                .map_err(|err| anyhow!("signature verification failed: {err}"))
+        }
+        SignatureAlgorithm::RsaPssRsaeSha256 => {
+            // https://datatracker.ietf.org/doc/html/rfc8446#section-4.2.3
+            let public_key = RsaPublicKey::from_public_key_der(public_key)
+                .map_err(|err| anyhow!("invalid rsa public key: {err}"))?;
+            public_key
+                .verify(Pss::new::<rsa::sha2::Sha256>(), &hashed, signature)
+                .map_err(|err| anyhow!("signature verification failed: {err}"))
+        }
+        SignatureAlgorithm::Unsupported => {
+            anyhow::bail!("unsupported signature algorithm; {algo_pair:?}")
+        }
+    }
+}
+
+/// Signs `message` with RSA-PSS and SHA-256, which DTLS 1.3 requires of RSA keys.
+// https://datatracker.ietf.org/doc/html/rfc8446#section-4.2.3
+pub fn sign_rsa_pss(key_pair: &KeyPair, message: &[u8]) -> Result<Vec<u8>> {
+    let private_key = RsaPrivateKey::from_pkcs8_der(&key_pair.serialize_der())
+        .map_err(|err| anyhow!("invalid rsa private key: {err}"))?;
+    private_key
+        .sign_with_rng(
+            &mut OsRng,
+            Pss::new::<rsa::sha2::Sha256>(),
+            &digest(HashAlgorithm::Sha256, message)?,
+        )
+        .map_err(|err| anyhow!("failed to sign: {err}"))
+}
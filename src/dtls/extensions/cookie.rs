@@ -0,0 +1,23 @@
+use anyhow::Result;
+use crate::common::buffer::{BufReader, BufWriter};
+
+// https://datatracker.ietf.org/doc/html/rfc8446#section-4.2.2
+// DTLS 1.3 moves the cookie of HelloVerifyRequest into HelloRetryRequest.
+#[derive(Debug)]
+pub struct CookieExtension {
+    pub cookie: Vec<u8>,
+}
+
+impl CookieExtension {
+    pub fn decode(reader: &mut BufReader) -> Result<Self> {
+        let cookie_length = reader.read_u16()? as usize;
+        let mut cookie = vec![0u8; cookie_length];
+        reader.read_exact(&mut cookie)?;
+        Ok(Self { cookie })
+    }
+
+    pub fn encode(&self, writer: &mut BufWriter) {
+        writer.write_u16(self.cookie.len() as u16);
+        writer.write_bytes(&self.cookie);
+    }
+}
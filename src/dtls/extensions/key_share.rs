@@ -0,0 +1,71 @@
+use anyhow::Result;
+use crate::common::buffer::{BufReader, BufWriter};
+
+use crate::dtls::{ECCurve, handshake::header::HandshakeType};
+
+// https://datatracker.ietf.org/doc/html/rfc8446#section-4.2.8
+#[derive(Debug, Clone)]
+pub struct KeyShareEntry {
+    pub group: ECCurve,
+    pub key_exchange: Vec<u8>,
+}
+
+impl KeyShareEntry {
+    fn decode(reader: &mut BufReader) -> Result<Self> {
+        let group = ECCurve::from(reader.read_u16()?);
+        let key_exchange_length = reader.read_u16()?;
+        let mut key_exchange = vec![0u8; key_exchange_length as usize];
+        reader.read_exact(&mut key_exchange)?;
+        Ok(Self {
+            group,
+            key_exchange,
+        })
+    }
+
+    fn encode(&self, writer: &mut BufWriter) {
+        writer.write_u16(self.group.into());
+        writer.write_u16(self.key_exchange.len() as u16);
+        writer.write_bytes(&self.key_exchange);
+    }
+}
+
+#[derive(Debug)]
+pub enum KeyShare {
+    ClientHello(Vec<KeyShareEntry>),
+    ServerHello(KeyShareEntry),
+    // the group of the share the client has to send instead.
+    HelloRetryRequest(ECCurve),
+}
+
+impl KeyShare {
+    pub fn decode(reader: &mut BufReader, handshake_type: HandshakeType) -> Result<Self> {
+        if handshake_type == HandshakeType::ClientHello {
+            let length = reader.read_u16()? as usize;
+            let offset = reader.pos;
+            let mut entries = vec![];
+            while reader.pos - offset < length {
+                entries.push(KeyShareEntry::decode(reader)?);
+            }
+            Ok(Self::ClientHello(entries))
+        } else if reader.rest_len() == 2 {
+            Ok(Self::HelloRetryRequest(ECCurve::from(reader.read_u16()?)))
+        } else {
+            Ok(Self::ServerHello(KeyShareEntry::decode(reader)?))
+        }
+    }
+
+    pub fn encode(&self, writer: &mut BufWriter) {
+        match self {
+            Self::ClientHello(entries) => {
+                let mut entries_writer = BufWriter::new();
+                for entry in entries {
+                    entry.encode(&mut entries_writer);
+                }
+                writer.write_u16(entries_writer.buf_ref().len() as u16);
+                writer.write_bytes(entries_writer.buf_ref());
+            }
+            Self::ServerHello(entry) => entry.encode(writer),
+            Self::HelloRetryRequest(group) => writer.write_u16((*group).into()),
+        }
+    }
+}
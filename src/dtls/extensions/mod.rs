@@ -1,14 +1,26 @@
+pub mod cookie;
+pub mod key_share;
 pub mod renegotiation_info;
+pub mod session_ticket;
 pub mod supported_groups;
+pub mod supported_signature_algorithms;
+pub mod supported_versions;
 pub mod use_extended_master_secret;
 pub mod use_srtp;
 
+use anyhow::Result;
 use mini_webrtc_derive::FromPrimitive;
+use tracing::{debug, info};
 
+use crate::common::buffer::{BufReader, BufWriter};
 use crate::dtls::extensions::{
-    renegotiation_info::RenegotiationInfo, supported_groups::SupportedGroups,
-    use_extended_master_secret::UseExtendedMasterSecret, use_srtp::UseSrtp,
+    cookie::CookieExtension, key_share::KeyShare, renegotiation_info::RenegotiationInfo,
+    session_ticket::SessionTicketExtension, supported_groups::SupportedGroups,
+    supported_signature_algorithms::SupportedSignatureAlgorithms,
+    supported_versions::SupportedVersions, use_extended_master_secret::UseExtendedMasterSecret,
+    use_srtp::UseSrtp,
 };
+use crate::dtls::handshake::header::HandshakeType;
 
 // https://www.iana.org/assignments/tls-extensiontype-values/tls-extensiontype-values.xhtml
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, Hash)]
@@ -21,6 +33,12 @@ pub enum ExtensionType {
     UseSrtp = 14,
     ALTP = 16,
     UseExtendedMasterSecret = 23,
+    // https://datatracker.ietf.org/doc/html/rfc5077#section-3.2
+    SessionTicket = 35,
+    // https://datatracker.ietf.org/doc/html/rfc8446#section-4.2
+    SupportedVersions = 43,
+    Cookie = 44,
+    KeyShare = 51,
     RenegotiationInfo = 65281,
     Unsupported = 65535,
 }
@@ -29,8 +47,13 @@ pub enum ExtensionType {
 pub enum Extension {
     RenegotiationInfo(RenegotiationInfo),
     SupportedGroups(SupportedGroups),
+    SupportedSignatureAlgorithms(SupportedSignatureAlgorithms),
     UseSrtp(UseSrtp),
     UseExtendedMasterSecret(UseExtendedMasterSecret),
+    SessionTicket(SessionTicketExtension),
+    SupportedVersions(SupportedVersions),
+    Cookie(CookieExtension),
+    KeyShare(KeyShare),
     Unsupported,
 }
 
@@ -39,9 +62,108 @@ impl Extension {
         match self {
             Self::RenegotiationInfo(_) => ExtensionType::RenegotiationInfo,
             Self::SupportedGroups(_) => ExtensionType::SupportedGroups,
+            Self::SupportedSignatureAlgorithms(_) => ExtensionType::SupportedSignatureAlgorithms,
             Self::UseSrtp(_) => ExtensionType::UseSrtp,
             Self::UseExtendedMasterSecret(_) => ExtensionType::UseExtendedMasterSecret,
+            Self::SessionTicket(_) => ExtensionType::SessionTicket,
+            Self::SupportedVersions(_) => ExtensionType::SupportedVersions,
+            Self::Cookie(_) => ExtensionType::Cookie,
+            Self::KeyShare(_) => ExtensionType::KeyShare,
             _ => ExtensionType::Unsupported,
         }
     }
 }
+
+// https://datatracker.ietf.org/doc/html/rfc5246#section-7.4.1.4
+// supported_versions and key_share are encoded differently per handshake message.
+pub fn decode_extensions(
+    reader: &mut BufReader,
+    handshake_type: HandshakeType,
+) -> Result<Vec<Extension>> {
+    let mut extensions: Vec<Extension> = vec![];
+    // extensions are optional at the end of hello messages
+    if reader.rest_len() == 0 {
+        return Ok(extensions);
+    }
+    let extension_map_length = reader.read_u16()? as usize;
+    debug!("{extension_map_length:?}");
+    let extensions_offset = reader.pos;
+    while reader.pos - extensions_offset < extension_map_length {
+        let extension_type = ExtensionType::from(reader.read_u16()?);
+        debug!("{extension_type:?}");
+        let extension_length = reader.read_u16()? as usize;
+        debug!("{extension_length:?}");
+        let mut extension_data = vec![0u8; extension_length];
+        reader.read_exact(&mut extension_data)?;
+
+        let extension: Extension = {
+            let mut extension_reader = BufReader::new(&extension_data);
+            match extension_type {
+                ExtensionType::UseSrtp => {
+                    Extension::UseSrtp(UseSrtp::decode(&mut extension_reader)?)
+                }
+                ExtensionType::SupportedGroups => {
+                    Extension::SupportedGroups(SupportedGroups::decode(&mut extension_reader)?)
+                }
+                ExtensionType::SupportedSignatureAlgorithms => {
+                    Extension::SupportedSignatureAlgorithms(SupportedSignatureAlgorithms::decode(
+                        &mut extension_reader,
+                    )?)
+                }
+                ExtensionType::UseExtendedMasterSecret => Extension::UseExtendedMasterSecret(
+                    UseExtendedMasterSecret::decode(extension_reader)?,
+                ),
+                ExtensionType::SessionTicket => Extension::SessionTicket(
+                    SessionTicketExtension::decode(&mut extension_reader)?,
+                ),
+                ExtensionType::RenegotiationInfo => Extension::RenegotiationInfo(
+                    RenegotiationInfo::decode(&mut extension_reader)?,
+                ),
+                ExtensionType::SupportedVersions => Extension::SupportedVersions(
+                    SupportedVersions::decode(&mut extension_reader, handshake_type)?,
+                ),
+                ExtensionType::Cookie => {
+                    Extension::Cookie(CookieExtension::decode(&mut extension_reader)?)
+                }
+                ExtensionType::KeyShare => {
+                    Extension::KeyShare(KeyShare::decode(&mut extension_reader, handshake_type)?)
+                }
+                _ => {
+                    info!("ignore unsupported extension; {extension_type:?}");
+                    continue;
+                }
+            }
+        };
+
+        extensions.push(extension);
+    }
+    Ok(extensions)
+}
+
+pub fn encode_extensions(extensions: &[Extension], writer: &mut BufWriter) {
+    let mut extensions_writer = BufWriter::new();
+    for extension in extensions {
+        let mut extension_data_writer = BufWriter::new();
+        match extension {
+            Extension::RenegotiationInfo(value) => value.encode(&mut extension_data_writer),
+            Extension::SupportedGroups(value) => value.encode(&mut extension_data_writer),
+            Extension::SupportedSignatureAlgorithms(value) => {
+                value.encode(&mut extension_data_writer)
+            }
+            Extension::UseSrtp(value) => value.encode(&mut extension_data_writer),
+            Extension::UseExtendedMasterSecret(value) => value.encode(&mut extension_data_writer),
+            Extension::SessionTicket(value) => value.encode(&mut extension_data_writer),
+            Extension::SupportedVersions(value) => value.encode(&mut extension_data_writer),
+            Extension::Cookie(value) => value.encode(&mut extension_data_writer),
+            Extension::KeyShare(value) => value.encode(&mut extension_data_writer),
+            Extension::Unsupported => continue,
+        }
+
+        extensions_writer.write_u16(extension.get_extension_type() as u16);
+        extensions_writer.write_u16(extension_data_writer.buf_ref().len() as u16);
+        extensions_writer.write_bytes(extension_data_writer.buf_ref());
+    }
+
+    writer.write_u16(extensions_writer.buf_ref().len() as u16);
+    writer.write_bytes(extensions_writer.buf_ref());
+}
@@ -0,0 +1,21 @@
+use anyhow::Result;
+use crate::common::buffer::{BufReader, BufWriter};
+
+// https://datatracker.ietf.org/doc/html/rfc5077#section-3.2
+// empty to announce support of tickets, or the ticket of the session to resume.
+#[derive(Debug)]
+pub struct SessionTicketExtension {
+    pub ticket: Vec<u8>,
+}
+
+impl SessionTicketExtension {
+    pub fn decode(reader: &mut BufReader) -> Result<Self> {
+        let mut ticket = vec![0u8; reader.rest_len()];
+        reader.read_exact(&mut ticket)?;
+        Ok(Self { ticket })
+    }
+
+    pub fn encode(&self, writer: &mut BufWriter) {
+        writer.write_bytes(&self.ticket);
+    }
+}
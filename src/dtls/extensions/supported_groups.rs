@@ -1,5 +1,5 @@
 use anyhow::Result;
-use crate::common::buffer::BufReader;
+use crate::common::buffer::{BufReader, BufWriter};
 
 use crate::dtls::ECCurve;
 
@@ -20,4 +20,11 @@ impl SupportedGroups {
 
         Ok(Self { curves })
     }
+
+    pub fn encode(&self, writer: &mut BufWriter) {
+        writer.write_u16((self.curves.len() * 2) as u16);
+        for curve in &self.curves {
+            writer.write_u16((*curve).into());
+        }
+    }
 }
@@ -0,0 +1,31 @@
+use anyhow::Result;
+use crate::common::buffer::{BufReader, BufWriter};
+
+use crate::dtls::{AlgoPair, HashAlgorithm, SignatureAlgorithm};
+
+// https://datatracker.ietf.org/doc/html/rfc5246#section-7.4.1.4.1
+#[derive(Debug)]
+pub struct SupportedSignatureAlgorithms {
+    pub algo_pairs: Vec<AlgoPair>,
+}
+
+impl SupportedSignatureAlgorithms {
+    pub fn decode(reader: &mut BufReader) -> Result<Self> {
+        let length = reader.read_u16()?;
+        let mut algo_pairs = vec![];
+        for _ in 0..length / 2 {
+            algo_pairs.push(AlgoPair {
+                hash: HashAlgorithm::from(reader.read_u8()?),
+                signature: SignatureAlgorithm::from(reader.read_u8()?),
+            });
+        }
+        Ok(Self { algo_pairs })
+    }
+
+    pub fn encode(&self, writer: &mut BufWriter) {
+        writer.write_u16((self.algo_pairs.len() * 2) as u16);
+        for algo_pair in &self.algo_pairs {
+            algo_pair.encode(writer);
+        }
+    }
+}
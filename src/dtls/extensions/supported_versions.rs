@@ -0,0 +1,41 @@
+use anyhow::Result;
+use crate::common::buffer::{BufReader, BufWriter};
+
+use crate::dtls::{handshake::header::HandshakeType, record_header::DtlsVersion};
+
+// https://datatracker.ietf.org/doc/html/rfc8446#section-4.2.1
+#[derive(Debug)]
+pub enum SupportedVersions {
+    ClientHello(Vec<DtlsVersion>),
+    ServerHello(DtlsVersion),
+}
+
+impl SupportedVersions {
+    pub fn decode(reader: &mut BufReader, handshake_type: HandshakeType) -> Result<Self> {
+        if handshake_type != HandshakeType::ClientHello {
+            let version = DtlsVersion::try_from(reader.read_u16()?)?;
+            return Ok(Self::ServerHello(version));
+        }
+        let length = reader.read_u8()?;
+        let mut versions = vec![];
+        for _ in 0..length / 2 {
+            // TLS versions and GREASE values are skipped.
+            if let Ok(version) = DtlsVersion::try_from(reader.read_u16()?) {
+                versions.push(version);
+            }
+        }
+        Ok(Self::ClientHello(versions))
+    }
+
+    pub fn encode(&self, writer: &mut BufWriter) {
+        match self {
+            Self::ClientHello(versions) => {
+                writer.write_u8((versions.len() * 2) as u8);
+                for version in versions {
+                    writer.write_u16((*version).into());
+                }
+            }
+            Self::ServerHello(version) => writer.write_u16((*version).into()),
+        }
+    }
+}
@@ -37,6 +37,8 @@ impl UseSrtp {
 }
 
 // https://www.iana.org/assignments/srtp-protection/srtp-protection.xhtml
+pub const SRTP_AEAD_AES_128_GCM: u16 = 0x0007;
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum SrtpProtectionProfile {
     SrtpAeadAes128Gcm(ProtectionProfile),
@@ -55,7 +57,7 @@ impl SrtpProtectionProfile {
 impl From<u16> for SrtpProtectionProfile {
     fn from(value: u16) -> Self {
         match value {
-            0x0007 => Self::SrtpAeadAes128Gcm(ProtectionProfile {
+            SRTP_AEAD_AES_128_GCM => Self::SrtpAeadAes128Gcm(ProtectionProfile {
                 value,
                 key_length: 16,
                 salt_length: 12,
@@ -5,18 +5,39 @@ use crate::common::buffer::{BufReader, BufWriter};
 #[derive(Debug)]
 pub struct Certificate {
     pub certificates: Vec<Vec<u8>>,
+    // https://datatracker.ietf.org/doc/html/rfc8446#section-4.4.2
+    // present only in DTLS 1.3, where every certificate also carries extensions.
+    pub request_context: Option<Vec<u8>>,
 }
 
 impl Certificate {
     pub fn new(certificates: Vec<Vec<u8>>) -> Self {
-        Self { certificates }
+        Self {
+            certificates,
+            request_context: None,
+        }
+    }
+
+    pub fn new_v13(certificates: Vec<Vec<u8>>, request_context: Vec<u8>) -> Self {
+        Self {
+            certificates,
+            request_context: Some(request_context),
+        }
     }
 
     pub fn encode(&self, writer: &mut BufWriter) {
+        if let Some(request_context) = &self.request_context {
+            writer.write_u8(request_context.len() as u8);
+            writer.write_bytes(request_context);
+        }
         let mut certs_writer = BufWriter::new();
         for cert in &self.certificates {
             certs_writer.write_u24(cert.len() as u32);
             certs_writer.write_bytes(cert);
+            if self.request_context.is_some() {
+                // no certificate extensions
+                certs_writer.write_u16(0);
+            }
         }
 
         let buf = certs_writer.buf_ref();
@@ -46,7 +67,33 @@ impl Certificate {
                 .with_context(|| format!("Certificate::decode: read certificate[{}] body (cert_len={cert_len}) at pos={}", certificates.len(), reader.pos))?;
             certificates.push(cert);
         }
-        Ok(Self { certificates })
+        Ok(Self {
+            certificates,
+            request_context: None,
+        })
+    }
+
+    pub fn decode_v13(reader: &mut BufReader) -> anyhow::Result<Self> {
+        let request_context_length = reader.read_u8()?;
+        let mut request_context = vec![0u8; request_context_length as usize];
+        reader.read_exact(&mut request_context)?;
+        let certificates_length = reader.read_u24()? as usize;
+        let offset = reader.pos;
+        let mut certificates: Vec<Vec<u8>> = vec![];
+        while reader.pos - offset < certificates_length {
+            let cert_len = reader.read_u24()?;
+            let mut cert: Vec<u8> = vec![0u8; cert_len as usize];
+            reader.read_exact(&mut cert)?;
+            // certificate extensions are ignored
+            let extensions_length = reader.read_u16()?;
+            let mut extensions = vec![0u8; extensions_length as usize];
+            reader.read_exact(&mut extensions)?;
+            certificates.push(cert);
+        }
+        Ok(Self {
+            certificates,
+            request_context: Some(request_context),
+        })
     }
 }
 
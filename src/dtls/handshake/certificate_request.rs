@@ -1,6 +1,12 @@
-use crate::common::buffer::BufWriter;
+use anyhow::Result;
+
+use crate::common::buffer::{BufReader, BufWriter};
 
 use crate::dtls::{
+    extensions::{
+        Extension, decode_extensions, encode_extensions,
+        supported_signature_algorithms::SupportedSignatureAlgorithms,
+    },
     handshake::{HandshakeMessage, header::HandshakeType},
     {AlgoPair, CertificateType, HashAlgorithm, SignatureAlgorithm},
 };
@@ -10,23 +16,48 @@ pub struct CertificateRequest {
     // https://datatracker.ietf.org/doc/html/rfc5246#section-7.4.4
     // https://www.rfc-editor.org/rfc/rfc8422.html#section-5.5
     certificate_types: Vec<CertificateType>,
-    supported_algo_pairs: Vec<AlgoPair>,
+    pub supported_algo_pairs: Vec<AlgoPair>,
     certificate_authorities: Vec<String>,
 }
 
 impl CertificateRequest {
     pub fn new() -> Self {
         Self {
-            // TODO: support others
-            certificate_types: vec![CertificateType::Ecdsa],
-            supported_algo_pairs: vec![AlgoPair {
-                hash: HashAlgorithm::Sha256,
-                signature: SignatureAlgorithm::Ecdsa,
-            }],
+            certificate_types: vec![CertificateType::Ecdsa, CertificateType::Rsa],
+            supported_algo_pairs: AlgoPair::SUPPORTED.to_vec(),
             certificate_authorities: vec![],
         }
     }
 
+    pub fn decode(reader: &mut BufReader) -> Result<Self> {
+        let certificate_types_length = reader.read_u8()?;
+        let mut certificate_types = vec![];
+        for _ in 0..certificate_types_length {
+            match CertificateType::from(reader.read_u8()?) {
+                CertificateType::Unsupported => {}
+                certificate_type => certificate_types.push(certificate_type),
+            }
+        }
+        let algo_pairs_length = reader.read_u16()?;
+        let mut supported_algo_pairs = vec![];
+        for _ in 0..algo_pairs_length / 2 {
+            supported_algo_pairs.push(AlgoPair {
+                hash: HashAlgorithm::from(reader.read_u8()?),
+                signature: SignatureAlgorithm::from(reader.read_u8()?),
+            });
+        }
+        // certificate authorities are ignored
+        let certificate_authorities_length = reader.read_u16()?;
+        let mut certificate_authorities = vec![0u8; certificate_authorities_length as usize];
+        reader.read_exact(&mut certificate_authorities)?;
+
+        Ok(Self {
+            certificate_types,
+            supported_algo_pairs,
+            certificate_authorities: vec![],
+        })
+    }
+
     pub fn encode(&self, writer: &mut BufWriter) {
         writer.write_u8(self.certificate_types.len() as u8);
         for t in &self.certificate_types {
@@ -53,3 +84,56 @@ impl HandshakeMessage for CertificateRequest {
         self.encode(writer);
     }
 }
+
+// https://datatracker.ietf.org/doc/html/rfc8446#section-4.3.2
+#[derive(Debug)]
+pub struct CertificateRequestV13 {
+    pub request_context: Vec<u8>,
+    pub extensions: Vec<Extension>,
+}
+
+impl Default for CertificateRequestV13 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CertificateRequestV13 {
+    pub fn new() -> Self {
+        Self {
+            request_context: vec![],
+            extensions: vec![Extension::SupportedSignatureAlgorithms(
+                SupportedSignatureAlgorithms {
+                    algo_pairs: AlgoPair::SUPPORTED.to_vec(),
+                },
+            )],
+        }
+    }
+
+    pub fn decode(reader: &mut BufReader) -> Result<Self> {
+        let request_context_length = reader.read_u8()?;
+        let mut request_context = vec![0u8; request_context_length as usize];
+        reader.read_exact(&mut request_context)?;
+        let extensions = decode_extensions(reader, HandshakeType::CertificateRequest)?;
+        Ok(Self {
+            request_context,
+            extensions,
+        })
+    }
+
+    pub fn encode(&self, writer: &mut BufWriter) {
+        writer.write_u8(self.request_context.len() as u8);
+        writer.write_bytes(&self.request_context);
+        encode_extensions(&self.extensions, writer);
+    }
+}
+
+impl HandshakeMessage for CertificateRequestV13 {
+    fn get_handshake_type(&self) -> HandshakeType {
+        HandshakeType::CertificateRequest
+    }
+
+    fn encode(&self, writer: &mut BufWriter) {
+        self.encode(writer);
+    }
+}
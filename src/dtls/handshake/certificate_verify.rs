@@ -1,14 +1,67 @@
 use anyhow::Result;
-use crate::common::buffer::BufReader;
+use rcgen::{KeyPair, SigningKey};
 
-use crate::dtls::{AlgoPair, HashAlgorithm, SignatureAlgorithm};
+use crate::common::buffer::{BufReader, BufWriter};
+use crate::dtls::{
+    AlgoPair, DtlsRole, HashAlgorithm, SignatureAlgorithm,
+    crypto::sign_rsa_pss,
+    handshake::{HandshakeMessage, header::HandshakeType},
+};
 
+// https://datatracker.ietf.org/doc/html/rfc5246#section-7.4.8
 pub struct CertificateVerify {
     pub algo_pair: AlgoPair,
     pub signature: Vec<u8>,
 }
 
 impl CertificateVerify {
+    /// Signs the handshake messages exchanged so far with the private key of the certificate.
+    pub fn new(private_key: &KeyPair, handshake_messages: &[u8]) -> Result<Self> {
+        Ok(Self {
+            algo_pair: AlgoPair::from_key_pair(private_key)?,
+            signature: private_key.sign(handshake_messages)?,
+        })
+    }
+
+    /// Signs the transcript hash of the handshake with the private key of the certificate;
+    /// RSA keys sign with PSS.
+    pub fn new_v13(
+        private_key: &KeyPair,
+        sender: DtlsRole,
+        transcript_hash: &[u8],
+    ) -> Result<Self> {
+        let content = Self::signed_content_v13(sender, transcript_hash);
+        let algo_pair = AlgoPair::from_key_pair(private_key)?;
+        if algo_pair.signature == SignatureAlgorithm::Rsa {
+            return Ok(Self {
+                algo_pair: AlgoPair::new(
+                    HashAlgorithm::Intrinsic,
+                    SignatureAlgorithm::RsaPssRsaeSha256,
+                ),
+                signature: sign_rsa_pss(private_key, &content)?,
+            });
+        }
+        Ok(Self {
+            algo_pair,
+            signature: private_key.sign(&content)?,
+        })
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8446#section-4.4.3
+    pub fn signed_content_v13(sender: DtlsRole, transcript_hash: &[u8]) -> Vec<u8> {
+        let context_string = match sender {
+            DtlsRole::Client => "TLS 1.3, client CertificateVerify",
+            DtlsRole::Server => "TLS 1.3, server CertificateVerify",
+        };
+        [
+            &[0x20; 64],
+            context_string.as_bytes(),
+            &[0],
+            transcript_hash,
+        ]
+        .concat()
+    }
+
     pub fn decode(reader: &mut BufReader) -> Result<Self> {
         let hash_algo = reader.read_u8()?;
         let signature_algo = reader.read_u8()?;
@@ -23,4 +76,20 @@ impl CertificateVerify {
             signature,
         })
     }
+
+    pub fn encode(&self, writer: &mut BufWriter) {
+        self.algo_pair.encode(writer);
+        writer.write_u16(self.signature.len() as u16);
+        writer.write_bytes(&self.signature);
+    }
+}
+
+impl HandshakeMessage for CertificateVerify {
+    fn get_handshake_type(&self) -> HandshakeType {
+        HandshakeType::CertificateVerify
+    }
+
+    fn encode(&self, writer: &mut BufWriter) {
+        self.encode(writer);
+    }
 }
@@ -1,24 +1,20 @@
 use anyhow::Result;
-use tracing::{debug, info};
+use tracing::debug;
 
 use crate::dtls::{
     cipher_suite::CipherSuiteId,
-    extensions::{
-        Extension, ExtensionType, renegotiation_info::RenegotiationInfo,
-        supported_groups::SupportedGroups, use_extended_master_secret::UseExtendedMasterSecret,
-        use_srtp::UseSrtp,
-    },
-    handshake::random::Random,
+    extensions::{Extension, decode_extensions, encode_extensions},
+    handshake::{HandshakeMessage, header::HandshakeType, random::Random},
     record_header::DtlsVersion,
-    {CompressionMethodId, Cookie},
+    {CompressionMethodId, Cookie, SessionId},
 };
-use crate::common::buffer::BufReader;
+use crate::common::buffer::{BufReader, BufWriter};
 
 #[derive(Debug)]
 pub struct ClientHello {
     pub version: DtlsVersion,
     pub random: Random,
-    // session_id: SessionId,
+    pub session_id: SessionId,
     pub cookie: Option<Cookie>,
     pub cipher_suite_ids: Vec<CipherSuiteId>,
     pub compression_method_ids: Vec<CompressionMethodId>,
@@ -26,6 +22,23 @@ pub struct ClientHello {
 }
 
 impl ClientHello {
+    pub fn new(
+        random: Random,
+        cookie: Option<Cookie>,
+        cipher_suite_ids: Vec<CipherSuiteId>,
+        extensions: Vec<Extension>,
+    ) -> Self {
+        Self {
+            version: DtlsVersion::V1_2,
+            random,
+            session_id: vec![],
+            cookie,
+            cipher_suite_ids,
+            compression_method_ids: vec![CompressionMethodId::Null],
+            extensions,
+        }
+    }
+
     pub fn decode(reader: &mut BufReader) -> Result<Self> {
         let raw_version = reader.read_u16()?;
         let version = DtlsVersion::try_from(raw_version)?;
@@ -34,7 +47,6 @@ impl ClientHello {
         let random = Random::decode(reader)?;
         debug!("{random:?}");
 
-        // ignore session id; not support session resumption
         let session_id_length = reader.read_u8()?;
         debug!("{session_id_length:?}");
         let mut session_id = vec![0u8; session_id_length as usize];
@@ -66,52 +78,49 @@ impl ClientHello {
         }
         debug!("{compression_method_ids:?}");
 
-        // TODO: decode extensions
-        let mut extensions: Vec<Extension> = vec![];
-        let extension_map_length = reader.read_u16()? as usize;
-        debug!("{extension_map_length:?}");
-        let extensions_offset = reader.pos;
-        while reader.pos - extensions_offset < extension_map_length {
-            let extension_type = ExtensionType::from(reader.read_u16()?);
-            debug!("{extension_type:?}");
-            let extension_length = reader.read_u16()? as usize;
-            debug!("{extension_length:?}");
-            let mut extension_data = vec![0u8; extension_length];
-            reader.read_exact(&mut extension_data)?;
-
-            let extension: Extension = {
-                let mut extension_reader = BufReader::new(&extension_data);
-                match extension_type {
-                    ExtensionType::UseSrtp => {
-                        Extension::UseSrtp(UseSrtp::decode(&mut extension_reader)?)
-                    }
-                    ExtensionType::SupportedGroups => {
-                        Extension::SupportedGroups(SupportedGroups::decode(&mut extension_reader)?)
-                    }
-                    ExtensionType::UseExtendedMasterSecret => Extension::UseExtendedMasterSecret(
-                        UseExtendedMasterSecret::decode(extension_reader)?,
-                    ),
-                    ExtensionType::RenegotiationInfo => Extension::RenegotiationInfo(
-                        RenegotiationInfo::decode(&mut extension_reader)?,
-                    ),
-                    _ => {
-                        info!("ignore unsupported extension; {extension_type:?}");
-                        continue;
-                    }
-                }
-            };
-
-            extensions.push(extension);
-        }
+        let extensions = decode_extensions(reader, HandshakeType::ClientHello)?;
 
         Ok(Self {
             version,
             random,
-            // session_id,
+            session_id,
             cookie: Cookie::try_from(cookie_buf).ok(),
             cipher_suite_ids,
             compression_method_ids,
             extensions,
         })
     }
+
+    pub fn encode(&self, writer: &mut BufWriter) {
+        writer.write_u16(self.version.into());
+        self.random.encode(writer);
+        writer.write_u8(self.session_id.len() as u8);
+        writer.write_bytes(&self.session_id);
+        let cookie = self
+            .cookie
+            .as_ref()
+            .map(|cookie| cookie.0.as_slice())
+            .unwrap_or_default();
+        writer.write_u8(cookie.len() as u8);
+        writer.write_bytes(cookie);
+        writer.write_u16((self.cipher_suite_ids.len() * 2) as u16);
+        for cipher_suite_id in &self.cipher_suite_ids {
+            writer.write_u16((*cipher_suite_id).into());
+        }
+        writer.write_u8(self.compression_method_ids.len() as u8);
+        for compression_method_id in &self.compression_method_ids {
+            writer.write_u8((*compression_method_id).into());
+        }
+        encode_extensions(&self.extensions, writer);
+    }
+}
+
+impl HandshakeMessage for ClientHello {
+    fn get_handshake_type(&self) -> HandshakeType {
+        HandshakeType::ClientHello
+    }
+
+    fn encode(&self, writer: &mut BufWriter) {
+        self.encode(writer);
+    }
 }
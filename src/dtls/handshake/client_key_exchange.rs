@@ -10,6 +10,10 @@ pub struct ClientKeyExchange {
 }
 
 impl ClientKeyExchange {
+    pub fn new(public_key: Vec<u8>) -> Self {
+        Self { public_key }
+    }
+
     pub fn decode(reader: &mut BufReader) -> Result<Self> {
         let len = reader.read_u8()?;
         let mut public_key = vec![0u8; len as usize];
@@ -17,8 +21,9 @@ impl ClientKeyExchange {
         Ok(Self { public_key })
     }
 
-    pub fn encode(&self, _: &mut BufWriter) {
-        // TODO
+    pub fn encode(&self, writer: &mut BufWriter) {
+        writer.write_u8(self.public_key.len() as u8);
+        writer.write_bytes(&self.public_key);
     }
 }
 
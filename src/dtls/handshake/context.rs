@@ -1,7 +1,10 @@
 #[derive(Debug, Clone)]
 pub enum HandshakeFlight {
     Flight0,
+    Flight1,
     Flight2,
+    Flight3,
     Flight4,
+    Flight5,
     Flight6,
 }
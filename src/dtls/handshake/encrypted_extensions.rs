@@ -0,0 +1,38 @@
+use anyhow::Result;
+
+use crate::common::buffer::{BufReader, BufWriter};
+use crate::dtls::{
+    extensions::{Extension, decode_extensions, encode_extensions},
+    handshake::{HandshakeMessage, header::HandshakeType},
+};
+
+// https://datatracker.ietf.org/doc/html/rfc8446#section-4.3.1
+#[derive(Debug)]
+pub struct EncryptedExtensions {
+    pub extensions: Vec<Extension>,
+}
+
+impl EncryptedExtensions {
+    pub fn new(extensions: Vec<Extension>) -> Self {
+        Self { extensions }
+    }
+
+    pub fn decode(reader: &mut BufReader) -> Result<Self> {
+        let extensions = decode_extensions(reader, HandshakeType::EncryptedExtensions)?;
+        Ok(Self { extensions })
+    }
+
+    pub fn encode(&self, writer: &mut BufWriter) {
+        encode_extensions(&self.extensions, writer);
+    }
+}
+
+impl HandshakeMessage for EncryptedExtensions {
+    fn get_handshake_type(&self) -> HandshakeType {
+        HandshakeType::EncryptedExtensions
+    }
+
+    fn encode(&self, writer: &mut BufWriter) {
+        self.encode(writer);
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use tracing::debug;
+
+use crate::common::buffer::BufWriter;
+use crate::dtls::handshake::header::HandshakeHeader;
+
+// certificate chains are the largest handshake messages
+pub const MAX_HANDSHAKE_MESSAGE_LENGTH: u32 = 64 * 1024;
+// later messages of a flight buffered ahead of the next one
+const MESSAGE_SEQ_WINDOW: u16 = 16;
+const MAX_BUFFERED_BYTES: usize = 256 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct PlainHandshakeMessage {
+    pub handshake_header: HandshakeHeader,
+    pub mask: Vec<bool>,
+    pub payload: Vec<u8>,
+}
+
+impl PlainHandshakeMessage {
+    pub fn new(handshake_header: HandshakeHeader) -> Self {
+        let length = handshake_header.length as usize;
+        Self {
+            // Transcript hashes are computed over full handshake messages, not per-fragment headers.
+            handshake_header: HandshakeHeader::new(
+                handshake_header.handshake_type,
+                handshake_header.length,
+                handshake_header.message_seq,
+                0,
+                handshake_header.length,
+            ),
+            mask: vec![false; length],
+            payload: vec![0u8; length],
+        }
+    }
+
+    /// Copies a fragment into the message; fragments may overlap or repeat.
+    pub fn add(&mut self, offset: u32, payload: &[u8]) -> Result<()> {
+        let offset = offset as usize;
+        if offset + payload.len() > self.payload.len() {
+            anyhow::bail!(
+                "handshake fragment out of range; offset={offset}, length={}, message length={}",
+                payload.len(),
+                self.payload.len()
+            );
+        }
+        self.payload[offset..offset + payload.len()].copy_from_slice(payload);
+        self.mask[offset..offset + payload.len()].fill(true);
+        Ok(())
+    }
+
+    pub fn completed(&self) -> bool {
+        self.mask.iter().all(|b| *b)
+    }
+
+    pub fn raw(&self) -> Vec<u8> {
+        let mut writer = BufWriter::new();
+        self.handshake_header.encode(&mut writer);
+        writer.write_bytes(&self.payload);
+        writer.buf()
+    }
+}
+
+/// Reassembles fragmented handshake messages and releases them in `message_seq` order.
+// https://datatracker.ietf.org/doc/html/rfc6347#section-4.2.2
+#[derive(Debug, Default)]
+pub struct HandshakeReassembler {
+    /// `message_seq` of the next message to be released.
+    pub next_message_seq: u16,
+    messages: BTreeMap<u16, PlainHandshakeMessage>,
+    buffered_bytes: usize,
+}
+
+impl HandshakeReassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffers a fragment of the next or a later message. Fragments beyond the window or
+    /// the buffer limit are dropped, since the peer retransmits them; a fragment that
+    /// disagrees with its header or with the earlier fragments of its message is an error.
+    pub fn add(&mut self, header: &HandshakeHeader, fragment: &[u8]) -> Result<()> {
+        if fragment.len() != header.fragment_length as usize
+            || header.fragment_offset + header.fragment_length > header.length
+        {
+            anyhow::bail!("inconsistent handshake fragment; {header:?}");
+        }
+        if header.length > MAX_HANDSHAKE_MESSAGE_LENGTH {
+            anyhow::bail!("handshake message too long; {header:?}");
+        }
+        if header.message_seq < self.next_message_seq {
+            return Ok(());
+        }
+        if header.message_seq - self.next_message_seq >= MESSAGE_SEQ_WINDOW {
+            debug!("drop handshake fragment beyond window; {header:?}");
+            return Ok(());
+        }
+
+        if let Some(message) = self.messages.get_mut(&header.message_seq) {
+            if message.handshake_header.handshake_type != header.handshake_type
+                || message.handshake_header.length != header.length
+            {
+                anyhow::bail!("handshake fragment does not match its message; {header:?}");
+            }
+            return message.add(header.fragment_offset, fragment);
+        }
+        if self.buffered_bytes + header.length as usize > MAX_BUFFERED_BYTES {
+            debug!("drop handshake fragment over buffer limit; {header:?}");
+            return Ok(());
+        }
+        let mut message = PlainHandshakeMessage::new(header.clone());
+        message.add(header.fragment_offset, fragment)?;
+        self.buffered_bytes += message.payload.len();
+        self.messages.insert(header.message_seq, message);
+        Ok(())
+    }
+
+    /// Releases the next message once all of its fragments have arrived.
+    pub fn pop(&mut self) -> Option<PlainHandshakeMessage> {
+        if !self.messages.get(&self.next_message_seq)?.completed() {
+            return None;
+        }
+        let message = self.messages.remove(&self.next_message_seq)?;
+        self.buffered_bytes -= message.payload.len();
+        self.next_message_seq += 1;
+        Some(message)
+    }
+}
+
+/// Splits a handshake message into fragments of at most `max_fragment_length` bytes, each
+/// with its own handshake header.
+// https://datatracker.ietf.org/doc/html/rfc6347#section-4.2.3
+pub fn fragment_handshake_message(
+    header: &HandshakeHeader,
+    payload: &[u8],
+    max_fragment_length: usize,
+) -> Vec<Vec<u8>> {
+    let mut fragments = vec![];
+    let mut offset = 0;
+    loop {
+        let fragment = &payload[offset..payload.len().min(offset + max_fragment_length)];
+        let mut writer = BufWriter::new();
+        HandshakeHeader::new(
+            header.handshake_type,
+            header.length,
+            header.message_seq,
+            offset as u32,
+            fragment.len() as u32,
+        )
+        .encode(&mut writer);
+        writer.write_bytes(fragment);
+        fragments.push(writer.buf());
+        offset += fragment.len();
+        if offset >= payload.len() {
+            return fragments;
+        }
+    }
+}
+
+#[cfg(test)]
+mod fragment_tests {
+    use super::*;
+    use crate::common::buffer::BufReader;
+    use crate::dtls::handshake::header::{HANDSHAKE_HEADER_BYTES, HandshakeType};
+
+    fn header(message_seq: u16, length: u32, offset: u32, fragment_length: u32) -> HandshakeHeader {
+        HandshakeHeader::new(
+            HandshakeType::Certificate,
+            length,
+            message_seq,
+            offset,
+            fragment_length,
+        )
+    }
+
+    #[test]
+    fn test_reassemble_out_of_order_overlapping_fragments() -> Result<()> {
+        let payload = (0..100u8).collect::<Vec<_>>();
+        let mut reassembler = HandshakeReassembler::new();
+        // the next message arrives before the current one is complete
+        reassembler.add(&header(1, 2, 0, 2), &[7, 7])?;
+        reassembler.add(&header(0, 100, 40, 60), &payload[40..])?;
+        assert!(reassembler.pop().is_none());
+        reassembler.add(&header(0, 100, 0, 50), &payload[..50])?;
+        reassembler.add(&header(0, 100, 0, 50), &payload[..50])?;
+
+        let message = reassembler.pop().ok_or(anyhow::anyhow!("incomplete"))?;
+        assert_eq!(message.payload, payload);
+        assert_eq!(message.handshake_header.fragment_length, 100);
+        assert_eq!(
+            reassembler.pop().map(|message| message.payload),
+            Some(vec![7, 7])
+        );
+        assert!(reassembler.pop().is_none());
+        assert_eq!(reassembler.next_message_seq, 2);
+
+        // fragments of released messages are ignored
+        reassembler.add(&header(0, 100, 0, 50), &payload[..50])?;
+        assert!(reassembler.pop().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reject_inconsistent_fragments() {
+        let mut reassembler = HandshakeReassembler::new();
+        assert!(reassembler.add(&header(0, 10, 8, 4), &[0; 4]).is_err());
+        assert!(reassembler.add(&header(0, 10, 0, 4), &[0; 3]).is_err());
+        assert!(
+            reassembler
+                .add(&header(0, MAX_HANDSHAKE_MESSAGE_LENGTH + 1, 0, 1), &[0])
+                .is_err()
+        );
+        assert!(reassembler.add(&header(0, 10, 0, 4), &[0; 4]).is_ok());
+        assert!(reassembler.add(&header(0, 12, 4, 4), &[0; 4]).is_err());
+
+        // messages far ahead of the next one are not buffered
+        assert!(
+            reassembler
+                .add(&header(MESSAGE_SEQ_WINDOW, 1, 0, 1), &[0])
+                .is_ok()
+        );
+        assert_eq!(reassembler.messages.len(), 1);
+    }
+
+    #[test]
+    fn test_fragment_handshake_message() -> Result<()> {
+        let payload = (0..250u8).collect::<Vec<_>>();
+        let fragments = fragment_handshake_message(&header(3, 250, 0, 250), &payload, 100);
+        assert_eq!(fragments.len(), 3);
+
+        let mut reassembler = HandshakeReassembler::new();
+        reassembler.next_message_seq = 3;
+        for fragment in fragments.iter().rev() {
+            let header = HandshakeHeader::decode(&mut BufReader::new(fragment))?;
+            reassembler.add(&header, &fragment[HANDSHAKE_HEADER_BYTES..])?;
+        }
+        assert_eq!(
+            reassembler.pop().map(|message| message.payload),
+            Some(payload)
+        );
+
+        // empty messages are sent as one empty fragment
+        assert_eq!(
+            fragment_handshake_message(&header(4, 0, 0, 0), &[], 100).len(),
+            1
+        );
+        Ok(())
+    }
+}
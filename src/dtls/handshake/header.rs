@@ -11,6 +11,10 @@ pub enum HandshakeType {
     ClientHello = 1,
     ServerHello = 2,
     HelloVerifyRequest = 3,
+    // https://datatracker.ietf.org/doc/html/rfc5077#section-3.3
+    NewSessionTicket = 4,
+    // https://datatracker.ietf.org/doc/html/rfc8446#section-4
+    EncryptedExtensions = 8,
     Certificate = 11,
     ServerKeyExchange = 12,
     CertificateRequest = 13,
@@ -18,6 +22,8 @@ pub enum HandshakeType {
     CertificateVerify = 15,
     ClientKeyExchange = 16,
     Finished = 20,
+    // https://datatracker.ietf.org/doc/html/rfc8446#section-4.4.1
+    MessageHash = 254,
     Unsupported = 255,
 }
 
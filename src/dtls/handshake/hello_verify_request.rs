@@ -14,11 +14,8 @@ pub struct HelloVerifyRequest {
 }
 
 impl HelloVerifyRequest {
-    pub fn new(version: DtlsVersion) -> Self {
-        Self {
-            version,
-            cookie: Cookie::new(),
-        }
+    pub fn new(version: DtlsVersion, cookie: Cookie) -> Self {
+        Self { version, cookie }
     }
 
     pub fn decode(reader: &mut BufReader) -> Result<Self> {
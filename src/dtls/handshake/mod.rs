@@ -8,9 +8,12 @@ pub mod certificate_verify;
 pub mod client_hello;
 pub mod client_key_exchange;
 pub mod context;
+pub mod encrypted_extensions;
 pub mod finished;
+pub mod fragment;
 pub mod header;
 pub mod hello_verify_request;
+pub mod new_session_ticket;
 pub mod random;
 pub mod server_hello;
 pub mod server_hello_done;
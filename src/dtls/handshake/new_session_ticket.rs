@@ -0,0 +1,40 @@
+use anyhow::Result;
+
+use crate::dtls::handshake::{HandshakeMessage, header::HandshakeType};
+use crate::common::buffer::{BufReader, BufWriter};
+
+// https://datatracker.ietf.org/doc/html/rfc5077#section-3.3
+#[derive(Debug)]
+pub struct NewSessionTicket {
+    pub ticket_lifetime_hint: u32, // seconds
+    pub ticket: Vec<u8>,
+}
+
+impl NewSessionTicket {
+    pub fn decode(reader: &mut BufReader) -> Result<Self> {
+        let ticket_lifetime_hint = reader.read_u32()?;
+        let ticket_length = reader.read_u16()? as usize;
+        let mut ticket = vec![0u8; ticket_length];
+        reader.read_exact(&mut ticket)?;
+        Ok(Self {
+            ticket_lifetime_hint,
+            ticket,
+        })
+    }
+
+    pub fn encode(&self, writer: &mut BufWriter) {
+        writer.write_u32(self.ticket_lifetime_hint);
+        writer.write_u16(self.ticket.len() as u16);
+        writer.write_bytes(&self.ticket);
+    }
+}
+
+impl HandshakeMessage for NewSessionTicket {
+    fn get_handshake_type(&self) -> HandshakeType {
+        HandshakeType::NewSessionTicket
+    }
+
+    fn encode(&self, writer: &mut BufWriter) {
+        self.encode(writer);
+    }
+}
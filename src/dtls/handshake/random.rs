@@ -4,6 +4,15 @@ use crate::common::buffer::{BufReader, BufWriter};
 
 const RANDOM_BYTES_LENGTH: usize = 28;
 
+// https://datatracker.ietf.org/doc/html/rfc8446#section-4.1.3
+// ServerHello with this random is a HelloRetryRequest.
+const HELLO_RETRY_REQUEST_RANDOM: [u8; 32] = [
+    0xcf, 0x21, 0xad, 0x74, 0xe5, 0x9a, 0x61, 0x11, 0xbe, 0x1d, 0x8c, 0x02, 0x1e, 0x65, 0xb8, 0x91,
+    0xc2, 0xa2, 0x11, 0x16, 0x7a, 0xbb, 0x8c, 0x5e, 0x07, 0x9e, 0x09, 0xe2, 0xc8, 0xa8, 0x33, 0x9c,
+];
+// the last bytes of the random of a DTLS 1.3 server negotiating DTLS 1.2.
+const DOWNGRADE_SENTINEL: [u8; 8] = *b"DOWNGRD\x01";
+
 #[derive(Debug, Clone, Copy)]
 pub struct Random {
     gmt_unix_time: SystemTime,
@@ -20,6 +29,26 @@ impl Random {
         }
     }
 
+    pub fn hello_retry_request() -> Self {
+        Self::decode(&mut BufReader::new(&HELLO_RETRY_REQUEST_RANDOM))
+            .expect("HelloRetryRequest random is 32 bytes")
+    }
+
+    pub fn is_hello_retry_request(&self) -> bool {
+        self.to_bytes() == HELLO_RETRY_REQUEST_RANDOM
+    }
+
+    /// Random of a server that supports DTLS 1.3 but negotiates DTLS 1.2.
+    pub fn with_downgrade_sentinel(mut self) -> Self {
+        self.random_bytes[RANDOM_BYTES_LENGTH - DOWNGRADE_SENTINEL.len()..]
+            .copy_from_slice(&DOWNGRADE_SENTINEL);
+        self
+    }
+
+    pub fn has_downgrade_sentinel(&self) -> bool {
+        self.random_bytes.ends_with(&DOWNGRADE_SENTINEL)
+    }
+
     pub fn decode(reader: &mut BufReader) -> anyhow::Result<Self> {
         let gmt_unix_time_u32 = reader.read_u32()?;
         let gmt_unix_time = SystemTime::UNIX_EPOCH + Duration::from_secs(gmt_unix_time_u32 as u64);
@@ -1,11 +1,13 @@
+use anyhow::Result;
+
 use crate::dtls::{
     cipher_suite::CipherSuiteId,
-    extensions::Extension,
+    extensions::{Extension, decode_extensions, encode_extensions},
     handshake::{HandshakeMessage, header::HandshakeType, random::Random},
     record_header::DtlsVersion,
     {CompressionMethodId, SessionId},
 };
-use crate::common::buffer::BufWriter;
+use crate::common::buffer::{BufReader, BufWriter};
 
 #[derive(Debug)]
 pub struct ServerHello {
@@ -18,17 +20,42 @@ pub struct ServerHello {
 }
 
 impl ServerHello {
-    pub fn new(version: DtlsVersion, random: Random, extensions: Vec<Extension>) -> Self {
+    pub fn new(
+        version: DtlsVersion,
+        random: Random,
+        cipher_suite_id: CipherSuiteId,
+        extensions: Vec<Extension>,
+    ) -> Self {
         Self {
             version,
             random,
             session_id: vec![],
-            cipher_suite_id: CipherSuiteId::TlsEcdheEcdsaWithAes128GcmSha256,
+            cipher_suite_id,
             compression_method_id: CompressionMethodId::Null,
             extensions,
         }
     }
 
+    pub fn decode(reader: &mut BufReader) -> Result<Self> {
+        let version = DtlsVersion::try_from(reader.read_u16()?)?;
+        let random = Random::decode(reader)?;
+        let session_id_length = reader.read_u8()?;
+        let mut session_id = vec![0u8; session_id_length as usize];
+        reader.read_exact(&mut session_id)?;
+        let cipher_suite_id = CipherSuiteId::from(reader.read_u16()?);
+        let compression_method_id = CompressionMethodId::from(reader.read_u8()?);
+        let extensions = decode_extensions(reader, HandshakeType::ServerHello)?;
+
+        Ok(Self {
+            version,
+            random,
+            session_id,
+            cipher_suite_id,
+            compression_method_id,
+            extensions,
+        })
+    }
+
     pub fn encode(&self, writer: &mut BufWriter) {
         writer.write_u16(self.version.into());
         self.random.encode(writer);
@@ -37,25 +64,7 @@ impl ServerHello {
         writer.write_u16(self.cipher_suite_id.into());
         writer.write_u8(self.compression_method_id.into());
 
-        let mut extensions_writer = BufWriter::new();
-        for extension in &self.extensions {
-            let mut extension_data_writer = BufWriter::new();
-            match extension {
-                Extension::RenegotiationInfo(value) => value.encode(&mut extension_data_writer),
-                Extension::UseSrtp(value) => value.encode(&mut extension_data_writer),
-                Extension::UseExtendedMasterSecret(value) => {
-                    value.encode(&mut extension_data_writer)
-                }
-                _ => continue,
-            }
-
-            extensions_writer.write_u16(extension.get_extension_type() as u16);
-            extensions_writer.write_u16(extension_data_writer.buf_ref().len() as u16);
-            extensions_writer.write_bytes(extension_data_writer.buf_ref());
-        }
-
-        writer.write_u16(extensions_writer.buf_ref().len() as u16);
-        writer.write_bytes(extensions_writer.buf_ref());
+        encode_extensions(&self.extensions, writer);
     }
 }
 
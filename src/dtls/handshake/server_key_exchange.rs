@@ -1,57 +1,80 @@
-use rcgen::{CertifiedKey, KeyPair, SigningKey};
-use x25519_dalek::PublicKey;
+use anyhow::{Result, anyhow};
+use rcgen::{KeyPair, SigningKey};
 
 use crate::dtls::{
     handshake::{HandshakeMessage, header::HandshakeType, random::Random},
     {AlgoPair, ECCurve, ECCurveType, HashAlgorithm, SignatureAlgorithm},
 };
-use crate::common::buffer::BufWriter;
+use crate::common::buffer::{BufReader, BufWriter};
 
 #[derive(Debug)]
 pub struct ServerKeyExchange {
     // https://datatracker.ietf.org/doc/html/rfc8422#autoid-18
     // https://datatracker.ietf.org/doc/html/rfc4492#section-5.4
     // ServerECDHParams
-    curve_type: ECCurveType,
-    curve: ECCurve,
-    public_key: Vec<u8>, // ephemeral public key
+    pub curve_type: ECCurveType,
+    pub curve: ECCurve,
+    pub public_key: Vec<u8>, // ephemeral public key
 
     // https://datatracker.ietf.org/doc/html/rfc5246#section-7.4.1.4.1
-    algo_pair: AlgoPair,
-    signature: Vec<u8>,
+    pub algo_pair: AlgoPair,
+    pub signature: Vec<u8>,
 }
 
 impl ServerKeyExchange {
     pub fn new(
-        public_key: PublicKey,
-        certified_key: &CertifiedKey<KeyPair>,
+        curve: ECCurve,
+        public_key: Vec<u8>,
+        private_key: &KeyPair,
         client_random: &Random,
         server_random: &Random,
-    ) -> Self {
-        let private_key = &certified_key.signing_key;
-
-        let mut message_writer = BufWriter::new();
-        message_writer.write_bytes(&client_random.to_bytes());
-        message_writer.write_bytes(&server_random.to_bytes());
-        message_writer.write_u8(ECCurveType::NamedCurve.into());
-        message_writer.write_u16(ECCurve::X25519.into());
-        message_writer.write_u8(public_key.as_bytes().len() as u8);
-        message_writer.write_bytes(public_key.as_bytes());
+    ) -> Result<Self> {
+        let mut message = Self {
+            curve_type: ECCurveType::NamedCurve,
+            curve,
+            public_key,
+            algo_pair: AlgoPair::from_key_pair(private_key)?,
+            signature: vec![],
+        };
+        let signed_params = message.signed_params(client_random, server_random);
+        message.signature = private_key.sign(&signed_params)?;
+        Ok(message)
+    }
 
-        let message_buf = message_writer.buf_ref();
-        let signature = private_key.sign(message_buf).unwrap();
+    pub fn decode(reader: &mut BufReader) -> Result<Self> {
+        let curve_type = reader.read_u8()?;
+        if curve_type != ECCurveType::NamedCurve as u8 {
+            return Err(anyhow!("unsupported curve type; {curve_type}"));
+        }
+        let curve = ECCurve::from(reader.read_u16()?);
+        let public_key_length = reader.read_u8()?;
+        let mut public_key = vec![0u8; public_key_length as usize];
+        reader.read_exact(&mut public_key)?;
+        let hash = HashAlgorithm::from(reader.read_u8()?);
+        let signature = SignatureAlgorithm::from(reader.read_u8()?);
+        let signature_length = reader.read_u16()?;
+        let mut signature_bytes = vec![0u8; signature_length as usize];
+        reader.read_exact(&mut signature_bytes)?;
 
-        // TODO: support others
-        Self {
+        Ok(Self {
             curve_type: ECCurveType::NamedCurve,
-            curve: ECCurve::X25519,
-            public_key: public_key.as_bytes().to_vec(),
-            algo_pair: AlgoPair {
-                hash: HashAlgorithm::Sha256,
-                signature: SignatureAlgorithm::Ecdsa,
-            },
-            signature: signature,
-        }
+            curve,
+            public_key,
+            algo_pair: AlgoPair { hash, signature },
+            signature: signature_bytes,
+        })
+    }
+
+    /// ServerECDHParams prefixed with both randoms; the input of `signature`.
+    pub fn signed_params(&self, client_random: &Random, server_random: &Random) -> Vec<u8> {
+        let mut writer = BufWriter::new();
+        writer.write_bytes(&client_random.to_bytes());
+        writer.write_bytes(&server_random.to_bytes());
+        writer.write_u8(self.curve_type.into());
+        writer.write_u16(self.curve.into());
+        writer.write_u8(self.public_key.len() as u8);
+        writer.write_bytes(&self.public_key);
+        writer.buf()
     }
 
     pub fn encode(&self, writer: &mut BufWriter) {
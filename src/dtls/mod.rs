@@ -1,3 +1,5 @@
+pub mod ack;
+pub mod alert;
 pub mod change_cipher_sec;
 pub mod cipher_suite;
 pub mod crypto;
@@ -5,16 +7,24 @@ pub mod extensions;
 pub mod handshake;
 pub mod manager;
 pub mod record_header;
+pub mod replay_window;
+pub mod session;
 
 use crate::dtls::{
-    change_cipher_sec::ChangeCipherSpec, handshake::HandshakeMessage, record_header::ContentType,
+    ack::Ack, alert::Alert, change_cipher_sec::ChangeCipherSpec, handshake::HandshakeMessage,
+    record_header::ContentType,
 };
 
+use crate::common::TransportMessage;
 use crate::common::buffer::BufWriter;
+use crate::sdp::FingerprintType;
+use crate::srtp::crypto::SrtpEncryptionKeys;
 use anyhow::{Result, anyhow};
 use hmac::digest::{array::Array, consts::U32};
-use sha2::{Digest, Sha256};
-use x25519_dalek::{EphemeralSecret, PublicKey};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use p256::elliptic_curve::rand_core::OsRng;
+use rcgen::KeyPair;
 
 use mini_webrtc_derive::FromPrimitive;
 
@@ -25,7 +35,9 @@ pub fn is_dtls_packet(data: &[u8]) -> bool {
 pub enum DtlsMessage {
     Handshake(Box<dyn HandshakeMessage + Send>),
     ChangeCipherSpec(ChangeCipherSpec),
+    Alert(Alert),
     ApplicationData(ApplicationDataMessage),
+    Ack(Ack),
 }
 
 pub struct ApplicationDataMessage {
@@ -37,12 +49,15 @@ impl DtlsMessage {
         match &self {
             DtlsMessage::Handshake(_) => ContentType::Handshake,
             DtlsMessage::ChangeCipherSpec(_) => ContentType::ChangeCipherSpec,
+            DtlsMessage::Alert(_) => ContentType::Alert,
             DtlsMessage::ApplicationData(_) => ContentType::ApplicationData,
+            DtlsMessage::Ack(_) => ContentType::Ack,
         }
     }
 }
 
 // https://developer.mozilla.org/en-US/docs/Web/API/RTCDtlsTransport/state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DtlsState {
     New,
     Connecting,
@@ -51,6 +66,22 @@ pub enum DtlsState {
     Failed,
 }
 
+/// Events of `DtlsManager`, drained by `poll_event`.
+pub enum DtlsEvent {
+    /// The handshake completed; SRTP keys are present when use_srtp is negotiated.
+    Connected(Option<SrtpEncryptionKeys>),
+    /// Decrypted application data of the peer.
+    ApplicationData(TransportMessage),
+}
+
+// https://datatracker.ietf.org/doc/html/rfc5763#section-5
+// `a=setup:active` endpoints are DTLS clients and `a=setup:passive` ones are servers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DtlsRole {
+    Client,
+    Server,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cookie(pub Vec<u8>); // 20 bytes
 
@@ -108,9 +139,16 @@ impl From<ECCurveType> for u8 {
 #[from(type = "u16", default = "Unsupported")]
 pub enum ECCurve {
     Unsupported = 0x0000,
+    Secp256r1 = 0x0017,
+    Secp384r1 = 0x0018,
     X25519 = 0x001d,
 }
 
+impl ECCurve {
+    /// Curves of ECDHE this endpoint implements, in order of preference.
+    pub const SUPPORTED: [ECCurve; 3] = [ECCurve::X25519, ECCurve::Secp256r1, ECCurve::Secp384r1];
+}
+
 impl From<ECCurve> for u16 {
     fn from(value: ECCurve) -> Self {
         value as u16
@@ -122,6 +160,11 @@ impl From<ECCurve> for u16 {
 pub enum HashAlgorithm {
     // https://datatracker.ietf.org/doc/html/rfc5246#section-7.4.1.4.1
     Sha256 = 4,
+    Sha384 = 5,
+    Sha512 = 6,
+    // https://datatracker.ietf.org/doc/html/rfc8446#section-4.2.3
+    // the hash of rsa_pss_rsae_* schemes is part of the signature algorithm.
+    Intrinsic = 8,
     Unsupported = 255,
 }
 
@@ -135,7 +178,9 @@ impl From<HashAlgorithm> for u8 {
 #[from(type = "u8", default = "Unsupported")]
 pub enum SignatureAlgorithm {
     // https://datatracker.ietf.org/doc/html/rfc5246#section-7.4.1.4.1
+    Rsa = 1,
     Ecdsa = 3,
+    RsaPssRsaeSha256 = 4,
     Unsupported = 255,
 }
 
@@ -145,9 +190,13 @@ impl From<SignatureAlgorithm> for u8 {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[from(type = "u8", default = "Unsupported")]
 pub enum CertificateType {
+    // https://datatracker.ietf.org/doc/html/rfc5246#section-7.4.4
+    Rsa = 1,
     Ecdsa = 64,
+    Unsupported = 255,
 }
 
 impl From<CertificateType> for u8 {
@@ -156,29 +205,119 @@ impl From<CertificateType> for u8 {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct AlgoPair {
     pub hash: HashAlgorithm,
     pub signature: SignatureAlgorithm,
 }
 
 impl AlgoPair {
+    /// Signatures this endpoint verifies, in order of preference.
+    pub const SUPPORTED: [AlgoPair; 7] = [
+        AlgoPair::new(HashAlgorithm::Sha256, SignatureAlgorithm::Ecdsa),
+        AlgoPair::new(HashAlgorithm::Sha384, SignatureAlgorithm::Ecdsa),
+        AlgoPair::new(HashAlgorithm::Sha512, SignatureAlgorithm::Ecdsa),
+        AlgoPair::new(HashAlgorithm::Intrinsic, SignatureAlgorithm::RsaPssRsaeSha256),
+        AlgoPair::new(HashAlgorithm::Sha256, SignatureAlgorithm::Rsa),
+        AlgoPair::new(HashAlgorithm::Sha384, SignatureAlgorithm::Rsa),
+        AlgoPair::new(HashAlgorithm::Sha512, SignatureAlgorithm::Rsa),
+    ];
+
+    pub const fn new(hash: HashAlgorithm, signature: SignatureAlgorithm) -> Self {
+        Self { hash, signature }
+    }
+
+    /// Signature and hash algorithms of the signatures made with `key_pair`.
+    pub fn from_key_pair(key_pair: &KeyPair) -> Result<Self> {
+        let algorithm = key_pair.algorithm();
+        let (hash, signature) = if algorithm == &rcgen::PKCS_ECDSA_P256_SHA256 {
+            (HashAlgorithm::Sha256, SignatureAlgorithm::Ecdsa)
+        } else if algorithm == &rcgen::PKCS_ECDSA_P384_SHA384 {
+            (HashAlgorithm::Sha384, SignatureAlgorithm::Ecdsa)
+        } else if algorithm == &rcgen::PKCS_RSA_SHA256 {
+            (HashAlgorithm::Sha256, SignatureAlgorithm::Rsa)
+        } else {
+            anyhow::bail!("unsupported certificate key; {algorithm:?}");
+        };
+        Ok(Self { hash, signature })
+    }
+
     pub fn encode(&self, writer: &mut BufWriter) {
         writer.write_u8(self.hash.into());
         writer.write_u8(self.signature.into());
     }
 }
 
+pub enum CurveSecret {
+    X25519(x25519_dalek::EphemeralSecret),
+    Secp256r1(p256::ecdh::EphemeralSecret),
+    Secp384r1(p384::ecdh::EphemeralSecret),
+}
+
+impl CurveSecret {
+    /// Computes the pre-master secret from the ephemeral public key of the peer.
+    // https://datatracker.ietf.org/doc/html/rfc8422#section-5.10
+    pub fn diffie_hellman(self, peer_public_key: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            CurveSecret::X25519(secret) => {
+                let peer_public_key: [u8; 32] = peer_public_key
+                    .try_into()
+                    .or(Err(anyhow!("invalid X25519 public key length.")))?;
+                Ok(secret
+                    .diffie_hellman(&x25519_dalek::PublicKey::from(peer_public_key))
+                    .as_bytes()
+                    .to_vec())
+            }
+            CurveSecret::Secp256r1(secret) => {
+                let peer_public_key = p256::PublicKey::from_sec1_bytes(peer_public_key)
+                    .map_err(|err| anyhow!("invalid secp256r1 public key; {err}"))?;
+                Ok(secret
+                    .diffie_hellman(&peer_public_key)
+                    .raw_secret_bytes()
+                    .to_vec())
+            }
+            CurveSecret::Secp384r1(secret) => {
+                let peer_public_key = p384::PublicKey::from_sec1_bytes(peer_public_key)
+                    .map_err(|err| anyhow!("invalid secp384r1 public key; {err}"))?;
+                Ok(secret
+                    .diffie_hellman(&peer_public_key)
+                    .raw_secret_bytes()
+                    .to_vec())
+            }
+        }
+    }
+}
+
 pub struct CurveKeyPair {
-    pub public_key: PublicKey,
-    pub secret: EphemeralSecret,
+    pub curve: ECCurve,
+    pub public_key: Vec<u8>, // X25519 key or uncompressed point
+    pub secret: CurveSecret,
 }
 
-pub fn generate_curve_key_pair() -> CurveKeyPair {
-    // generate X25519 key pair
-    let secret = EphemeralSecret::random();
-    let public_key = PublicKey::from(&secret);
-    CurveKeyPair { public_key, secret }
+pub fn generate_curve_key_pair(curve: ECCurve) -> Result<CurveKeyPair> {
+    let (public_key, secret) = match curve {
+        ECCurve::X25519 => {
+            let secret = x25519_dalek::EphemeralSecret::random();
+            let public_key = x25519_dalek::PublicKey::from(&secret);
+            (public_key.as_bytes().to_vec(), CurveSecret::X25519(secret))
+        }
+        ECCurve::Secp256r1 => {
+            let secret = p256::ecdh::EphemeralSecret::random(&mut OsRng);
+            let public_key = p256::EncodedPoint::from(secret.public_key());
+            (public_key.as_bytes().to_vec(), CurveSecret::Secp256r1(secret))
+        }
+        ECCurve::Secp384r1 => {
+            let secret = p384::ecdh::EphemeralSecret::random(&mut OsRng);
+            let public_key = p384::EncodedPoint::from(secret.public_key());
+            (public_key.as_bytes().to_vec(), CurveSecret::Secp384r1(secret))
+        }
+        ECCurve::Unsupported => anyhow::bail!("unsupported curve; {curve:?}"),
+    };
+    Ok(CurveKeyPair {
+        curve,
+        public_key,
+        secret,
+    })
 }
 
 #[derive(Debug, Clone)]
@@ -190,10 +329,24 @@ impl Fingerprint {
     }
 
     pub fn to_string(&self) -> String {
-        self.0
-            .iter()
-            .map(|b| format!("{:02X}", b))
-            .collect::<Vec<_>>()
-            .join(":")
+        encode_fingerprint(&self.0)
     }
 }
+
+/// Hash of a DER certificate in the form of `a=fingerprint`.
+// https://datatracker.ietf.org/doc/html/rfc8122#section-5
+pub fn certificate_fingerprint(certificate: &[u8], fingerprint_type: FingerprintType) -> String {
+    match fingerprint_type {
+        FingerprintType::Sha1 => encode_fingerprint(&Sha1::digest(certificate)),
+        FingerprintType::Sha256 => encode_fingerprint(&Sha256::digest(certificate)),
+        FingerprintType::Sha384 => encode_fingerprint(&Sha384::digest(certificate)),
+        FingerprintType::Sha512 => encode_fingerprint(&Sha512::digest(certificate)),
+    }
+}
+
+fn encode_fingerprint(hash: &[u8]) -> String {
+    hash.iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
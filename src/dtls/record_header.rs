@@ -10,14 +10,17 @@ pub enum ContentType {
     Alert = 21,
     Handshake = 22,
     ApplicationData = 23,
+    // https://datatracker.ietf.org/doc/html/rfc9147#section-7
+    Ack = 26,
 }
 
 #[derive(TryFromPrimitive)]
 #[try_from(type = "u16")]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DtlsVersion {
     V1_0 = 0xfeff,
     V1_2 = 0xfefd,
+    V1_3 = 0xfefc,
 }
 
 impl Into<u16> for DtlsVersion {
@@ -25,10 +28,13 @@ impl Into<u16> for DtlsVersion {
         match self {
             Self::V1_0 => self as u16,
             Self::V1_2 => self as u16,
+            Self::V1_3 => self as u16,
         }
     }
 }
 
+pub const RECORD_HEADER_BYTES: usize = 13;
+
 #[derive(Debug, Clone)]
 pub struct RecordHeader {
     pub content_type: ContentType,
@@ -89,3 +95,80 @@ impl RecordHeader {
         writer.write_u16(self.length);
     }
 }
+
+// https://datatracker.ietf.org/doc/html/rfc9147#section-4
+// 0 0 1 C S L E E
+const UNIFIED_HEADER_FIXED_BITS: u8 = 0b0010_0000;
+const UNIFIED_HEADER_CONNECTION_ID_BIT: u8 = 0b0001_0000;
+const UNIFIED_HEADER_SEQUENCE_NUMBER_BIT: u8 = 0b0000_1000;
+const UNIFIED_HEADER_LENGTH_BIT: u8 = 0b0000_0100;
+const UNIFIED_HEADER_EPOCH_BITS: u8 = 0b0000_0011;
+
+/// Header of DTLS 1.3 protected records; the sequence number is encrypted on the wire.
+#[derive(Debug, Clone)]
+pub struct UnifiedHeader {
+    pub epoch_bits: u8,       // low 2 bits of the epoch
+    pub sequence_number: u16, // low 8 or 16 bits of the sequence number
+    pub sequence_number_length: usize,
+    pub length: Option<u16>,
+}
+
+impl UnifiedHeader {
+    /// Header with a 16-bit sequence number and a length, as sent by this endpoint.
+    pub fn new(epoch: u16, sequence_number: u64, length: u16) -> Self {
+        Self {
+            epoch_bits: (epoch as u8) & UNIFIED_HEADER_EPOCH_BITS,
+            sequence_number: sequence_number as u16,
+            sequence_number_length: 2,
+            length: Some(length),
+        }
+    }
+
+    pub fn is_unified_header(first_byte: u8) -> bool {
+        first_byte & 0b1110_0000 == UNIFIED_HEADER_FIXED_BITS
+    }
+
+    pub fn decode(reader: &mut BufReader) -> anyhow::Result<Self> {
+        let flags = reader.read_u8()?;
+        if flags & UNIFIED_HEADER_CONNECTION_ID_BIT != 0 {
+            anyhow::bail!("connection id is not supported");
+        }
+        let (sequence_number, sequence_number_length) =
+            if flags & UNIFIED_HEADER_SEQUENCE_NUMBER_BIT != 0 {
+                (reader.read_u16()?, 2)
+            } else {
+                (reader.read_u8()? as u16, 1)
+            };
+        let length = if flags & UNIFIED_HEADER_LENGTH_BIT != 0 {
+            Some(reader.read_u16()?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            epoch_bits: flags & UNIFIED_HEADER_EPOCH_BITS,
+            sequence_number,
+            sequence_number_length,
+            length,
+        })
+    }
+
+    pub fn encode(&self, writer: &mut BufWriter) {
+        let mut flags = UNIFIED_HEADER_FIXED_BITS | self.epoch_bits;
+        if self.sequence_number_length == 2 {
+            flags |= UNIFIED_HEADER_SEQUENCE_NUMBER_BIT;
+        }
+        if self.length.is_some() {
+            flags |= UNIFIED_HEADER_LENGTH_BIT;
+        }
+        writer.write_u8(flags);
+        if self.sequence_number_length == 2 {
+            writer.write_u16(self.sequence_number);
+        } else {
+            writer.write_u8(self.sequence_number as u8);
+        }
+        if let Some(length) = self.length {
+            writer.write_u16(length);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+// https://datatracker.ietf.org/doc/html/rfc6347#section-4.1.2.6
+const REPLAY_WINDOW_SIZE: u64 = 64;
+
+/// Sliding window of the record sequence numbers received in an epoch.
+#[derive(Debug, Default, Clone)]
+pub struct ReplayWindow {
+    // highest sequence number received; none before the first record
+    latest: Option<u64>,
+    // bit i is set when `latest - i` has been received
+    bitmap: u64,
+}
+
+impl ReplayWindow {
+    /// Whether a record is neither a duplicate nor older than the window.
+    pub fn check(&self, sequence_number: u64) -> bool {
+        let Some(latest) = self.latest else {
+            return true;
+        };
+        if sequence_number > latest {
+            return true;
+        }
+        let offset = latest - sequence_number;
+        offset < REPLAY_WINDOW_SIZE && self.bitmap & (1 << offset) == 0
+    }
+
+    /// Marks a record as received; only authenticated records may update the window.
+    pub fn update(&mut self, sequence_number: u64) {
+        match self.latest {
+            Some(latest) if sequence_number <= latest => {
+                let offset = latest - sequence_number;
+                if offset < REPLAY_WINDOW_SIZE {
+                    self.bitmap |= 1 << offset;
+                }
+            }
+            Some(latest) => {
+                let shift = sequence_number - latest;
+                self.bitmap = match shift < REPLAY_WINDOW_SIZE {
+                    true => self.bitmap << shift | 1,
+                    false => 1,
+                };
+                self.latest = Some(sequence_number);
+            }
+            None => {
+                self.bitmap = 1;
+                self.latest = Some(sequence_number);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod replay_window_tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_window() {
+        let mut window = ReplayWindow::default();
+        for sequence_number in [0, 2, 1, 70] {
+            assert!(window.check(sequence_number));
+            window.update(sequence_number);
+            assert!(!window.check(sequence_number));
+        }
+        // 2 is older than the window of 70
+        assert!(!window.check(2));
+        assert!(window.check(7));
+        window.update(7);
+        assert!(!window.check(7));
+        assert!(window.check(69));
+
+        window.update(1000);
+        assert!(!window.check(70));
+        assert!(window.check(999));
+        assert!(window.check(1001));
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use tracing::debug;
+
+use crate::common::buffer::{BufReader, BufWriter};
+use crate::dtls::{
+    Cookie, HashAlgorithm, SessionId,
+    cipher_suite::CipherSuiteId,
+    crypto::{TicketKey, hmac_sha},
+};
+
+// https://datatracker.ietf.org/doc/html/rfc5246#appendix-F.1.4
+// sessions should not be resumed after more than 24 hours.
+pub const DEFAULT_SESSION_LIFETIME: Duration = Duration::from_secs(2 * 60 * 60);
+pub const DEFAULT_TICKET_KEY_ROTATION: Duration = Duration::from_secs(60 * 60);
+const MAX_SESSIONS: usize = 1024;
+const SESSION_ID_LENGTH: usize = 32;
+const COOKIE_LENGTH: usize = 20;
+
+/// State of a DTLS 1.2 session, which an abbreviated handshake resumes.
+// https://datatracker.ietf.org/doc/html/rfc5246#section-7.3
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: SessionId,
+    /// Ticket issued by the server; empty when the server caches the session by its id.
+    pub ticket: Vec<u8>,
+    pub cipher_suite_id: CipherSuiteId,
+    pub master_secret: Vec<u8>,
+    pub use_extended_master_secret: bool,
+    /// Certificate of the peer, verified against `a=fingerprint` again on resumption.
+    pub remote_certificate: Option<Vec<u8>>,
+    pub created_at: SystemTime,
+}
+
+impl Session {
+    pub fn generate_id() -> SessionId {
+        let mut id = vec![0u8; SESSION_ID_LENGTH];
+        rand::fill(id.as_mut_slice());
+        id
+    }
+
+    pub fn is_expired(&self, lifetime: Duration) -> bool {
+        self.created_at
+            .elapsed()
+            .map_or(true, |elapsed| elapsed >= lifetime)
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc5077#section-4
+    // the state sealed in a ticket; the id and the ticket itself are not part of it.
+    fn encode_state(&self, writer: &mut BufWriter) {
+        writer.write_u16(self.cipher_suite_id.into());
+        writer.write_u8(self.use_extended_master_secret as u8);
+        writer.write_u8(self.master_secret.len() as u8);
+        writer.write_bytes(&self.master_secret);
+        let created_at = self
+            .created_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        writer.write_bytes(&created_at.to_be_bytes());
+        let remote_certificate = self.remote_certificate.as_deref().unwrap_or_default();
+        writer.write_u24(remote_certificate.len() as u32);
+        writer.write_bytes(remote_certificate);
+    }
+
+    fn decode_state(reader: &mut BufReader) -> Result<Self> {
+        let cipher_suite_id = CipherSuiteId::from(reader.read_u16()?);
+        let use_extended_master_secret = reader.read_u8()? != 0;
+        let mut master_secret = vec![0u8; reader.read_u8()? as usize];
+        reader.read_exact(&mut master_secret)?;
+        let mut created_at = [0u8; 8];
+        reader.read_exact(&mut created_at)?;
+        let mut remote_certificate = vec![0u8; reader.read_u24()? as usize];
+        reader.read_exact(&mut remote_certificate)?;
+
+        Ok(Self {
+            id: vec![],
+            ticket: vec![],
+            cipher_suite_id,
+            master_secret,
+            use_extended_master_secret,
+            remote_certificate: (!remote_certificate.is_empty()).then_some(remote_certificate),
+            created_at: UNIX_EPOCH + Duration::from_secs(u64::from_be_bytes(created_at)),
+        })
+    }
+}
+
+/// Sessions and ticket keys shared by the `DtlsManager`s of an endpoint, so that a
+/// reconnecting peer resumes its previous session instead of a full handshake.
+pub struct SessionCache {
+    /// Sessions and tickets older than this are not resumed.
+    pub lifetime: Duration,
+    /// Tickets are sealed by a new key after this period; tickets of the previous key are
+    /// still accepted for another period and renewed on use.
+    pub ticket_key_rotation: Duration,
+    // sessions of clients by id, when the client does not support tickets
+    sessions: HashMap<SessionId, Session>,
+    // sessions with servers by their address
+    client_sessions: HashMap<SocketAddr, Session>,
+    ticket_key: TicketKey,
+    previous_ticket_key: Option<TicketKey>,
+    ticket_key_created_at: Instant,
+    cookie_secret: [u8; 32],
+}
+
+impl Default for SessionCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionCache {
+    pub fn new() -> Self {
+        Self {
+            lifetime: DEFAULT_SESSION_LIFETIME,
+            ticket_key_rotation: DEFAULT_TICKET_KEY_ROTATION,
+            sessions: HashMap::new(),
+            client_sessions: HashMap::new(),
+            ticket_key: TicketKey::new(),
+            previous_ticket_key: None,
+            ticket_key_created_at: Instant::now(),
+            cookie_secret: rand::random(),
+        }
+    }
+
+    /// Cookie of a HelloVerifyRequest to `peer_addr`; derived from a secret of the endpoint,
+    /// so a ClientHello returning it is verified without keeping state for the client.
+    // https://datatracker.ietf.org/doc/html/rfc6347#section-4.2.1
+    pub fn cookie(&self, peer_addr: SocketAddr) -> Cookie {
+        let mac = hmac_sha(
+            HashAlgorithm::Sha256,
+            &self.cookie_secret,
+            peer_addr.to_string().as_bytes(),
+        );
+        Cookie(mac[..COOKIE_LENGTH].to_vec())
+    }
+
+    pub fn insert(&mut self, session: Session) {
+        if self.sessions.len() >= MAX_SESSIONS {
+            let lifetime = self.lifetime;
+            self.sessions
+                .retain(|_, session| !session.is_expired(lifetime));
+        }
+        if self.sessions.len() >= MAX_SESSIONS
+            && let Some(oldest) = self
+                .sessions
+                .values()
+                .min_by_key(|session| session.created_at)
+                .map(|session| session.id.clone())
+        {
+            self.sessions.remove(&oldest);
+        }
+        self.sessions.insert(session.id.clone(), session);
+    }
+
+    pub fn get(&self, id: &[u8]) -> Option<Session> {
+        self.sessions
+            .get(id)
+            .filter(|session| !session.is_expired(self.lifetime))
+            .cloned()
+    }
+
+    pub fn insert_client_session(&mut self, peer_addr: SocketAddr, session: Session) {
+        self.client_sessions.insert(peer_addr, session);
+    }
+
+    pub fn remove_client_session(&mut self, peer_addr: SocketAddr) {
+        self.client_sessions.remove(&peer_addr);
+    }
+
+    pub fn client_session(&self, peer_addr: SocketAddr) -> Option<Session> {
+        self.client_sessions
+            .get(&peer_addr)
+            .filter(|session| !session.is_expired(self.lifetime))
+            .cloned()
+    }
+
+    /// Seals a session into a ticket with the current key.
+    pub fn seal_ticket(&mut self, session: &Session) -> Result<Vec<u8>> {
+        self.rotate_expired_ticket_key();
+        let mut writer = BufWriter::new();
+        session.encode_state(&mut writer);
+        self.ticket_key.seal(writer.buf_ref())
+    }
+
+    /// Opens a ticket of the current or the previous key; the flag tells that it was
+    /// sealed by the previous key and should be renewed.
+    pub fn open_ticket(&mut self, ticket: &[u8]) -> Option<(Session, bool)> {
+        self.rotate_expired_ticket_key();
+        let (state, renew) = match self.ticket_key.open(ticket) {
+            Ok(state) => (state, false),
+            Err(err) => match &self.previous_ticket_key {
+                Some(ticket_key) => (ticket_key.open(ticket).ok()?, true),
+                None => {
+                    debug!("ignore session ticket; {err}");
+                    return None;
+                }
+            },
+        };
+        Session::decode_state(&mut BufReader::new(&state))
+            .ok()
+            .filter(|session| !session.is_expired(self.lifetime))
+            .map(|session| (session, renew))
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc5077#section-5.5
+    pub fn rotate_ticket_key(&mut self) {
+        debug!("rotate session ticket key");
+        let ticket_key = std::mem::take(&mut self.ticket_key);
+        self.previous_ticket_key = Some(ticket_key);
+        self.ticket_key_created_at = Instant::now();
+    }
+
+    fn rotate_expired_ticket_key(&mut self) {
+        let age = self.ticket_key_created_at.elapsed();
+        if age >= self.ticket_key_rotation * 2 {
+            // the previous key would have expired as well
+            self.ticket_key = TicketKey::new();
+            self.previous_ticket_key = None;
+            self.ticket_key_created_at = Instant::now();
+        } else if age >= self.ticket_key_rotation {
+            self.rotate_ticket_key();
+        }
+    }
+}
@@ -0,0 +1,1373 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use tracing::{debug, info, warn};
+
+use crate::{
+    common::error::MiniWebrtcRsError,
+    ice::{
+        IceCandidate, IceConnectionState, IceGatheringState, IceRole, Peer,
+        candidate_pair::{CandidatePair, CandidatePairState},
+        generate_ice_pwd, generate_ice_ufrag,
+    },
+    sdp::{
+        CandidateType, FingerprintType, MediaDirection, MediaType, Rtp, SdpMedia,
+        SdpMediaCandidate, SdpMessage, Setup, TransportType, session_description::SdpFingerprint,
+    },
+    stun::{
+        AttributeType, StunAttribute, StunMessage, StunMessageBuilder, StunMessageClass,
+        StunMessageMethod, StunMessageType, generate_transaction_id,
+    },
+};
+
+// https://datatracker.ietf.org/doc/html/rfc8445#section-14.2
+const TA: Duration = Duration::from_millis(50);
+// https://datatracker.ietf.org/doc/html/rfc8445#section-14.3
+const INITIAL_RTO: Duration = Duration::from_millis(500);
+// Rc and Rm of https://datatracker.ietf.org/doc/html/rfc5389#section-7.2.1
+const MAX_TRANSMISSIONS: u32 = 7;
+const LAST_RTO_MULTIPLIER: u32 = 16;
+const NOMINATION_DELAY: Duration = Duration::from_secs(2);
+// https://datatracker.ietf.org/doc/html/rfc7675#section-5.1
+const CONSENT_INTERVAL: Duration = Duration::from_secs(5);
+const CONSENT_TIMEOUT: Duration = Duration::from_secs(30);
+// reported as disconnected when no consent response arrived for this long.
+const DISCONNECTED_TIMEOUT: Duration = Duration::from_secs(10);
+// https://datatracker.ietf.org/doc/html/rfc8489#section-14.8
+const BAD_REQUEST: u16 = 400;
+const UNAUTHORIZED: u16 = 401;
+const UNKNOWN_ATTRIBUTE: u16 = 420;
+const ROLE_CONFLICT: u16 = 487;
+
+#[derive(Debug, Clone)]
+pub struct Transmit {
+    /// Base of the local candidate to send from; the host socket or a TURN allocation.
+    pub from: SocketAddr,
+    pub to: SocketAddr,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TransactionKind {
+    Check { pair: usize, use_candidate: bool },
+    Consent,
+}
+
+#[derive(Debug, Clone)]
+struct Transaction {
+    kind: TransactionKind,
+    role: IceRole,
+    from: SocketAddr,
+    to: SocketAddr,
+    request: Vec<u8>,
+    transmissions: u32,
+    max_transmissions: u32,
+    rto: Duration,
+    next_at: Instant,
+}
+
+/// RFC 8445 agent for a single (bundled, rtcp-muxed) component.
+/// It does no I/O: inbound stun messages and timeouts are fed in, and datagrams to send
+/// are taken out with `poll_transmit`.
+#[derive(Debug, Clone)]
+pub struct IceAgent {
+    pub ice_candidates: Vec<IceCandidate>,
+    pub local_peer: Peer,
+    pub remote_peers: Vec<Peer>,
+    pub remote_candidates: Vec<IceCandidate>,
+    pub gathering_state: IceGatheringState,
+    /// The remote signaled end-of-candidates or does not trickle; checks cannot fail before that.
+    // https://datatracker.ietf.org/doc/html/rfc8838#section-8.2
+    pub remote_end_of_candidates: bool,
+    pub role: IceRole,
+    pub tie_breaker: u64,
+    pub state: IceConnectionState,
+    pub check_list: Vec<CandidatePair>,
+    pub selected_pair: Option<usize>,
+    triggered_check_queue: VecDeque<(usize, bool)>, // (pair, use_candidate)
+    transactions: HashMap<Vec<u8>, Transaction>,
+    // remotes that sent a request or a success response with a valid MESSAGE-INTEGRITY
+    authenticated_addrs: HashSet<SocketAddr>,
+    next_check_at: Option<Instant>,
+    first_success_at: Option<Instant>,
+    nominating: bool,
+    next_consent_at: Option<Instant>,
+    consent_expires_at: Option<Instant>,
+    transmits: VecDeque<Transmit>,
+}
+
+impl IceAgent {
+    pub fn new(ice_candidates: Vec<IceCandidate>, fingerprints: Vec<SdpFingerprint>) -> Self {
+        Self {
+            ice_candidates,
+            local_peer: Peer {
+                ufrag: generate_ice_ufrag(),
+                pwd: generate_ice_pwd(),
+                fingerprints,
+            },
+            remote_peers: vec![],
+            remote_candidates: vec![],
+            gathering_state: IceGatheringState::New,
+            remote_end_of_candidates: false,
+            role: IceRole::Controlling,
+            tie_breaker: rand::random(),
+            state: IceConnectionState::New,
+            check_list: vec![],
+            selected_pair: None,
+            triggered_check_queue: VecDeque::new(),
+            transactions: HashMap::new(),
+            authenticated_addrs: HashSet::new(),
+            next_check_at: None,
+            first_success_at: None,
+            nominating: false,
+            next_consent_at: None,
+            consent_expires_at: None,
+            transmits: VecDeque::new(),
+        }
+    }
+
+    pub fn generate_sdp_offer(&self) -> SdpMessage {
+        let (fingerprint_type, fingerprint_hash, extra_fingerprints) = self.local_fingerprints();
+        SdpMessage {
+            session_id: "123456789".to_string(),
+            medias: vec![
+                SdpMedia {
+                    media_id: "0".to_string(),
+                    media_type: MediaType::Video,
+                    stream_id: "stream0".to_string(),
+                    track_id: "track0".to_string(),
+                    direction: MediaDirection::Recvonly,
+                    payloads: "96".to_string(), // VP8
+                    rtp: vec![Rtp {
+                        payload: 96,
+                        codec: "VP8".to_string(),
+                        rate: 90000,
+                    }],
+                    ufrag: self.local_peer.ufrag.clone(),
+                    pwd: self.local_peer.pwd.clone(),
+                    fingerprint_type,
+                    fingerprint_hash: fingerprint_hash.clone(),
+                    extra_fingerprints: extra_fingerprints.clone(),
+                    setup: Some(Setup::Actpass),
+                    candidates: self.signaled_candidates(),
+                    end_of_candidates: self.local_end_of_candidates(),
+                    ice_options: vec!["trickle".to_string()],
+                    rtcp_mux: Some("rtcp-mux".to_string()),
+                    protocol: "UDP/TLS/RTP/SAVPF".to_string(),
+                    sctp_port: None,
+                    max_message_size: None,
+                },
+                SdpMedia {
+                    media_id: "1".to_string(),
+                    media_type: MediaType::Application,
+                    stream_id: "stream0".to_string(),
+                    track_id: "track0".to_string(),
+                    direction: MediaDirection::Recvonly,
+                    payloads: "webrtc-datachannel".to_string(),
+                    rtp: vec![],
+                    ufrag: self.local_peer.ufrag.clone(),
+                    pwd: self.local_peer.pwd.clone(),
+                    fingerprint_type,
+                    fingerprint_hash: fingerprint_hash.clone(),
+                    extra_fingerprints: extra_fingerprints.clone(),
+                    setup: Some(Setup::Actpass),
+                    candidates: self.signaled_candidates(),
+                    end_of_candidates: self.local_end_of_candidates(),
+                    ice_options: vec!["trickle".to_string()],
+                    rtcp_mux: None,
+                    protocol: "UDP/DTLS/SCTP".to_string(),
+                    sctp_port: Some(4433),
+                    max_message_size: None,
+                },
+            ],
+        }
+    }
+
+    pub fn generate_sdp_answer(&self, offer: &SdpMessage) -> Result<SdpMessage> {
+        let (fingerprint_type, fingerprint_hash, extra_fingerprints) = self.local_fingerprints();
+        let medias = offer
+            .medias
+            .iter()
+            .map(|media| {
+                // https://datatracker.ietf.org/doc/html/rfc5763#section-5
+                // prefer the DTLS server role unless the remote insists on it.
+                let setup = match media.setup {
+                    None | Some(Setup::Actpass) | Some(Setup::Active) => Setup::Passive,
+                    Some(Setup::Passive) => Setup::Active,
+                    Some(setup) => Err(MiniWebrtcRsError::NotImplementedError {
+                        message: format!("answer to remote setup `{setup:?}`"),
+                    })?,
+                };
+                let direction = match media.direction {
+                    MediaDirection::Sendrecv | MediaDirection::Sendonly => MediaDirection::Recvonly,
+                    MediaDirection::Recvonly | MediaDirection::Inactive => MediaDirection::Inactive,
+                };
+                let rtp = media
+                    .rtp
+                    .iter()
+                    .filter(|rtp| rtp.codec.eq_ignore_ascii_case("VP8"))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let payloads = match media.media_type {
+                    MediaType::Application => media.payloads.clone(),
+                    _ => rtp
+                        .iter()
+                        .map(|rtp| rtp.payload.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                };
+
+                Ok(SdpMedia {
+                    media_id: media.media_id.clone(),
+                    media_type: media.media_type,
+                    stream_id: "stream0".to_string(),
+                    track_id: format!("track{}", media.media_id),
+                    direction,
+                    payloads,
+                    rtp,
+                    ufrag: self.local_peer.ufrag.clone(),
+                    pwd: self.local_peer.pwd.clone(),
+                    fingerprint_type,
+                    fingerprint_hash: fingerprint_hash.clone(),
+                    extra_fingerprints: extra_fingerprints.clone(),
+                    setup: Some(setup),
+                    candidates: self.signaled_candidates(),
+                    end_of_candidates: self.local_end_of_candidates(),
+                    ice_options: vec!["trickle".to_string()],
+                    rtcp_mux: media.rtcp_mux.clone(),
+                    protocol: media.protocol.clone(),
+                    sctp_port: media.sctp_port,
+                    max_message_size: None,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(SdpMessage {
+            session_id: "123456789".to_string(),
+            medias,
+        })
+    }
+
+    /// First local `a=fingerprint` as `SdpMedia` carries it, followed by the others.
+    fn local_fingerprints(&self) -> (FingerprintType, String, Vec<SdpFingerprint>) {
+        let fingerprints = &self.local_peer.fingerprints;
+        let (fingerprint_type, fingerprint_hash) = fingerprints
+            .first()
+            .map_or((FingerprintType::Sha256, String::new()), |fingerprint| {
+                (fingerprint.hash_function, fingerprint.fingerprint.clone())
+            });
+        let extra_fingerprints = fingerprints.iter().skip(1).cloned().collect();
+        (fingerprint_type, fingerprint_hash, extra_fingerprints)
+    }
+
+    pub fn close(&mut self) {
+        self.state = IceConnectionState::Closed;
+        self.selected_pair = None;
+        self.transactions.clear();
+        self.triggered_check_queue.clear();
+        self.next_check_at = None;
+        self.next_consent_at = None;
+        self.consent_expires_at = None;
+    }
+
+    pub fn selected_remote_addr(&self) -> Option<SocketAddr> {
+        self.selected_pair
+            .map(|pair| self.check_list[pair].remote.addr())
+    }
+
+    /// Base of the local candidate of the selected pair; media is sent from it.
+    pub fn selected_local_addr(&self) -> Option<SocketAddr> {
+        self.selected_pair
+            .map(|pair| self.check_list[pair].local.base())
+    }
+
+    pub fn set_role(&mut self, role: IceRole) {
+        if self.role == role {
+            return;
+        }
+        debug!("ice role changed; {:?} -> {role:?}", self.role);
+        self.role = role;
+        self.nominating = false;
+        for pair in &mut self.check_list {
+            pair.update_priority(role);
+        }
+    }
+
+    /// Pairs the remote candidate with every local candidate and returns the index of
+    /// its first pair in the check list.
+    pub fn add_remote_candidate(&mut self, remote: IceCandidate) -> Option<usize> {
+        if remote.is_unresolved() {
+            return None;
+        }
+        if let Some(pair) = self
+            .check_list
+            .iter()
+            .position(|pair| pair.remote.addr() == remote.addr())
+        {
+            return Some(pair);
+        }
+        // kept for pairing with local candidates gathered later.
+        if !self
+            .remote_candidates
+            .iter()
+            .any(|other| other.addr() == remote.addr())
+        {
+            self.remote_candidates.push(remote.clone());
+        }
+        // https://datatracker.ietf.org/doc/html/rfc8445#section-6.1.2.4
+        // srflx candidates are replaced by their base, so only candidates that are their own
+        // base (host and relay) are paired.
+        let locals = self
+            .ice_candidates
+            .iter()
+            .filter(|local| {
+                local.base() == local.addr()
+                    && local.ip.is_ipv4() == remote.ip.is_ipv4()
+                    && local.transport_type == remote.transport_type
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if locals.is_empty() {
+            return None;
+        }
+
+        let first = self.check_list.len();
+        for local in locals {
+            let mut pair = CandidatePair::new(local, remote.clone(), self.role);
+            // https://datatracker.ietf.org/doc/html/rfc8445#section-6.1.2.6
+            if !self
+                .check_list
+                .iter()
+                .any(|other| other.foundation == pair.foundation)
+            {
+                pair.state = CandidatePairState::Waiting;
+            }
+            self.check_list.push(pair);
+        }
+        Some(first)
+    }
+
+    /// Adds a gathered local candidate and pairs it with the remote candidates known so far,
+    /// like `add_remote_candidate` pairs a remote candidate with the local ones.
+    // https://datatracker.ietf.org/doc/html/rfc8838#section-10
+    pub fn add_local_candidate(&mut self, local: IceCandidate, now: Instant) {
+        self.ice_candidates.push(local.clone());
+        // srflx candidates are replaced by their base, which is paired already.
+        if local.base() != local.addr() {
+            return;
+        }
+        let remotes = self
+            .remote_candidates
+            .iter()
+            .filter(|remote| {
+                local.ip.is_ipv4() == remote.ip.is_ipv4()
+                    && local.transport_type == remote.transport_type
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if remotes.is_empty() {
+            return;
+        }
+        for remote in remotes {
+            let mut pair = CandidatePair::new(local.clone(), remote, self.role);
+            if !self
+                .check_list
+                .iter()
+                .any(|other| other.foundation == pair.foundation)
+            {
+                pair.state = CandidatePairState::Waiting;
+            }
+            self.check_list.push(pair);
+        }
+        if self.state == IceConnectionState::Checking {
+            self.next_check_at.get_or_insert(now);
+        }
+    }
+
+    /// Adds a trickled remote candidate; checks start right away if they are already running.
+    // https://datatracker.ietf.org/doc/html/rfc8838#section-11
+    pub fn add_ice_candidate(&mut self, remote: IceCandidate, now: Instant) {
+        let addr = remote.addr();
+        if self.add_remote_candidate(remote).is_none() {
+            debug!("no local candidate to pair with; remote={addr}");
+            return;
+        }
+        if self.state == IceConnectionState::Checking {
+            self.next_check_at.get_or_insert(now);
+        }
+    }
+
+    /// Takes the end-of-candidates of a remote description; a remote without trickle ICE
+    /// has signaled all of its candidates in the description.
+    // https://datatracker.ietf.org/doc/html/rfc8838#section-8
+    pub fn set_remote_description(&mut self, media: &SdpMedia) {
+        self.remote_end_of_candidates |=
+            media.end_of_candidates.is_some() || !media.supports_trickle();
+    }
+
+    /// Whether `remote` has proven to know the credentials, by a request or a success response
+    /// with a valid MESSAGE-INTEGRITY; datagrams of any other source are not accepted.
+    pub fn is_authenticated(&self, remote: SocketAddr) -> bool {
+        self.authenticated_addrs.contains(&remote)
+    }
+
+    /// Starts connectivity checks once the remote credentials are known.
+    pub fn start_checks(&mut self, now: Instant) {
+        if self.state == IceConnectionState::New {
+            self.state = IceConnectionState::Checking;
+        }
+        self.next_check_at = Some(now);
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<Transmit> {
+        self.transmits.pop_front()
+    }
+
+    pub fn next_timeout(&self) -> Option<Instant> {
+        let nomination_at = self
+            .first_success_at
+            .filter(|_| self.can_nominate())
+            .map(|at| at + NOMINATION_DELAY);
+        self.transactions
+            .values()
+            .map(|transaction| transaction.next_at)
+            .chain(self.next_check_at)
+            .chain(self.next_consent_at)
+            .chain(self.consent_expires_at)
+            .chain(self.disconnected_at())
+            .chain(nomination_at)
+            .min()
+    }
+
+    pub fn handle_timeout(&mut self, now: Instant) {
+        if matches!(
+            self.state,
+            IceConnectionState::Failed | IceConnectionState::Closed
+        ) {
+            return;
+        }
+
+        self.handle_transaction_timeouts(now);
+        self.handle_consent_timeouts(now);
+        self.maybe_nominate(now);
+
+        if self.next_check_at.is_some_and(|at| at <= now) {
+            // https://datatracker.ietf.org/doc/html/rfc8445#section-6.1.4.2
+            match self.next_check() {
+                Some((pair, use_candidate)) => {
+                    self.send_check(pair, use_candidate, now);
+                    self.next_check_at = Some(now + TA);
+                }
+                None => self.next_check_at = None,
+            }
+        }
+
+        self.update_state(now);
+    }
+
+    /// Handles a stun message received on `local`; the host socket or a TURN allocation.
+    pub fn handle_stun_message(
+        &mut self,
+        message: &StunMessage,
+        local: SocketAddr,
+        from: SocketAddr,
+        now: Instant,
+    ) -> Result<()> {
+        // https://datatracker.ietf.org/doc/html/rfc8445#section-7.2.2
+        if message.attributes.contains_key(&AttributeType::Fingerprint)
+            && !message.verify_fingerprint()?
+        {
+            warn!("fingerprint mismatch; ignore the message.");
+            return Ok(());
+        }
+        match message.message_type.class {
+            StunMessageClass::Request => self.handle_binding_request(message, local, from, now)?,
+            StunMessageClass::SuccessResponse | StunMessageClass::ErrorResponse => {
+                self.handle_binding_response(message, local, from, now)?
+            }
+            StunMessageClass::Indication => {}
+        }
+        self.update_state(now);
+        Ok(())
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8445#section-7.3
+    fn handle_binding_request(
+        &mut self,
+        message: &StunMessage,
+        local: SocketAddr,
+        from: SocketAddr,
+        now: Instant,
+    ) -> Result<()> {
+        // https://datatracker.ietf.org/doc/html/rfc8489#section-9.1.3
+        // - reject requests without credentials
+        let Some(username_attr) = message.attributes.get(&AttributeType::Username) else {
+            warn!("username attribute does not exist; reject the message.");
+            self.send_error_response(message, local, from, BAD_REQUEST, "Bad Request", false);
+            return Ok(());
+        };
+        if !message
+            .attributes
+            .contains_key(&AttributeType::MessageIntegrity)
+        {
+            warn!("message integrity attribute does not exist; reject the message.");
+            self.send_error_response(message, local, from, BAD_REQUEST, "Bad Request", false);
+            return Ok(());
+        }
+
+        // https://datatracker.ietf.org/doc/html/rfc8445#section-7.2.2
+        // - verify username in the message
+        let username = String::from_utf8_lossy(&username_attr.value);
+        let username_matches =
+            username
+                .split_once(':')
+                .is_some_and(|(local_ufrag, remote_ufrag)| {
+                    local_ufrag == self.local_peer.ufrag
+                        && (self.remote_peers.is_empty()
+                            || self
+                                .remote_peers
+                                .iter()
+                                .any(|peer| peer.ufrag == remote_ufrag))
+                });
+        if !username_matches {
+            warn!("username attribute mismatch: actual={username}; reject the message.");
+            self.send_error_response(message, local, from, UNAUTHORIZED, "Unauthorized", false);
+            return Ok(());
+        }
+
+        // - verify message integrity
+        if !message.verify_message_integrity(self.local_peer.pwd.clone())? {
+            warn!("message integrity mismatch; reject the message.");
+            self.send_error_response(message, local, from, UNAUTHORIZED, "Unauthorized", false);
+            return Ok(());
+        }
+        self.authenticated_addrs.insert(from);
+
+        // https://datatracker.ietf.org/doc/html/rfc8489#section-6.3.1.1
+        if !message.unknown_attributes.is_empty() {
+            warn!(
+                "unknown comprehension-required attributes; {:04x?}",
+                message.unknown_attributes
+            );
+            self.send_error_response(
+                message,
+                local,
+                from,
+                UNKNOWN_ATTRIBUTE,
+                "Unknown Attribute",
+                true,
+            );
+            return Ok(());
+        }
+
+        // https://datatracker.ietf.org/doc/html/rfc8445#section-7.3.1.1
+        let controlling = match message.attribute(AttributeType::IceControlling)? {
+            Some(StunAttribute::IceControlling(tie_breaker)) => Some(tie_breaker),
+            _ => None,
+        };
+        let controlled = match message.attribute(AttributeType::IceControlled)? {
+            Some(StunAttribute::IceControlled(tie_breaker)) => Some(tie_breaker),
+            _ => None,
+        };
+        match (self.role, controlling, controlled) {
+            (IceRole::Controlling, Some(tie_breaker), _) => {
+                if self.tie_breaker >= tie_breaker {
+                    self.send_error_response(
+                        message,
+                        local,
+                        from,
+                        ROLE_CONFLICT,
+                        "Role Conflict",
+                        true,
+                    );
+                    return Ok(());
+                }
+                self.set_role(IceRole::Controlled);
+            }
+            (IceRole::Controlled, _, Some(tie_breaker)) => {
+                if self.tie_breaker < tie_breaker {
+                    self.send_error_response(
+                        message,
+                        local,
+                        from,
+                        ROLE_CONFLICT,
+                        "Role Conflict",
+                        true,
+                    );
+                    return Ok(());
+                }
+                self.set_role(IceRole::Controlling);
+            }
+            _ => {}
+        }
+
+        // - send stun binding response
+        let response = StunMessageBuilder::new(
+            StunMessageType {
+                method: StunMessageMethod::Binding,
+                class: StunMessageClass::SuccessResponse,
+            },
+            message.transaction_id.clone(),
+        )
+        .add_attribute(&StunAttribute::XorMappedAddress(from))
+        .add_attr(AttributeType::Username, &username_attr.value[..])
+        .build(self.local_peer.pwd.clone());
+        self.transmits.push_back(Transmit {
+            from: local,
+            to: from,
+            data: response.raw,
+        });
+
+        // https://datatracker.ietf.org/doc/html/rfc8445#section-7.3.1.3
+        let priority = match message.attribute(AttributeType::Priority)? {
+            Some(StunAttribute::Priority(priority)) => priority,
+            _ => 0,
+        };
+        // ICE-TCP checks arrive on the listener address of a passive candidate.
+        let transport_type = self
+            .ice_candidates
+            .iter()
+            .find(|candidate| candidate.base() == local)
+            .map_or(TransportType::Udp, |candidate| candidate.transport_type);
+        self.add_remote_candidate(IceCandidate::peer_reflexive(from, priority, transport_type));
+        // https://datatracker.ietf.org/doc/html/rfc8445#section-7.3.1.4
+        // the pair is formed by the local candidate the request was received on.
+        let Some(pair) = self.find_pair(local, from) else {
+            return Ok(());
+        };
+
+        match self.check_list[pair].state {
+            CandidatePairState::Frozen
+            | CandidatePairState::Waiting
+            | CandidatePairState::Failed => {
+                self.check_list[pair].state = CandidatePairState::Waiting;
+                self.enqueue_triggered_check(pair, false, now);
+            }
+            CandidatePairState::InProgress | CandidatePairState::Succeeded => {}
+        }
+
+        // https://datatracker.ietf.org/doc/html/rfc8445#section-7.3.1.5
+        if self.role == IceRole::Controlled
+            && message
+                .attributes
+                .contains_key(&AttributeType::UseCandidate)
+        {
+            self.check_list[pair].use_candidate_received = true;
+            if self.check_list[pair].state == CandidatePairState::Succeeded {
+                self.nominate(pair, now);
+            }
+        }
+        Ok(())
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8445#section-7.2.5
+    fn handle_binding_response(
+        &mut self,
+        message: &StunMessage,
+        local: SocketAddr,
+        from: SocketAddr,
+        now: Instant,
+    ) -> Result<()> {
+        if !self.transactions.contains_key(&message.transaction_id) {
+            debug!("stun response for unknown transaction; ignore the message.");
+            return Ok(());
+        }
+        let remote_pwd = self.remote_pwd().unwrap_or_default();
+        if !message.verify_message_integrity(remote_pwd)? {
+            warn!("message integrity mismatch; ignore the message.");
+            return Ok(()); // ignore message
+        }
+        if matches!(
+            message.message_type.class,
+            StunMessageClass::SuccessResponse
+        ) {
+            self.authenticated_addrs.insert(from);
+        }
+        let Some(transaction) = self.transactions.remove(&message.transaction_id) else {
+            return Ok(());
+        };
+
+        let TransactionKind::Check {
+            pair,
+            use_candidate,
+        } = transaction.kind
+        else {
+            // https://datatracker.ietf.org/doc/html/rfc7675#section-5.1
+            if matches!(
+                message.message_type.class,
+                StunMessageClass::SuccessResponse
+            ) {
+                self.consent_expires_at = Some(now + CONSENT_TIMEOUT);
+            }
+            return Ok(());
+        };
+
+        if matches!(message.message_type.class, StunMessageClass::ErrorResponse) {
+            let code = match message.attribute(AttributeType::ErrorCode)? {
+                Some(StunAttribute::ErrorCode { code, .. }) => Some(code),
+                _ => None,
+            };
+            // https://datatracker.ietf.org/doc/html/rfc8445#section-7.2.5.1
+            if code == Some(ROLE_CONFLICT) {
+                if transaction.role == self.role {
+                    self.set_role(match self.role {
+                        IceRole::Controlling => IceRole::Controlled,
+                        IceRole::Controlled => IceRole::Controlling,
+                    });
+                }
+                self.check_list[pair].state = CandidatePairState::Waiting;
+                self.enqueue_triggered_check(pair, false, now);
+            } else {
+                warn!("binding error response; code={code:?}");
+                self.fail_pair(pair);
+            }
+            return Ok(());
+        }
+
+        // https://datatracker.ietf.org/doc/html/rfc8445#section-7.2.5.2.1
+        if from != transaction.to || local != transaction.from {
+            warn!(
+                "non-symmetric binding response; sent {} -> {}, received {from} -> {local}",
+                transaction.from, transaction.to
+            );
+            self.fail_pair(pair);
+            return Ok(());
+        }
+
+        // https://datatracker.ietf.org/doc/html/rfc8445#section-7.2.5.3.3
+        let foundation = self.check_list[pair].foundation.clone();
+        self.check_list[pair].state = CandidatePairState::Succeeded;
+        for other in &mut self.check_list {
+            if other.foundation == foundation && other.state == CandidatePairState::Frozen {
+                other.state = CandidatePairState::Waiting;
+            }
+        }
+        self.first_success_at.get_or_insert(now);
+        if self.selected_pair == Some(pair) {
+            self.consent_expires_at = Some(now + CONSENT_TIMEOUT);
+        }
+
+        // https://datatracker.ietf.org/doc/html/rfc8445#section-7.2.5.3.4
+        if use_candidate || self.check_list[pair].use_candidate_received {
+            self.nominate(pair, now);
+        }
+        self.maybe_nominate(now);
+        Ok(())
+    }
+
+    fn handle_transaction_timeouts(&mut self, now: Instant) {
+        let mut timed_out = vec![];
+        for (transaction_id, transaction) in &mut self.transactions {
+            if transaction.next_at > now {
+                continue;
+            }
+            if transaction.transmissions >= transaction.max_transmissions {
+                timed_out.push(transaction_id.clone());
+                continue;
+            }
+            // https://datatracker.ietf.org/doc/html/rfc5389#section-7.2.1
+            transaction.transmissions += 1;
+            transaction.rto *= 2;
+            transaction.next_at = if transaction.transmissions >= transaction.max_transmissions {
+                now + INITIAL_RTO * LAST_RTO_MULTIPLIER
+            } else {
+                now + transaction.rto
+            };
+            self.transmits.push_back(Transmit {
+                from: transaction.from,
+                to: transaction.to,
+                data: transaction.request.clone(),
+            });
+        }
+
+        for transaction_id in timed_out {
+            let Some(transaction) = self.transactions.remove(&transaction_id) else {
+                continue;
+            };
+            if let TransactionKind::Check { pair, .. } = transaction.kind {
+                debug!(
+                    "connectivity check timed out; remote={}",
+                    self.check_list[pair].remote.addr()
+                );
+                self.fail_pair(pair);
+            }
+        }
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc7675#section-5.1
+    fn handle_consent_timeouts(&mut self, now: Instant) {
+        if self.consent_expires_at.is_some_and(|at| at <= now) {
+            warn!("ice consent expired; remote stopped responding.");
+            self.state = IceConnectionState::Failed;
+            self.selected_pair = None;
+            self.next_consent_at = None;
+            self.consent_expires_at = None;
+            self.next_check_at = None;
+            self.transactions.clear();
+            return;
+        }
+
+        let Some(selected) = self.selected_pair else {
+            return;
+        };
+        let from = self.check_list[selected].local.base();
+        let to = self.check_list[selected].remote.addr();
+        if self.next_consent_at.is_some_and(|at| at <= now) {
+            let transaction_id = generate_transaction_id();
+            if let Some(request) = self.build_request(&transaction_id, selected, false) {
+                self.transactions.insert(
+                    transaction_id,
+                    Transaction {
+                        kind: TransactionKind::Consent,
+                        role: self.role,
+                        from,
+                        to,
+                        request: request.clone(),
+                        transmissions: 1,
+                        max_transmissions: 1,
+                        rto: CONSENT_INTERVAL,
+                        next_at: now + CONSENT_INTERVAL,
+                    },
+                );
+                self.transmits.push_back(Transmit {
+                    from,
+                    to,
+                    data: request,
+                });
+            }
+            self.next_consent_at = Some(now + consent_interval());
+        }
+    }
+
+    fn can_nominate(&self) -> bool {
+        self.role == IceRole::Controlling && !self.nominating && self.selected_pair.is_none()
+    }
+
+    // Regular nomination: the controlling agent nominates the highest priority valid pair
+    // once no higher priority pair is pending, or NOMINATION_DELAY after the first success.
+    // https://datatracker.ietf.org/doc/html/rfc8445#section-8.1.1
+    fn maybe_nominate(&mut self, now: Instant) {
+        if !self.can_nominate() {
+            return;
+        }
+        let Some(first_success_at) = self.first_success_at else {
+            return;
+        };
+        let waited_enough = first_success_at + NOMINATION_DELAY <= now;
+
+        let mut pairs = (0..self.check_list.len()).collect::<Vec<_>>();
+        pairs.sort_by_key(|&pair| std::cmp::Reverse(self.check_list[pair].priority));
+        for pair in pairs {
+            match self.check_list[pair].state {
+                CandidatePairState::Succeeded => {
+                    debug!(
+                        "nominate candidate pair; remote={}",
+                        self.check_list[pair].remote.addr()
+                    );
+                    self.nominating = true;
+                    self.triggered_check_queue.push_front((pair, true));
+                    self.next_check_at = Some(now);
+                    return;
+                }
+                CandidatePairState::Failed => {}
+                _ if waited_enough => {}
+                _ => return,
+            }
+        }
+    }
+
+    fn nominate(&mut self, pair: usize, now: Instant) {
+        self.check_list[pair].nominated = true;
+        self.nominating = false;
+
+        // the controlled agent may be told about a better pair later.
+        if let Some(selected) = self.selected_pair
+            && self.check_list[selected].priority >= self.check_list[pair].priority
+        {
+            return;
+        }
+        info!(
+            "ice selected candidate pair; remote={}",
+            self.check_list[pair].remote.addr()
+        );
+        if self.selected_pair.is_none() {
+            self.next_consent_at = Some(now + consent_interval());
+            self.consent_expires_at = Some(now + CONSENT_TIMEOUT);
+        }
+        self.selected_pair = Some(pair);
+        self.state = IceConnectionState::Connected;
+    }
+
+    fn next_check(&mut self) -> Option<(usize, bool)> {
+        if let Some(check) = self.triggered_check_queue.pop_front() {
+            return Some(check);
+        }
+        let highest_priority = |state: CandidatePairState| {
+            (0..self.check_list.len())
+                .filter(|&pair| self.check_list[pair].state == state)
+                .max_by_key(|&pair| self.check_list[pair].priority)
+        };
+        if let Some(pair) = highest_priority(CandidatePairState::Waiting) {
+            return Some((pair, false));
+        }
+        let pair = highest_priority(CandidatePairState::Frozen)?;
+        self.check_list[pair].state = CandidatePairState::Waiting;
+        Some((pair, false))
+    }
+
+    fn enqueue_triggered_check(&mut self, pair: usize, use_candidate: bool, now: Instant) {
+        if self
+            .triggered_check_queue
+            .iter()
+            .all(|&(queued, _)| queued != pair)
+        {
+            self.triggered_check_queue.push_back((pair, use_candidate));
+        }
+        if self.state != IceConnectionState::New {
+            self.next_check_at.get_or_insert(now);
+        }
+    }
+
+    fn send_check(&mut self, pair: usize, use_candidate: bool, now: Instant) {
+        let transaction_id = generate_transaction_id();
+        let Some(request) = self.build_request(&transaction_id, pair, use_candidate) else {
+            return;
+        };
+        let from = self.check_list[pair].local.base();
+        let to = self.check_list[pair].remote.addr();
+        if !use_candidate {
+            self.check_list[pair].state = CandidatePairState::InProgress;
+        }
+        self.transactions.insert(
+            transaction_id,
+            Transaction {
+                kind: TransactionKind::Check {
+                    pair,
+                    use_candidate,
+                },
+                role: self.role,
+                from,
+                to,
+                request: request.clone(),
+                transmissions: 1,
+                max_transmissions: MAX_TRANSMISSIONS,
+                rto: INITIAL_RTO,
+                next_at: now + INITIAL_RTO,
+            },
+        );
+        self.transmits.push_back(Transmit {
+            from,
+            to,
+            data: request,
+        });
+    }
+
+    fn find_pair(&self, local: SocketAddr, remote: SocketAddr) -> Option<usize> {
+        self.check_list
+            .iter()
+            .position(|pair| pair.local.base() == local && pair.remote.addr() == remote)
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8445#section-7.1
+    fn build_request(
+        &self,
+        transaction_id: &[u8],
+        pair: usize,
+        use_candidate: bool,
+    ) -> Option<Vec<u8>> {
+        let remote_peer = self.remote_peers.first()?;
+        let priority = self.check_list[pair].local.peer_reflexive_priority();
+        let username = format!("{}:{}", remote_peer.ufrag, self.local_peer.ufrag);
+
+        let mut builder = StunMessageBuilder::new(
+            StunMessageType {
+                method: StunMessageMethod::Binding,
+                class: StunMessageClass::Request,
+            },
+            transaction_id.to_vec(),
+        )
+        .add_attribute(&StunAttribute::Username(username))
+        .add_attribute(&StunAttribute::Priority(priority))
+        .add_attribute(&match self.role {
+            IceRole::Controlling => StunAttribute::IceControlling(self.tie_breaker),
+            IceRole::Controlled => StunAttribute::IceControlled(self.tie_breaker),
+        });
+        if use_candidate {
+            builder = builder.add_attribute(&StunAttribute::UseCandidate);
+        }
+        Some(builder.build(remote_peer.pwd.clone()).raw)
+    }
+
+    // MESSAGE-INTEGRITY is only added to errors of authenticated requests.
+    // https://datatracker.ietf.org/doc/html/rfc8489#section-9.1.3
+    fn send_error_response(
+        &mut self,
+        message: &StunMessage,
+        local: SocketAddr,
+        from: SocketAddr,
+        code: u16,
+        reason: &str,
+        integrity: bool,
+    ) {
+        let mut builder = StunMessageBuilder::new(
+            StunMessageType {
+                method: StunMessageMethod::Binding,
+                class: StunMessageClass::ErrorResponse,
+            },
+            message.transaction_id.clone(),
+        )
+        .add_attribute(&StunAttribute::ErrorCode {
+            code,
+            reason: reason.to_string(),
+        });
+        if code == UNKNOWN_ATTRIBUTE {
+            builder = builder.add_attribute(&StunAttribute::UnknownAttributes(
+                message.unknown_attributes.clone(),
+            ));
+        }
+        let response = if integrity {
+            builder.build(self.local_peer.pwd.clone())
+        } else {
+            builder.build_without_integrity()
+        };
+        self.transmits.push_back(Transmit {
+            from: local,
+            to: from,
+            data: response.raw,
+        });
+    }
+
+    fn fail_pair(&mut self, pair: usize) {
+        self.check_list[pair].state = CandidatePairState::Failed;
+        if self.check_list[pair].nominated || self.triggered_check_queue.is_empty() {
+            self.nominating = false;
+        }
+    }
+
+    fn disconnected_at(&self) -> Option<Instant> {
+        self.consent_expires_at
+            .map(|at| at - (CONSENT_TIMEOUT - DISCONNECTED_TIMEOUT))
+    }
+
+    // https://w3c.github.io/webrtc-pc/#dom-rtciceconnectionstate
+    fn update_state(&mut self, now: Instant) {
+        let state = match self.state {
+            IceConnectionState::Connected
+            | IceConnectionState::Completed
+            | IceConnectionState::Disconnected => {
+                if self.disconnected_at().is_some_and(|at| at <= now) {
+                    IceConnectionState::Disconnected
+                } else if self.check_list.iter().all(|pair| {
+                    matches!(
+                        pair.state,
+                        CandidatePairState::Succeeded | CandidatePairState::Failed
+                    )
+                }) && self.triggered_check_queue.is_empty()
+                {
+                    IceConnectionState::Completed
+                } else {
+                    IceConnectionState::Connected
+                }
+            }
+            state => state,
+        };
+        if state != self.state {
+            debug!(
+                "ice connection state changed; {:?} -> {state:?}",
+                self.state
+            );
+            self.state = state;
+        }
+        self.update_failed_state();
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8445#section-6.1.2.6
+    fn update_failed_state(&mut self) {
+        if self.state == IceConnectionState::Checking
+            && self.remote_end_of_candidates
+            && self.gathering_state != IceGatheringState::Gathering
+            && !self.check_list.is_empty()
+            && self.transactions.is_empty()
+            && self.triggered_check_queue.is_empty()
+            && self
+                .check_list
+                .iter()
+                .all(|pair| pair.state == CandidatePairState::Failed)
+        {
+            warn!("all candidate pairs failed.");
+            self.state = IceConnectionState::Failed;
+        }
+    }
+
+    /// Local candidate as signaled to the remote; the related address of a srflx candidate
+    /// would reveal the ip of a host candidate published as an mDNS name.
+    // https://datatracker.ietf.org/doc/html/draft-ietf-mmusic-mdns-ice-candidates
+    pub fn signaled_candidate(&self, candidate: &IceCandidate) -> IceCandidate {
+        let mut candidate = candidate.clone();
+        if candidate.candidate_type == CandidateType::Srflx
+            && self.ice_candidates.iter().any(|host| {
+                host.mdns_name.is_some() && Some(host.addr()) == candidate.related_address
+            })
+        {
+            candidate.related_address = Some(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0));
+        }
+        candidate
+    }
+
+    fn signaled_candidates(&self) -> Vec<SdpMediaCandidate> {
+        self.ice_candidates
+            .iter()
+            .map(|candidate| SdpMediaCandidate::from(&self.signaled_candidate(candidate)))
+            .collect()
+    }
+
+    fn local_end_of_candidates(&self) -> Option<String> {
+        (self.gathering_state == IceGatheringState::Complete)
+            .then(|| "end-of-candidates".to_string())
+    }
+
+    fn remote_pwd(&self) -> Option<String> {
+        self.remote_peers.first().map(|peer| peer.pwd.clone())
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7675#section-5.1
+// randomized to 0.8 .. 1.2 times the base interval to avoid synchronized checks.
+fn consent_interval() -> Duration {
+    CONSENT_INTERVAL.mul_f64(rand::random_range(0.8..1.2))
+}
+
+#[cfg(test)]
+mod agent_tests {
+    use super::*;
+    use crate::common::buffer::BufReader;
+    use crate::sdp::CandidateType;
+
+    fn new_agent(port: u64) -> IceAgent {
+        IceAgent::new(
+            vec![IceCandidate::host(
+                "127.0.0.1".parse().unwrap(),
+                port,
+                u16::MAX,
+            )],
+            vec![SdpFingerprint {
+                hash_function: FingerprintType::Sha256,
+                fingerprint: format!("{port:04X}"),
+            }],
+        )
+    }
+
+    fn new_agents() -> (IceAgent, IceAgent) {
+        let mut a = new_agent(10000);
+        let mut b = new_agent(20000);
+        a.remote_peers = vec![b.local_peer.clone()];
+        b.remote_peers = vec![a.local_peer.clone()];
+        a.add_remote_candidate(b.ice_candidates[0].clone());
+        b.add_remote_candidate(a.ice_candidates[0].clone());
+        b.set_role(IceRole::Controlled);
+        (a, b)
+    }
+
+    // delivers every pending datagram until both agents are quiet.
+    fn exchange(a: &mut IceAgent, b: &mut IceAgent, now: Instant, drop_to_b: bool) -> Result<()> {
+        let a_addr = a.ice_candidates[0].addr();
+        let b_addr = b.ice_candidates[0].addr();
+        loop {
+            let mut delivered = false;
+            while let Some(transmit) = a.poll_transmit() {
+                delivered = true;
+                if !drop_to_b {
+                    let message = StunMessage::decode(&mut BufReader::new(&transmit.data))?;
+                    b.handle_stun_message(&message, b_addr, a_addr, now)?;
+                }
+            }
+            while let Some(transmit) = b.poll_transmit() {
+                delivered = true;
+                let message = StunMessage::decode(&mut BufReader::new(&transmit.data))?;
+                a.handle_stun_message(&message, a_addr, b_addr, now)?;
+            }
+            if !delivered {
+                return Ok(());
+            }
+        }
+    }
+
+    fn run(
+        a: &mut IceAgent,
+        b: &mut IceAgent,
+        mut now: Instant,
+        until: Instant,
+        drop_to_b: bool,
+    ) -> Result<Instant> {
+        while now < until {
+            a.handle_timeout(now);
+            b.handle_timeout(now);
+            exchange(a, b, now, drop_to_b)?;
+            now = [a.next_timeout(), b.next_timeout()]
+                .into_iter()
+                .flatten()
+                .min()
+                .unwrap_or(until)
+                .max(now + Duration::from_millis(1));
+        }
+        Ok(now)
+    }
+
+    #[test]
+    fn test_connectivity_checks_and_nomination() -> Result<()> {
+        let (mut a, mut b) = new_agents();
+        let now = Instant::now();
+        a.start_checks(now);
+        b.start_checks(now);
+        run(&mut a, &mut b, now, now + Duration::from_secs(3), false)?;
+
+        assert_eq!(a.state, IceConnectionState::Completed);
+        assert_eq!(b.state, IceConnectionState::Completed);
+        assert_eq!(a.selected_remote_addr(), Some(b.ice_candidates[0].addr()));
+        assert_eq!(b.selected_remote_addr(), Some(a.ice_candidates[0].addr()));
+        assert!(b.check_list[b.selected_pair.unwrap()].nominated);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trickle_candidate() -> Result<()> {
+        let mut a = new_agent(10000);
+        let mut b = new_agent(20000);
+        a.remote_peers = vec![b.local_peer.clone()];
+        b.remote_peers = vec![a.local_peer.clone()];
+        b.set_role(IceRole::Controlled);
+        let now = Instant::now();
+        a.start_checks(now);
+        b.start_checks(now);
+        let now = run(&mut a, &mut b, now, now + Duration::from_secs(3), false)?;
+        assert_eq!(a.state, IceConnectionState::Checking);
+
+        // b learns a as a peer reflexive candidate from the triggered check
+        a.add_ice_candidate(b.ice_candidates[0].clone(), now);
+        run(&mut a, &mut b, now, now + Duration::from_secs(3), false)?;
+        assert_eq!(a.state, IceConnectionState::Completed);
+        assert_eq!(b.state, IceConnectionState::Completed);
+        assert_eq!(b.remote_candidates[0].candidate_type, CandidateType::Prflx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trickle_local_candidate() -> Result<()> {
+        let mut a = new_agent(10000);
+        let mut b = new_agent(20000);
+        a.remote_peers = vec![b.local_peer.clone()];
+        b.remote_peers = vec![a.local_peer.clone()];
+        b.add_remote_candidate(a.ice_candidates[0].clone());
+        b.set_role(IceRole::Controlled);
+        // the candidate of b arrives before a gathers its host candidate
+        let host = a.ice_candidates.remove(0);
+        assert_eq!(a.add_remote_candidate(b.ice_candidates[0].clone()), None);
+        let now = Instant::now();
+        a.start_checks(now);
+        b.start_checks(now);
+        a.add_local_candidate(host, now);
+        assert_eq!(a.check_list.len(), 1);
+        run(&mut a, &mut b, now, now + Duration::from_secs(3), false)?;
+        assert_eq!(a.state, IceConnectionState::Completed);
+        assert_eq!(b.state, IceConnectionState::Completed);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_role_conflict() -> Result<()> {
+        let (mut a, mut b) = new_agents();
+        b.set_role(IceRole::Controlling);
+        a.tie_breaker = 2;
+        b.tie_breaker = 1;
+        let now = Instant::now();
+        a.start_checks(now);
+        b.start_checks(now);
+        run(&mut a, &mut b, now, now + Duration::from_secs(3), false)?;
+
+        assert_eq!(a.role, IceRole::Controlling);
+        assert_eq!(b.role, IceRole::Controlled);
+        assert_eq!(a.state, IceConnectionState::Completed);
+        assert_eq!(b.state, IceConnectionState::Completed);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reject_unauthorized_request() -> Result<()> {
+        let (mut a, mut b) = new_agents();
+        b.local_peer.pwd = "another-password".to_string();
+        let now = Instant::now();
+        a.start_checks(now);
+        a.handle_timeout(now);
+        let transmit = a
+            .poll_transmit()
+            .expect("connectivity check should be sent");
+        let request = StunMessage::decode(&mut BufReader::new(&transmit.data))?;
+        b.handle_stun_message(&request, transmit.to, transmit.from, now)?;
+
+        let transmit = b.poll_transmit().expect("error response should be sent");
+        let response = StunMessage::decode(&mut BufReader::new(&transmit.data))?;
+        assert!(matches!(
+            response.message_type.class,
+            StunMessageClass::ErrorResponse
+        ));
+        assert!(matches!(
+            response.attribute(AttributeType::ErrorCode)?,
+            Some(StunAttribute::ErrorCode { code: 401, .. })
+        ));
+        assert!(b.poll_transmit().is_none());
+        assert!(!b.is_authenticated(transmit.to));
+        Ok(())
+    }
+
+    #[test]
+    fn test_consent_expires() -> Result<()> {
+        let (mut a, mut b) = new_agents();
+        let now = Instant::now();
+        a.start_checks(now);
+        b.start_checks(now);
+        let now = run(&mut a, &mut b, now, now + Duration::from_secs(3), false)?;
+        assert_eq!(a.state, IceConnectionState::Completed);
+
+        // consent is kept while b keeps responding
+        let now = run(&mut a, &mut b, now, now + Duration::from_secs(60), false)?;
+        assert_eq!(a.state, IceConnectionState::Completed);
+
+        // b disappears; a is disconnected after 10 seconds without responses
+        // and revokes consent 30 seconds after the last response
+        let now = run(&mut a, &mut b, now, now + Duration::from_secs(3), true)?;
+        assert_eq!(a.state, IceConnectionState::Completed);
+        let now = run(&mut a, &mut b, now, now + Duration::from_secs(17), true)?;
+        assert_eq!(a.state, IceConnectionState::Disconnected);
+        run(&mut a, &mut b, now, now + Duration::from_secs(20), true)?;
+        assert_eq!(a.state, IceConnectionState::Failed);
+        assert_eq!(a.selected_remote_addr(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fail_without_trickle() -> Result<()> {
+        let (mut a, mut b) = new_agents();
+        let now = Instant::now();
+        a.set_remote_description(&b.generate_sdp_offer().medias[0]);
+        a.start_checks(now);
+        run(&mut a, &mut b, now, now + Duration::from_secs(60), true)?;
+        // more candidates may be trickled
+        assert_eq!(a.state, IceConnectionState::Checking);
+
+        let (mut a, mut b) = new_agents();
+        let mut offer = b.generate_sdp_offer();
+        offer.medias[0].ice_options.clear();
+        a.set_remote_description(&offer.medias[0]);
+        a.start_checks(now);
+        run(&mut a, &mut b, now, now + Duration::from_secs(60), true)?;
+        assert!(
+            a.check_list
+                .iter()
+                .all(|pair| pair.state == CandidatePairState::Failed)
+        );
+        assert_eq!(a.state, IceConnectionState::Failed);
+        Ok(())
+    }
+}
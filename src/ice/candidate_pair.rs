@@ -0,0 +1,77 @@
+use crate::ice::{IceCandidate, IceRole};
+
+// https://datatracker.ietf.org/doc/html/rfc8445#section-5.1.2.2
+pub const HOST_TYPE_PREFERENCE: u8 = 126;
+pub const PRFLX_TYPE_PREFERENCE: u8 = 110;
+pub const SRFLX_TYPE_PREFERENCE: u8 = 100;
+pub const RELAY_TYPE_PREFERENCE: u8 = 0;
+
+// https://datatracker.ietf.org/doc/html/rfc6544#section-4.2
+pub const PASSIVE_DIRECTION_PREFERENCE: u16 = 4;
+
+// rtcp is always muxed, so every candidate belongs to the rtp component.
+pub const RTP_COMPONENT_ID: u16 = 1;
+
+// https://datatracker.ietf.org/doc/html/rfc8445#section-5.1.2.1
+pub fn candidate_priority(type_preference: u8, local_preference: u16, component_id: u16) -> u32 {
+    ((type_preference as u32) << 24)
+        | ((local_preference as u32) << 8)
+        | (256 - component_id as u32)
+}
+
+// https://datatracker.ietf.org/doc/html/rfc6544#section-4.2
+pub fn tcp_local_preference(direction_preference: u16, other_preference: u16) -> u16 {
+    (direction_preference << 13) | (other_preference & 0x1fff)
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8445#section-6.1.2.3
+pub fn pair_priority(controlling: u32, controlled: u32) -> u64 {
+    let (g, d) = (controlling as u64, controlled as u64);
+    (1 << 32) * g.min(d) + 2 * g.max(d) + if g > d { 1 } else { 0 }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8445#section-6.1.2.6
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CandidatePairState {
+    Frozen,
+    Waiting,
+    InProgress,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+pub struct CandidatePair {
+    pub local: IceCandidate,
+    pub remote: IceCandidate,
+    pub foundation: String,
+    pub state: CandidatePairState,
+    pub priority: u64,
+    pub nominated: bool,
+    /// The controlled agent received USE-CANDIDATE for this pair.
+    pub use_candidate_received: bool,
+}
+
+impl CandidatePair {
+    pub fn new(local: IceCandidate, remote: IceCandidate, role: IceRole) -> Self {
+        let mut pair = Self {
+            // https://datatracker.ietf.org/doc/html/rfc8445#section-6.1.2.6
+            foundation: format!("{}:{}", local.foundation, remote.foundation),
+            local,
+            remote,
+            state: CandidatePairState::Frozen,
+            priority: 0,
+            nominated: false,
+            use_candidate_received: false,
+        };
+        pair.update_priority(role);
+        pair
+    }
+
+    pub fn update_priority(&mut self, role: IceRole) {
+        self.priority = match role {
+            IceRole::Controlling => pair_priority(self.local.priority, self.remote.priority),
+            IceRole::Controlled => pair_priority(self.remote.priority, self.local.priority),
+        };
+    }
+}
@@ -0,0 +1,360 @@
+pub mod agent;
+pub mod candidate_pair;
+
+pub use agent::IceAgent;
+
+use anyhow::Result;
+use crc::{CRC_32_ISO_HDLC, Crc};
+use local_ip_address::list_afinet_netifas;
+use rand::RngExt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use crate::ice::candidate_pair::{
+    HOST_TYPE_PREFERENCE, PASSIVE_DIRECTION_PREFERENCE, PRFLX_TYPE_PREFERENCE,
+    RELAY_TYPE_PREFERENCE, RTP_COMPONENT_ID, SRFLX_TYPE_PREFERENCE, candidate_priority,
+    tcp_local_preference,
+};
+use crate::sdp::session_description::SdpFingerprint;
+use crate::sdp::{CandidateAddress, CandidateType, SdpMediaCandidate, TcpType, TransportType};
+
+pub fn generate_ice_ufrag() -> String {
+    let mut rng = rand::rng();
+    let u_frag: String = (0..13)
+        .map(|_| rng.sample(rand::distr::Alphabetic).to_string())
+        .collect();
+    u_frag + "mini"
+}
+
+pub fn generate_ice_pwd() -> String {
+    let mut rng = rand::rng();
+    (0..32)
+        .map(|_| rng.sample(rand::distr::Alphabetic).to_string())
+        .collect()
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8445#section-5.1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IceCandidate {
+    pub foundation: String,
+    pub component: u16,
+    pub ip: IpAddr,
+    pub port: u64,
+    pub candidate_type: CandidateType,
+    pub transport_type: TransportType,
+    pub tcp_type: Option<TcpType>,
+    pub priority: u32,
+    /// `raddr`/`rport`; the base of srflx and prflx candidates and the mapped address of relay.
+    pub related_address: Option<SocketAddr>,
+    /// `.local` name signaled instead of `ip`; remote candidates with a name keep the
+    /// unspecified ip until it is resolved.
+    // https://datatracker.ietf.org/doc/html/draft-ietf-mmusic-mdns-ice-candidates
+    pub mdns_name: Option<String>,
+}
+
+impl IceCandidate {
+    pub fn host(ip: IpAddr, port: u64, local_preference: u16) -> Self {
+        Self {
+            foundation: compute_foundation(CandidateType::Host, ip, None, TransportType::Udp),
+            component: RTP_COMPONENT_ID,
+            ip,
+            port,
+            candidate_type: CandidateType::Host,
+            transport_type: TransportType::Udp,
+            tcp_type: None,
+            priority: candidate_priority(HOST_TYPE_PREFERENCE, local_preference, RTP_COMPONENT_ID),
+            related_address: None,
+            mdns_name: None,
+        }
+    }
+
+    /// Passive ICE-TCP host candidate; remotes connect to the listener on `port`.
+    /// `other_preference` (13 bits) orders candidates of the same direction.
+    // https://datatracker.ietf.org/doc/html/rfc6544#section-4.1
+    pub fn passive_tcp_host(ip: IpAddr, port: u64, other_preference: u16) -> Self {
+        let local_preference = tcp_local_preference(PASSIVE_DIRECTION_PREFERENCE, other_preference);
+        Self {
+            foundation: compute_foundation(CandidateType::Host, ip, None, TransportType::Tcp),
+            component: RTP_COMPONENT_ID,
+            ip,
+            port,
+            candidate_type: CandidateType::Host,
+            transport_type: TransportType::Tcp,
+            tcp_type: Some(TcpType::Passive),
+            priority: candidate_priority(HOST_TYPE_PREFERENCE, local_preference, RTP_COMPONENT_ID),
+            related_address: None,
+            mdns_name: None,
+        }
+    }
+
+    /// Address mapped by `stun_server` for requests sent from `base`.
+    pub fn server_reflexive(
+        addr: SocketAddr,
+        base: SocketAddr,
+        stun_server: SocketAddr,
+        local_preference: u16,
+    ) -> Self {
+        Self {
+            foundation: compute_foundation(
+                CandidateType::Srflx,
+                base.ip(),
+                Some(stun_server),
+                TransportType::Udp,
+            ),
+            component: RTP_COMPONENT_ID,
+            ip: addr.ip(),
+            port: addr.port() as u64,
+            candidate_type: CandidateType::Srflx,
+            transport_type: TransportType::Udp,
+            tcp_type: None,
+            priority: candidate_priority(SRFLX_TYPE_PREFERENCE, local_preference, RTP_COMPONENT_ID),
+            related_address: Some(base),
+            mdns_name: None,
+        }
+    }
+
+    /// Address allocated on `turn_server`; `mapped` is the server reflexive address of the allocation.
+    pub fn relayed(
+        addr: SocketAddr,
+        mapped: SocketAddr,
+        turn_server: SocketAddr,
+        local_preference: u16,
+    ) -> Self {
+        Self {
+            foundation: compute_foundation(
+                CandidateType::Relay,
+                addr.ip(),
+                Some(turn_server),
+                TransportType::Udp,
+            ),
+            component: RTP_COMPONENT_ID,
+            ip: addr.ip(),
+            port: addr.port() as u64,
+            candidate_type: CandidateType::Relay,
+            transport_type: TransportType::Udp,
+            tcp_type: None,
+            priority: candidate_priority(RELAY_TYPE_PREFERENCE, local_preference, RTP_COMPONENT_ID),
+            related_address: Some(mapped),
+            mdns_name: None,
+        }
+    }
+
+    /// Remote candidate learned from a check over `transport_type`; over TCP, the remote is
+    /// the active side of the connection.
+    // https://datatracker.ietf.org/doc/html/rfc8445#section-7.3.1.3
+    // https://datatracker.ietf.org/doc/html/rfc6544#section-7.2
+    pub fn peer_reflexive(addr: SocketAddr, priority: u32, transport_type: TransportType) -> Self {
+        Self {
+            // any value distinct from the foundations of other remote candidates
+            foundation: compute_foundation(CandidateType::Prflx, addr.ip(), None, transport_type),
+            component: RTP_COMPONENT_ID,
+            ip: addr.ip(),
+            port: addr.port() as u64,
+            candidate_type: CandidateType::Prflx,
+            transport_type,
+            tcp_type: (transport_type == TransportType::Tcp).then_some(TcpType::Active),
+            priority,
+            related_address: None,
+            mdns_name: None,
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.ip, self.port as u16)
+    }
+
+    /// Address checks are sent from; srflx candidates share the socket of their host base.
+    // https://datatracker.ietf.org/doc/html/rfc8445#section-5.1.1.1
+    pub fn base(&self) -> SocketAddr {
+        match self.candidate_type {
+            CandidateType::Srflx | CandidateType::Prflx => {
+                self.related_address.unwrap_or(self.addr())
+            }
+            CandidateType::Host | CandidateType::Relay => self.addr(),
+        }
+    }
+
+    /// Remote candidate whose mDNS name is not resolved yet.
+    pub fn is_unresolved(&self) -> bool {
+        self.mdns_name.is_some() && self.ip.is_unspecified()
+    }
+
+    pub fn local_preference(&self) -> u16 {
+        (self.priority >> 8) as u16
+    }
+
+    pub fn signaled_address(&self) -> CandidateAddress {
+        match &self.mdns_name {
+            Some(name) => CandidateAddress::Mdns(name.clone()),
+            None => CandidateAddress::Ip(self.ip),
+        }
+    }
+
+    /// Priority this candidate would have as peer reflexive; sent in the PRIORITY attribute.
+    pub fn peer_reflexive_priority(&self) -> u32 {
+        candidate_priority(
+            PRFLX_TYPE_PREFERENCE,
+            self.local_preference(),
+            self.component,
+        )
+    }
+}
+
+impl From<&SdpMediaCandidate> for IceCandidate {
+    fn from(candidate: &SdpMediaCandidate) -> Self {
+        let (ip, mdns_name) = match &candidate.address {
+            CandidateAddress::Ip(ip) => (*ip, None),
+            CandidateAddress::Mdns(name) => (IpAddr::V4(Ipv4Addr::UNSPECIFIED), Some(name.clone())),
+        };
+        Self {
+            foundation: candidate.foundation.clone(),
+            component: candidate.component,
+            ip,
+            port: candidate.port,
+            candidate_type: candidate.candidate_type,
+            transport_type: candidate.transport_type,
+            tcp_type: candidate.tcp_type,
+            priority: candidate.priority,
+            related_address: candidate
+                .related_address
+                .zip(candidate.related_port)
+                .map(|(ip, port)| SocketAddr::new(ip, port as u16)),
+            mdns_name,
+        }
+    }
+}
+
+impl From<&IceCandidate> for SdpMediaCandidate {
+    fn from(candidate: &IceCandidate) -> Self {
+        Self {
+            foundation: candidate.foundation.clone(),
+            component: candidate.component,
+            address: candidate.signaled_address(),
+            port: candidate.port,
+            candidate_type: candidate.candidate_type,
+            transport_type: candidate.transport_type,
+            tcp_type: candidate.tcp_type,
+            priority: candidate.priority,
+            related_address: candidate.related_address.map(|addr| addr.ip()),
+            related_port: candidate.related_address.map(|addr| addr.port() as u64),
+            generation: Some(0),
+        }
+    }
+}
+
+/// Addresses of host candidates for a socket bound to `bind_address`, most preferred first.
+/// Every interface address of the socket's family is gathered for an unspecified address;
+/// IPv4 ones too when the IPv6 socket is `dual_stack`.
+// https://datatracker.ietf.org/doc/html/rfc8445#section-5.1.1.1
+pub fn gather_host_addresses(bind_address: IpAddr, dual_stack: bool) -> Result<Vec<IpAddr>> {
+    if !bind_address.is_unspecified() {
+        return Ok(vec![bind_address]);
+    }
+    let addresses = list_afinet_netifas()?
+        .into_iter()
+        .map(|(_, ip)| ip)
+        .filter(|ip| ip.is_ipv4() == bind_address.is_ipv4() || (ip.is_ipv4() && dual_stack))
+        .collect::<Vec<_>>();
+    let host_addresses = prioritize_host_addresses(&addresses);
+    if host_addresses.is_empty() {
+        // loopback still lets peers on the same machine connect.
+        return Ok(addresses.into_iter().filter(IpAddr::is_loopback).collect());
+    }
+    Ok(host_addresses)
+}
+
+/// Drops loopback and link-local addresses and interleaves the rest starting from IPv6.
+// https://datatracker.ietf.org/doc/html/rfc8445#section-5.1.1.1
+// https://datatracker.ietf.org/doc/html/rfc8421#section-4
+pub fn prioritize_host_addresses(addresses: &[IpAddr]) -> Vec<IpAddr> {
+    let mut ipv6 = vec![];
+    let mut ipv4 = vec![];
+    for &ip in addresses {
+        match ip {
+            _ if ip.is_loopback() || ip.is_unspecified() => {}
+            IpAddr::V4(v4) if v4.is_link_local() => {}
+            IpAddr::V6(v6) if v6.is_unicast_link_local() => {}
+            IpAddr::V4(_) if !ipv4.contains(&ip) => ipv4.push(ip),
+            IpAddr::V6(_) if !ipv6.contains(&ip) => ipv6.push(ip),
+            _ => {}
+        }
+    }
+    let mut prioritized = vec![];
+    let (mut ipv6, mut ipv4) = (ipv6.into_iter(), ipv4.into_iter());
+    loop {
+        match (ipv6.next(), ipv4.next()) {
+            (None, None) => return prioritized,
+            (v6, v4) => prioritized.extend(v6.into_iter().chain(v4)),
+        }
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8445#section-5.1.1.3
+// same type, base ip, server and transport give the same foundation.
+fn compute_foundation(
+    candidate_type: CandidateType,
+    base_ip: IpAddr,
+    server: Option<SocketAddr>,
+    transport_type: TransportType,
+) -> String {
+    let key = format!("{candidate_type:?}/{base_ip}/{server:?}/{transport_type:?}");
+    Crc::<u32>::new(&CRC_32_ISO_HDLC)
+        .checksum(key.as_bytes())
+        .to_string()
+}
+
+#[derive(Debug, Clone)]
+pub struct Peer {
+    pub ufrag: String,
+    pub pwd: String,
+    /// One `a=fingerprint` per DTLS certificate.
+    pub fingerprints: Vec<SdpFingerprint>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IceRole {
+    Controlling,
+    Controlled,
+}
+
+// https://developer.mozilla.org/en-US/docs/Web/API/RTCPeerConnection/iceConnectionState
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IceConnectionState {
+    New,
+    Checking,
+    Connected,
+    Completed,
+    Failed,
+    Disconnected,
+    Closed,
+}
+
+// https://developer.mozilla.org/en-US/docs/Web/API/RTCPeerConnection/iceGatheringState
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IceGatheringState {
+    New,
+    Gathering,
+    Complete,
+}
+
+#[cfg(test)]
+mod ice_tests {
+    use super::*;
+
+    #[test]
+    fn test_prioritize_host_addresses() {
+        let addresses = [
+            "192.168.0.2",
+            "127.0.0.1",
+            "169.254.0.1",
+            "10.0.0.2",
+            "::1",
+            "fe80::1",
+            "2001:db8::2",
+            "192.168.0.2",
+        ]
+        .map(|ip| ip.parse::<IpAddr>().unwrap());
+        let expected =
+            ["2001:db8::2", "192.168.0.2", "10.0.0.2"].map(|ip| ip.parse::<IpAddr>().unwrap());
+        assert_eq!(prioritize_host_addresses(&addresses), expected);
+    }
+}
@@ -5,11 +5,13 @@ use crate::{common::TransportMessage, sdp::SdpMessage, srtp::crypto::SrtpEncrypt
 pub type EventQueue = VecDeque<InternalEvent>;
 
 pub enum InternalEvent {
-    SdpAnswer(SdpMessage),
+    SetRemoteDescription(SdpMessage),
     InboundDtlsPacket(TransportMessage),
     OutboundDtlsPacket(TransportMessage),
     InboundSctpPacket(TransportMessage),
     OutboundSctpPacket(TransportMessage),
     InboundRtpPacket(TransportMessage),
     DtlsConnected(SrtpEncryptionKeys),
+    /// Sends close_notify and stops the event loop once the queue drains.
+    Close,
 }
@@ -3,13 +3,21 @@ pub mod data_channel;
 pub mod dtls;
 pub mod ice;
 pub mod internal_event;
+pub mod mdns;
 pub mod media_stream_track;
+pub mod rtc_certificate;
+pub mod rtc_configuration;
 pub mod rtc_event;
+pub mod rtc_ice_candidate;
 pub mod rtc_peer_connection;
 pub mod rtc_sctp;
+pub mod rtc_session_description;
 pub mod sctp;
 pub mod sdp;
 pub mod signaling_server;
 pub mod srtp;
 pub mod stun;
+pub mod stun_server;
+pub mod tcp_server;
+pub mod turn;
 pub mod udp_server;
@@ -2,7 +2,10 @@ use std::env;
 
 use anyhow::Result;
 use mini_webrtc_rs::{
+    mdns::MdnsOptions,
     media_stream_track::MediaStreamTrack,
+    rtc_certificate::{RtcCertificate, RtcCertificateParams},
+    rtc_configuration::{RtcConfiguration, SignalingOptions},
     rtc_event::{RtcEvent, RtcTrackEvent},
     rtc_peer_connection::RtcPeerConnection,
 };
@@ -12,10 +15,22 @@ use tracing::{info, warn};
 // GStreamer viewer listens for VP8/PT=96 RTP on this address (see DEV.md).
 const GSTREAMER_RTP_ADDR: &str = "127.0.0.1:5004";
 const LIVE_RTP_FORWARD_ENABLED_ENV: &str = "MINI_WEBRTC_LIVE_RTP_FORWARD";
+// PEM file keeping the DTLS certificate, and so the fingerprint, across restarts.
+const CERTIFICATE_PATH_ENV: &str = "MINI_WEBRTC_CERTIFICATE";
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let mut pc = RtcPeerConnection::new().await?;
+    tracing_subscriber::fmt::init();
+
+    let mut config = RtcConfiguration::default();
+    config.mdns = Some(MdnsOptions::default());
+    config.signaling = Some(SignalingOptions::default());
+    if let Ok(path) = env::var(CERTIFICATE_PATH_ENV) {
+        let certificate =
+            RtcCertificate::load_or_generate(&path, &RtcCertificateParams::default())?;
+        config.certificates.push(certificate);
+    }
+    let mut pc = RtcPeerConnection::new(config).await?;
     let mut dc = pc.create_data_channel().await?;
 
     loop {
@@ -25,6 +40,21 @@ async fn main() -> Result<()> {
                     Some(RtcEvent::RtcTrack(RtcTrackEvent { track })) => {
                         tokio::spawn(pipe_media_to_gstreamer(track));
                     }
+                    Some(RtcEvent::IceConnectionStateChange(state)) => {
+                        info!("ice connection state: {state:?}");
+                    }
+                    Some(RtcEvent::IceGatheringStateChange(state)) => {
+                        info!("ice gathering state: {state:?}");
+                    }
+                    Some(RtcEvent::ConnectionStateChange(state)) => {
+                        info!("connection state: {state:?}");
+                    }
+                    Some(RtcEvent::DtlsStateChange(state)) => {
+                        info!("dtls state: {state:?}");
+                    }
+                    Some(RtcEvent::IceCandidate(candidate)) => {
+                        info!("local ice candidate: {candidate:?}");
+                    }
                     None => break,
                 }
             }
@@ -37,7 +67,7 @@ async fn main() -> Result<()> {
         }
     }
 
-    pc.close();
+    pc.close().await;
     Ok(())
 }
 
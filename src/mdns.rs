@@ -0,0 +1,481 @@
+use crate::common::buffer::{BufReader, BufWriter};
+use anyhow::{Result, anyhow};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tracing::{debug, info};
+
+// https://datatracker.ietf.org/doc/html/rfc6762#section-3
+pub const MDNS_PORT: u16 = 5353;
+pub const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+// https://datatracker.ietf.org/doc/html/rfc6762#section-10.2
+const CACHE_FLUSH: u16 = 0x8000;
+// https://datatracker.ietf.org/doc/html/rfc6762#section-10
+const RECORD_TTL: u32 = 120;
+// https://datatracker.ietf.org/doc/html/rfc6762#section-11
+const MULTICAST_TTL: u32 = 255;
+const MAX_MESSAGE_SIZE: usize = 9000;
+const MAX_POINTER_JUMPS: usize = 16;
+const QUERY_INTERVAL: Duration = Duration::from_secs(1);
+const QUERY_ATTEMPTS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MdnsOptions {
+    pub group: SocketAddrV4,
+    /// Interface to join the group on and send from; the default one when unspecified.
+    pub interface: Ipv4Addr,
+}
+
+impl Default for MdnsOptions {
+    fn default() -> Self {
+        Self {
+            group: SocketAddrV4::new(MDNS_GROUP, MDNS_PORT),
+            interface: Ipv4Addr::UNSPECIFIED,
+        }
+    }
+}
+
+#[derive(Default)]
+struct MdnsState {
+    // lowercase name -> ip of a local host candidate
+    published: HashMap<String, IpAddr>,
+    // lowercase name -> pending resolutions
+    queries: HashMap<String, Vec<oneshot::Sender<IpAddr>>>,
+}
+
+/// mDNS responder for the names hiding local host candidates and resolver for the names of
+/// remote ones; only A/AAAA records of `.local` names are handled.
+// https://datatracker.ietf.org/doc/html/draft-ietf-mmusic-mdns-ice-candidates
+pub struct Mdns {
+    socket: Arc<UdpSocket>,
+    group: SocketAddrV4,
+    state: Arc<StdMutex<MdnsState>>,
+    recv_loop_handle: JoinHandle<()>,
+}
+
+impl Mdns {
+    pub fn bind(options: MdnsOptions) -> Result<Arc<Self>> {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        // other responders on the host listen on the same port.
+        socket.set_reuse_address(true)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, options.group.port())).into())?;
+        socket.join_multicast_v4(options.group.ip(), &options.interface)?;
+        socket.set_multicast_loop_v4(true)?;
+        socket.set_multicast_ttl_v4(MULTICAST_TTL)?;
+        if !options.interface.is_unspecified() {
+            socket.set_multicast_if_v4(&options.interface)?;
+        }
+        let socket = Arc::new(UdpSocket::from_std(socket.into())?);
+        info!("mDNS listening on {}", options.group);
+
+        let state = Arc::new(StdMutex::new(MdnsState::default()));
+        let recv_loop_handle =
+            tokio::spawn(recv_loop(socket.clone(), options.group, state.clone()));
+        Ok(Arc::new(Mdns {
+            socket,
+            group: options.group,
+            state,
+            recv_loop_handle,
+        }))
+    }
+
+    /// Returns the random `<uuid>.local` name answered with `ip`; one name per ip.
+    // https://datatracker.ietf.org/doc/html/draft-ietf-mmusic-mdns-ice-candidates#section-3.1.1
+    pub fn publish(&self, ip: IpAddr) -> String {
+        let mut state = self.state.lock().unwrap();
+        if let Some((name, _)) = state
+            .published
+            .iter()
+            .find(|(_, published)| **published == ip)
+        {
+            return name.clone();
+        }
+        let name = format!("{}.local", generate_uuid());
+        state.published.insert(name.clone(), ip);
+        name
+    }
+
+    pub fn unpublish(&self, name: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .published
+            .remove(&name.to_ascii_lowercase());
+    }
+
+    /// Queries the group for the A/AAAA record of `name` until an answer arrives.
+    // https://datatracker.ietf.org/doc/html/rfc6762#section-5.2
+    pub async fn resolve(&self, name: &str) -> Result<IpAddr> {
+        let name = name.to_ascii_lowercase();
+        if !name.ends_with(".local") {
+            return Err(anyhow!("not an mDNS name; name={name}"));
+        }
+        let (tx, mut rx) = oneshot::channel();
+        {
+            let mut state = self.state.lock().unwrap();
+            if let Some(ip) = state.published.get(&name) {
+                return Ok(*ip);
+            }
+            state.queries.retain(|_, senders| {
+                senders.retain(|sender| !sender.is_closed());
+                !senders.is_empty()
+            });
+            state.queries.entry(name.clone()).or_default().push(tx);
+        }
+
+        let query = DnsMessage {
+            id: 0,
+            is_response: false,
+            questions: [TYPE_A, TYPE_AAAA]
+                .into_iter()
+                .map(|record_type| DnsQuestion {
+                    name: name.clone(),
+                    record_type,
+                })
+                .collect(),
+            answers: vec![],
+        }
+        .encode()?;
+        for _ in 0..QUERY_ATTEMPTS {
+            self.socket.send_to(&query, self.group).await?;
+            if let Ok(result) = timeout(QUERY_INTERVAL, &mut rx).await {
+                let ip = result.map_err(|_| anyhow!("mDNS resolver closed; name={name}"))?;
+                debug!("mDNS name resolved; {name} -> {ip}");
+                return Ok(ip);
+            }
+        }
+        Err(anyhow!("mDNS name not resolved; name={name}"))
+    }
+}
+
+impl Drop for Mdns {
+    fn drop(&mut self) {
+        self.recv_loop_handle.abort();
+    }
+}
+
+async fn recv_loop(socket: Arc<UdpSocket>, group: SocketAddrV4, state: Arc<StdMutex<MdnsState>>) {
+    let mut buf = vec![0u8; MAX_MESSAGE_SIZE];
+    loop {
+        let (len, from) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(err) => {
+                debug!("failed to receive mDNS message: {err}");
+                continue;
+            }
+        };
+        let message = match DnsMessage::decode(&buf[..len]) {
+            Ok(message) => message,
+            Err(err) => {
+                debug!("failed to decode mDNS message; from={from}: {err}");
+                continue;
+            }
+        };
+
+        if message.is_response {
+            let mut state = state.lock().unwrap();
+            for answer in message.answers {
+                let Some(senders) = state.queries.remove(&answer.name.to_ascii_lowercase()) else {
+                    continue;
+                };
+                for sender in senders {
+                    let _ = sender.send(answer.ip);
+                }
+            }
+            continue;
+        }
+
+        // https://datatracker.ietf.org/doc/html/rfc6762#section-6
+        let answers = {
+            let state = state.lock().unwrap();
+            message
+                .questions
+                .iter()
+                .filter_map(|question| {
+                    let name = question.name.to_ascii_lowercase();
+                    let ip = *state.published.get(&name)?;
+                    let record_type = record_type_of(ip);
+                    (question.record_type == record_type || question.record_type == TYPE_ANY)
+                        .then_some(DnsRecord {
+                            name,
+                            ip,
+                            ttl: RECORD_TTL,
+                        })
+                })
+                .collect::<Vec<_>>()
+        };
+        if answers.is_empty() {
+            continue;
+        }
+        let response = DnsMessage {
+            id: 0,
+            is_response: true,
+            questions: vec![],
+            answers,
+        };
+        let result = match response.encode() {
+            Ok(response) => socket.send_to(&response, group).await.map_err(Into::into),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            debug!("failed to send mDNS response; from={from}: {err}");
+        }
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc4122#section-4.4
+fn generate_uuid() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = bytes
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn record_type_of(ip: IpAddr) -> u16 {
+    match ip {
+        IpAddr::V4(_) => TYPE_A,
+        IpAddr::V6(_) => TYPE_AAAA,
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc1035#section-4.1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsMessage {
+    pub id: u16,
+    pub is_response: bool,
+    pub questions: Vec<DnsQuestion>,
+    /// A/AAAA records of the answer section; other records are skipped.
+    pub answers: Vec<DnsRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsQuestion {
+    pub name: String,
+    pub record_type: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsRecord {
+    pub name: String,
+    pub ip: IpAddr,
+    pub ttl: u32,
+}
+
+impl DnsMessage {
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        let mut reader = BufReader::new(buf);
+        let id = reader.read_u16()?;
+        let flags = reader.read_u16()?;
+        let question_count = reader.read_u16()?;
+        let answer_count = reader.read_u16()?;
+        // authority and additional sections are not read.
+        reader.read_u16()?;
+        reader.read_u16()?;
+
+        let mut questions = vec![];
+        for _ in 0..question_count {
+            let name = decode_name(&mut reader)?;
+            let record_type = reader.read_u16()?;
+            reader.read_u16()?; // class
+            questions.push(DnsQuestion { name, record_type });
+        }
+        let mut answers = vec![];
+        for _ in 0..answer_count {
+            let name = decode_name(&mut reader)?;
+            let record_type = reader.read_u16()?;
+            let class = reader.read_u16()? & !CACHE_FLUSH;
+            let ttl = reader.read_u32()?;
+            let mut data = vec![0u8; reader.read_u16()? as usize];
+            reader.read_exact(&mut data)?;
+            let ip = match (class, record_type, data.len()) {
+                (CLASS_IN, TYPE_A, 4) => {
+                    IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(data.as_slice())?))
+                }
+                (CLASS_IN, TYPE_AAAA, 16) => {
+                    IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(data.as_slice())?))
+                }
+                _ => continue,
+            };
+            answers.push(DnsRecord { name, ip, ttl });
+        }
+        Ok(Self {
+            id,
+            // https://datatracker.ietf.org/doc/html/rfc6762#section-18.2
+            is_response: flags & 0x8000 != 0,
+            questions,
+            answers,
+        })
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut writer = BufWriter::new();
+        writer.write_u16(self.id);
+        // https://datatracker.ietf.org/doc/html/rfc6762#section-18.4
+        writer.write_u16(if self.is_response { 0x8400 } else { 0 });
+        writer.write_u16(self.questions.len() as u16);
+        writer.write_u16(self.answers.len() as u16);
+        writer.write_u16(0);
+        writer.write_u16(0);
+        for question in &self.questions {
+            encode_name(&mut writer, &question.name)?;
+            writer.write_u16(question.record_type);
+            writer.write_u16(CLASS_IN);
+        }
+        for answer in &self.answers {
+            encode_name(&mut writer, &answer.name)?;
+            writer.write_u16(record_type_of(answer.ip));
+            writer.write_u16(CACHE_FLUSH | CLASS_IN);
+            writer.write_u32(answer.ttl);
+            match answer.ip {
+                IpAddr::V4(ip) => {
+                    writer.write_u16(4);
+                    writer.write_bytes(&ip.octets());
+                }
+                IpAddr::V6(ip) => {
+                    writer.write_u16(16);
+                    writer.write_bytes(&ip.octets());
+                }
+            }
+        }
+        Ok(writer.buf())
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc1035#section-4.1.4
+fn decode_name(reader: &mut BufReader) -> Result<String> {
+    let mut labels = vec![];
+    // position to continue from after the first compression pointer
+    let mut resume_at = None;
+    let mut jumps = 0;
+    loop {
+        let length = reader.read_u8()?;
+        match length {
+            0 => break,
+            _ if length & 0xc0 == 0xc0 => {
+                let offset = (((length & 0x3f) as usize) << 8) | reader.read_u8()? as usize;
+                jumps += 1;
+                if jumps > MAX_POINTER_JUMPS || offset >= reader.buf.len() {
+                    return Err(anyhow!("invalid name compression pointer; offset={offset}"));
+                }
+                resume_at.get_or_insert(reader.pos);
+                reader.pos = offset;
+            }
+            _ if length & 0xc0 != 0 => return Err(anyhow!("invalid label length; {length}")),
+            _ => {
+                let mut label = vec![0u8; length as usize];
+                reader.read_exact(&mut label)?;
+                labels.push(String::from_utf8(label)?);
+            }
+        }
+    }
+    if let Some(pos) = resume_at {
+        reader.pos = pos;
+    }
+    Ok(labels.join("."))
+}
+
+fn encode_name(writer: &mut BufWriter, name: &str) -> Result<()> {
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(anyhow!("invalid label; name={name}"));
+        }
+        writer.write_u8(label.len() as u8);
+        writer.write_bytes(label.as_bytes());
+    }
+    writer.write_u8(0);
+    Ok(())
+}
+
+#[cfg(test)]
+mod mdns_tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_compressed_name() -> Result<()> {
+        let mut writer = BufWriter::new();
+        writer.write_bytes(&[0, 0, 0x84, 0, 0, 0, 0, 2, 0, 0, 0, 0]);
+        encode_name(&mut writer, "a6b3c1d2.local")?;
+        writer.write_bytes(&[0, 1, 0x80, 1, 0, 0, 0, 120, 0, 4, 192, 168, 1, 10]);
+        // pointer to the name of the first record
+        writer.write_bytes(&[0xc0, 12, 0, 28, 0x80, 1, 0, 0, 0, 120, 0, 16]);
+        writer.write_bytes(&"fd00::1".parse::<Ipv6Addr>()?.octets());
+
+        let message = DnsMessage::decode(&writer.buf())?;
+        assert!(message.is_response);
+        assert_eq!(
+            message.answers,
+            vec![
+                DnsRecord {
+                    name: "a6b3c1d2.local".to_string(),
+                    ip: "192.168.1.10".parse()?,
+                    ttl: 120,
+                },
+                DnsRecord {
+                    name: "a6b3c1d2.local".to_string(),
+                    ip: "fd00::1".parse()?,
+                    ttl: 120,
+                },
+            ]
+        );
+        assert_eq!(DnsMessage::decode(&message.encode()?)?, message);
+
+        // pointers must not loop forever
+        let mut writer = BufWriter::new();
+        writer.write_bytes(&[0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0xc0, 12, 0, 1, 0, 1]);
+        assert!(DnsMessage::decode(&writer.buf()).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_over_loopback() -> Result<()> {
+        let port = std::net::UdpSocket::bind("127.0.0.1:0")?
+            .local_addr()?
+            .port();
+        let options = MdnsOptions {
+            group: SocketAddrV4::new(MDNS_GROUP, port),
+            interface: Ipv4Addr::LOCALHOST,
+        };
+        let responder = Mdns::bind(options.clone())?;
+        let resolver = Mdns::bind(options)?;
+
+        let name = responder.publish("192.168.1.10".parse()?);
+        assert!(name.ends_with(".local"));
+        assert_eq!(responder.publish("192.168.1.10".parse()?), name);
+        let v6_name = responder.publish("fd00::1".parse()?);
+        assert_ne!(v6_name, name);
+
+        assert_eq!(
+            resolver.resolve(&name.to_uppercase()).await?,
+            "192.168.1.10".parse::<IpAddr>()?
+        );
+        assert_eq!(
+            resolver.resolve(&v6_name).await?,
+            "fd00::1".parse::<IpAddr>()?
+        );
+        assert!(resolver.resolve("example.com").await.is_err());
+        Ok(())
+    }
+}
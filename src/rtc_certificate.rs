@@ -0,0 +1,239 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, anyhow};
+use p256::elliptic_curve::rand_core::OsRng;
+use pem::{EncodeConfig, LineEnding, Pem};
+use rcgen::{CertificateParams, DistinguishedName, DnType, KeyPair};
+use rsa::RsaPrivateKey;
+use rsa::pkcs8::EncodePrivateKey;
+
+use crate::dtls::certificate_fingerprint;
+use crate::sdp::FingerprintType;
+use crate::sdp::session_description::SdpFingerprint;
+
+const DEFAULT_COMMON_NAME: &str = "mini-webrtc-rs";
+// https://www.w3.org/TR/webrtc/#dom-rtcpeerconnection-generatecertificate
+const DEFAULT_VALIDITY: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+// tolerates peers whose clocks run behind.
+const NOT_BEFORE_MARGIN: Duration = Duration::from_secs(24 * 60 * 60);
+const CERTIFICATE_TAG: &str = "CERTIFICATE";
+const PRIVATE_KEY_TAG: &str = "PRIVATE KEY";
+
+/// Key of a generated certificate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAlgorithm {
+    EcdsaP256,
+    EcdsaP384,
+    Rsa { modulus_bits: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtcCertificateParams {
+    pub key_algorithm: KeyAlgorithm,
+    /// Common name of the self-signed subject.
+    pub common_name: String,
+    /// The certificate is valid from `not_before` until `not_before + validity`.
+    pub not_before: SystemTime,
+    pub validity: Duration,
+}
+
+impl Default for RtcCertificateParams {
+    fn default() -> Self {
+        Self {
+            key_algorithm: KeyAlgorithm::EcdsaP256,
+            common_name: DEFAULT_COMMON_NAME.to_string(),
+            not_before: SystemTime::now() - NOT_BEFORE_MARGIN,
+            validity: DEFAULT_VALIDITY,
+        }
+    }
+}
+
+/// Self-signed certificate identifying the endpoint in DTLS; its fingerprint is signaled
+/// as `a=fingerprint`, so keep it to stay pinnable across restarts.
+// https://www.w3.org/TR/webrtc/#dom-rtccertificate
+#[derive(Debug, Clone)]
+pub struct RtcCertificate {
+    /// DER-encoded X.509 certificate.
+    pub certificate: Vec<u8>,
+    pub key_pair: Arc<KeyPair>,
+    pub expires: SystemTime,
+}
+
+impl RtcCertificate {
+    pub fn generate(params: &RtcCertificateParams) -> Result<Self> {
+        let key_pair = match params.key_algorithm {
+            KeyAlgorithm::EcdsaP256 => KeyPair::generate_for(&rcgen::PKCS_ECDSA_P256_SHA256)?,
+            KeyAlgorithm::EcdsaP384 => KeyPair::generate_for(&rcgen::PKCS_ECDSA_P384_SHA384)?,
+            KeyAlgorithm::Rsa { modulus_bits } => {
+                let private_key = RsaPrivateKey::new(&mut OsRng, modulus_bits)
+                    .map_err(|err| anyhow!("failed to generate rsa key: {err}"))?;
+                let private_key = private_key
+                    .to_pkcs8_der()
+                    .map_err(|err| anyhow!("invalid rsa private key: {err}"))?;
+                KeyPair::try_from(private_key.as_bytes())?
+            }
+        };
+
+        let mut certificate_params = CertificateParams::default();
+        certificate_params.distinguished_name = DistinguishedName::new();
+        certificate_params
+            .distinguished_name
+            .push(DnType::CommonName, params.common_name.as_str());
+        certificate_params.not_before = params.not_before.into();
+        certificate_params.not_after = (params.not_before + params.validity).into();
+        let certificate = certificate_params.self_signed(&key_pair)?;
+        Self::new(certificate.der().to_vec(), key_pair)
+    }
+
+    /// Pairs a DER certificate with its PKCS#8 DER private key.
+    pub fn from_der(certificate: &[u8], private_key: &[u8]) -> Result<Self> {
+        let key_pair = KeyPair::try_from(private_key)
+            .map_err(|err| anyhow!("unsupported private key, PKCS#8 required: {err}"))?;
+        Self::new(certificate.to_vec(), key_pair)
+    }
+
+    /// Parses the `CERTIFICATE` and PKCS#8 `PRIVATE KEY` blocks written by `to_pem`.
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        let blocks = pem::parse_many(pem)?;
+        let find_block = |tag: &str| {
+            blocks
+                .iter()
+                .find(|block| block.tag() == tag)
+                .map(|block| block.contents())
+                .ok_or(anyhow!("`{tag}` block not found"))
+        };
+        Self::from_der(find_block(CERTIFICATE_TAG)?, find_block(PRIVATE_KEY_TAG)?)
+    }
+
+    /// Returns the DER certificate and the PKCS#8 DER private key.
+    pub fn to_der(&self) -> (Vec<u8>, Vec<u8>) {
+        (self.certificate.clone(), self.key_pair.serialize_der())
+    }
+
+    pub fn to_pem(&self) -> String {
+        let (certificate, private_key) = self.to_der();
+        pem::encode_many_config(
+            &[
+                Pem::new(CERTIFICATE_TAG, certificate),
+                Pem::new(PRIVATE_KEY_TAG, private_key),
+            ],
+            EncodeConfig::new().set_line_ending(LineEnding::LF),
+        )
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let pem = fs::read_to_string(path)
+            .with_context(|| format!("read certificate; {}", path.display()))?;
+        Self::from_pem(&pem).with_context(|| format!("parse certificate; {}", path.display()))
+    }
+
+    /// Writes `to_pem` to `path`; the file is readable by its owner only.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(path)
+            .and_then(|mut file| file.write_all(self.to_pem().as_bytes()))
+            .with_context(|| format!("write certificate; {}", path.display()))
+    }
+
+    /// Loads the certificate at `path`, or generates and saves one when it is missing or
+    /// expired.
+    pub fn load_or_generate(path: impl AsRef<Path>, params: &RtcCertificateParams) -> Result<Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            let certificate = Self::load(path)?;
+            if !certificate.is_expired(SystemTime::now()) {
+                return Ok(certificate);
+            }
+        }
+        let certificate = Self::generate(params)?;
+        certificate.save(path)?;
+        Ok(certificate)
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        now >= self.expires
+    }
+
+    /// `a=fingerprint` of the certificate.
+    pub fn fingerprint(&self, hash_function: FingerprintType) -> SdpFingerprint {
+        SdpFingerprint {
+            hash_function,
+            fingerprint: certificate_fingerprint(&self.certificate, hash_function),
+        }
+    }
+
+    fn new(certificate: Vec<u8>, key_pair: KeyPair) -> Result<Self> {
+        let (_, x509) = x509_parser::parse_x509_certificate(&certificate)?;
+        if x509.public_key().subject_public_key.data != key_pair.public_key_raw() {
+            anyhow::bail!("private key does not match the certificate");
+        }
+        let not_after = x509.validity().not_after.timestamp();
+        let expires = UNIX_EPOCH + Duration::from_secs(not_after.max(0) as u64);
+        Ok(Self {
+            certificate,
+            key_pair: Arc::new(key_pair),
+            expires,
+        })
+    }
+}
+
+#[cfg(test)]
+mod rtc_certificate_tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        let not_before = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let certificate = RtcCertificate::generate(&RtcCertificateParams {
+            key_algorithm: KeyAlgorithm::EcdsaP384,
+            common_name: "example".to_string(),
+            not_before,
+            validity: Duration::from_secs(3600),
+        })?;
+        assert_eq!(
+            certificate.key_pair.algorithm(),
+            &rcgen::PKCS_ECDSA_P384_SHA384
+        );
+        assert_eq!(certificate.expires, not_before + Duration::from_secs(3600));
+        assert!(!certificate.is_expired(not_before));
+        assert!(certificate.is_expired(certificate.expires));
+
+        let (_, x509) = x509_parser::parse_x509_certificate(&certificate.certificate)?;
+        assert_eq!(x509.subject().to_string(), "CN=example");
+        assert_eq!(x509.validity().not_before.timestamp(), 1_700_000_000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pem_and_der_roundtrip() -> Result<()> {
+        let certificate = RtcCertificate::generate(&RtcCertificateParams::default())?;
+        let fingerprint = certificate.fingerprint(FingerprintType::Sha256);
+
+        let loaded = RtcCertificate::from_pem(&certificate.to_pem())?;
+        assert_eq!(loaded.fingerprint(FingerprintType::Sha256), fingerprint);
+        assert_eq!(loaded.expires, certificate.expires);
+        assert_eq!(
+            loaded.key_pair.serialize_der(),
+            certificate.key_pair.serialize_der()
+        );
+
+        let (der, private_key) = certificate.to_der();
+        let loaded = RtcCertificate::from_der(&der, &private_key)?;
+        assert_eq!(loaded.fingerprint(FingerprintType::Sha256), fingerprint);
+
+        let stranger = RtcCertificate::generate(&RtcCertificateParams::default())?;
+        let (_, stranger_key) = stranger.to_der();
+        assert!(RtcCertificate::from_der(&der, &stranger_key).is_err());
+        Ok(())
+    }
+}
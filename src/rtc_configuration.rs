@@ -0,0 +1,232 @@
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::dtls::manager::{DEFAULT_HANDSHAKE_TIMEOUT, DEFAULT_MTU};
+use crate::dtls::record_header::DtlsVersion;
+use crate::dtls::session::SessionCache;
+use crate::mdns::MdnsOptions;
+use crate::rtc_certificate::RtcCertificate;
+
+const DEFAULT_STUN_SERVER_URL: &str = "stun:stun.l.google.com:19302";
+const DEFAULT_UDP_PORT: u16 = 4433;
+const DEFAULT_TCP_PORT: u16 = 4434;
+const DEFAULT_SIGNALING_ADDRESS: &str = "127.0.0.1:3001";
+const DEFAULT_ALLOWED_ORIGIN: &str = "http://localhost:5173";
+
+// https://developer.mozilla.org/en-US/docs/Web/API/RTCPeerConnection/RTCPeerConnection#configuration
+pub struct RtcConfiguration {
+    pub ice_servers: Vec<RtcIceServer>,
+    /// `[::]` binds a dual-stack socket, falling back to `0.0.0.0` without IPv6.
+    pub bind_address: IpAddr,
+    /// Ports tried in order until the UDP socket binds.
+    pub port_range: RangeInclusive<u16>,
+    /// Port of the listener for passive ICE-TCP candidates, which must differ from the UDP
+    /// port; ICE-TCP is disabled when `None`.
+    pub tcp_port: Option<u16>,
+    /// mDNS responder/resolver for `.local` host candidates; remote ones are ignored when
+    /// `None`, the default, or when binding fails.
+    pub mdns: Option<MdnsOptions>,
+    /// Signals host candidates as random mDNS names instead of their ips; requires `mdns`.
+    pub mdns_host_candidates: bool,
+    /// DTLS certificates, each signaled as `a=fingerprint`; a self-signed ECDSA P-256
+    /// certificate is generated when empty, and expired ones are rejected.
+    // https://www.w3.org/TR/webrtc/#dom-rtcconfiguration-certificates
+    pub certificates: Vec<RtcCertificate>,
+    /// DTLS fails when the handshake does not complete in time, retransmissions included.
+    pub dtls_handshake_timeout: Duration,
+    /// `V1_3` offers DTLS 1.3 and still accepts DTLS 1.2 peers.
+    pub dtls_max_version: DtlsVersion,
+    /// Path MTU of DTLS datagrams; larger handshake messages are fragmented.
+    pub dtls_mtu: usize,
+    /// Sessions resumed by a reconnecting peer; share it between configurations to resume
+    /// across peer connections.
+    pub dtls_session_cache: Arc<Mutex<SessionCache>>,
+    /// Built-in HTTP signaling server is not started when `None`, the default.
+    pub signaling: Option<SignalingOptions>,
+    pub bundle_policy: RtcBundlePolicy,
+    pub rtcp_mux_policy: RtcRtcpMuxPolicy,
+}
+
+impl Default for RtcConfiguration {
+    fn default() -> Self {
+        Self {
+            ice_servers: vec![RtcIceServer::new(DEFAULT_STUN_SERVER_URL)],
+            bind_address: IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            port_range: DEFAULT_UDP_PORT..=DEFAULT_UDP_PORT,
+            tcp_port: Some(DEFAULT_TCP_PORT),
+            mdns: None,
+            mdns_host_candidates: false,
+            certificates: vec![],
+            dtls_handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+            dtls_max_version: DtlsVersion::V1_2,
+            dtls_mtu: DEFAULT_MTU,
+            dtls_session_cache: Arc::new(Mutex::new(SessionCache::new())),
+            signaling: None,
+            bundle_policy: RtcBundlePolicy::MaxBundle,
+            rtcp_mux_policy: RtcRtcpMuxPolicy::Require,
+        }
+    }
+}
+
+impl RtcConfiguration {
+    /// Returns `host:port` of every `stun:` url in `ice_servers`.
+    pub fn stun_server_addresses(&self) -> Vec<String> {
+        self.ice_servers
+            .iter()
+            .flat_map(|server| server.urls.iter())
+            .filter_map(|url| url.strip_prefix("stun:"))
+            .map(|address| address.split('?').next().unwrap_or(address).to_string())
+            .collect()
+    }
+
+    /// Returns every udp `turn:` url in `ice_servers` that comes with credentials.
+    pub fn turn_servers(&self) -> Vec<TurnServer> {
+        self.ice_servers
+            .iter()
+            .filter_map(|server| {
+                Some((
+                    server,
+                    server.username.as_ref()?,
+                    server.credential.as_ref()?,
+                ))
+            })
+            .flat_map(|(server, username, credential)| {
+                server.urls.iter().filter_map(|url| {
+                    let address = url.strip_prefix("turn:")?;
+                    let (address, query) = address.split_once('?').unwrap_or((address, ""));
+                    // https://datatracker.ietf.org/doc/html/rfc7065#section-3.1
+                    (query.is_empty() || query == "transport=udp").then(|| TurnServer {
+                        address: address.to_string(),
+                        username: username.clone(),
+                        credential: credential.clone(),
+                    })
+                })
+            })
+            .collect()
+    }
+}
+
+// https://developer.mozilla.org/en-US/docs/Web/API/RTCIceServer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtcIceServer {
+    pub urls: Vec<String>,
+    pub username: Option<String>,
+    pub credential: Option<String>,
+}
+
+impl RtcIceServer {
+    pub fn new(url: &str) -> Self {
+        Self {
+            urls: vec![url.to_string()],
+            username: None,
+            credential: None,
+        }
+    }
+}
+
+/// `host:port` of a `turn:` url with the credentials of its ice server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TurnServer {
+    pub address: String,
+    pub username: String,
+    pub credential: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignalingOptions {
+    pub bind_address: SocketAddr,
+    pub allowed_origins: Vec<String>,
+}
+
+impl Default for SignalingOptions {
+    fn default() -> Self {
+        Self {
+            bind_address: DEFAULT_SIGNALING_ADDRESS.parse().unwrap(),
+            allowed_origins: vec![DEFAULT_ALLOWED_ORIGIN.to_string()],
+        }
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8829#section-4.1.1
+// Every media section already shares a single transport (`MaxBundle` behavior);
+// the other policies are accepted but not distinguished yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtcBundlePolicy {
+    Balanced,
+    MaxCompat,
+    MaxBundle,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8829#section-4.1.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtcRtcpMuxPolicy {
+    /// Remote RTP media sections without `a=rtcp-mux` are rejected.
+    Require,
+    Negotiate,
+}
+
+#[cfg(test)]
+mod rtc_configuration_tests {
+    use super::*;
+
+    #[test]
+    fn test_stun_server_addresses() {
+        let config = RtcConfiguration {
+            ice_servers: vec![
+                RtcIceServer {
+                    urls: vec![
+                        "stun:stun.example.com:3478".to_string(),
+                        "turn:turn.example.com:3478?transport=udp".to_string(),
+                    ],
+                    username: Some("user".to_string()),
+                    credential: Some("pass".to_string()),
+                },
+                RtcIceServer::new("stun:127.0.0.1:3478?transport=udp"),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            config.stun_server_addresses(),
+            vec!["stun.example.com:3478", "127.0.0.1:3478"]
+        );
+        let config = RtcConfiguration {
+            ice_servers: vec![],
+            ..Default::default()
+        };
+        assert!(config.stun_server_addresses().is_empty());
+    }
+
+    #[test]
+    fn test_turn_servers() {
+        let config = RtcConfiguration {
+            ice_servers: vec![
+                RtcIceServer {
+                    urls: vec![
+                        "stun:stun.example.com:3478".to_string(),
+                        "turn:turn.example.com:3478?transport=udp".to_string(),
+                        "turn:turn.example.com:3478?transport=tcp".to_string(),
+                        "turn:127.0.0.1:3478".to_string(),
+                    ],
+                    username: Some("user".to_string()),
+                    credential: Some("pass".to_string()),
+                },
+                RtcIceServer::new("turn:no-credentials.example.com:3478"),
+            ],
+            ..Default::default()
+        };
+        let turn_server = |address: &str| TurnServer {
+            address: address.to_string(),
+            username: "user".to_string(),
+            credential: "pass".to_string(),
+        };
+        assert_eq!(
+            config.turn_servers(),
+            vec![
+                turn_server("turn.example.com:3478"),
+                turn_server("127.0.0.1:3478")
+            ]
+        );
+    }
+}
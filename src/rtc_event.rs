@@ -1,7 +1,19 @@
-use crate::media_stream_track::MediaStreamTrack;
+use crate::{
+    dtls::DtlsState,
+    ice::{IceConnectionState, IceGatheringState},
+    media_stream_track::MediaStreamTrack,
+    rtc_ice_candidate::RtcIceCandidate,
+    rtc_peer_connection::PeerConnectionState,
+};
 
 pub enum RtcEvent {
     RtcTrack(RtcTrackEvent),
+    IceConnectionStateChange(IceConnectionState),
+    IceGatheringStateChange(IceGatheringState),
+    ConnectionStateChange(PeerConnectionState),
+    DtlsStateChange(DtlsState),
+    /// Locally gathered candidate to be sent to the remote; the last one is end-of-candidates.
+    IceCandidate(RtcIceCandidate),
 }
 
 pub struct RtcTrackEvent {
@@ -0,0 +1,189 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ice::{IceCandidate, candidate_pair::RTP_COMPONENT_ID},
+    sdp::{
+        CandidateAddress, TransportType,
+        session_description::{
+            SdpCandidate, decode_candidate_type, encode_candidate_type, encode_tcp_type,
+        },
+    },
+};
+
+const CANDIDATE_PREFIX: &str = "candidate:";
+
+// https://developer.mozilla.org/en-US/docs/Web/API/RTCIceCandidate
+// Serialized the same way as `RTCIceCandidate.toJSON()` in browsers.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RtcIceCandidate {
+    /// `candidate:` attribute; an empty string signals end-of-candidates.
+    pub candidate: String,
+    pub sdp_mid: Option<String>,
+    pub sdp_m_line_index: Option<u16>,
+    pub username_fragment: Option<String>,
+}
+
+impl RtcIceCandidate {
+    pub fn new(candidate: &IceCandidate, sdp_mid: &str, username_fragment: &str) -> Self {
+        let candidate = SdpCandidate {
+            foundation: candidate.foundation.clone(),
+            component: candidate.component,
+            transport: candidate.transport_type,
+            priority: candidate.priority,
+            address: candidate.signaled_address().to_string(),
+            port: candidate.port as u16,
+            candidate_type: encode_candidate_type(candidate.candidate_type).to_string(),
+            related_address: candidate.related_address.map(|addr| addr.ip().to_string()),
+            related_port: candidate.related_address.map(|addr| addr.port()),
+            tcp_type: candidate
+                .tcp_type
+                .map(|tcp_type| encode_tcp_type(tcp_type).to_string()),
+            extensions: vec![],
+        };
+        Self {
+            candidate: format!("{CANDIDATE_PREFIX}{}", candidate.encode()),
+            sdp_mid: Some(sdp_mid.to_string()),
+            sdp_m_line_index: Some(0),
+            username_fragment: Some(username_fragment.to_string()),
+        }
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8838#section-13
+    pub fn end_of_candidates(sdp_mid: &str, username_fragment: &str) -> Self {
+        Self {
+            candidate: String::new(),
+            sdp_mid: Some(sdp_mid.to_string()),
+            sdp_m_line_index: Some(0),
+            username_fragment: Some(username_fragment.to_string()),
+        }
+    }
+
+    pub fn is_end_of_candidates(&self) -> bool {
+        self.candidate.is_empty()
+    }
+
+    /// Returns `None` for candidates this agent cannot use; end-of-candidates, rtcp,
+    /// tcp and names other than mDNS ones. Candidates with an mDNS name are unresolved.
+    pub fn to_ice_candidate(&self) -> Result<Option<IceCandidate>> {
+        if self.is_end_of_candidates() {
+            return Ok(None);
+        }
+        let value = self
+            .candidate
+            .strip_prefix(CANDIDATE_PREFIX)
+            .unwrap_or(&self.candidate);
+        let candidate = SdpCandidate::decode(value)?;
+        if candidate.component != RTP_COMPONENT_ID || candidate.transport != TransportType::Udp {
+            return Ok(None);
+        }
+        let (ip, mdns_name) = match CandidateAddress::decode(&candidate.address) {
+            Some(CandidateAddress::Ip(ip)) => (ip, None),
+            Some(CandidateAddress::Mdns(name)) => (IpAddr::V4(Ipv4Addr::UNSPECIFIED), Some(name)),
+            None => return Ok(None),
+        };
+        if candidate.port == 0 {
+            return Err(anyhow!("invalid candidate port; {}", self.candidate));
+        }
+        let related_address = candidate
+            .related_address
+            .and_then(|address| address.parse::<IpAddr>().ok())
+            .zip(candidate.related_port)
+            .map(|(ip, port)| SocketAddr::new(ip, port));
+        Ok(Some(IceCandidate {
+            foundation: candidate.foundation,
+            component: candidate.component,
+            ip,
+            port: candidate.port as u64,
+            candidate_type: decode_candidate_type(&candidate.candidate_type)?,
+            transport_type: TransportType::Udp,
+            tcp_type: None,
+            priority: candidate.priority,
+            related_address,
+            mdns_name,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod rtc_ice_candidate_tests {
+    use super::*;
+    use crate::sdp::CandidateType;
+
+    #[test]
+    fn test_candidate_round_trip() -> Result<()> {
+        let candidate = IceCandidate::host("192.168.1.10".parse()?, 54400, u16::MAX);
+        let rtc_candidate = RtcIceCandidate::new(&candidate, "0", "ufrag");
+        assert_eq!(
+            rtc_candidate.candidate,
+            format!(
+                "candidate:{} 1 udp 2130706431 192.168.1.10 54400 typ host",
+                candidate.foundation
+            )
+        );
+        assert_eq!(rtc_candidate.to_ice_candidate()?, Some(candidate));
+
+        let candidate = IceCandidate::server_reflexive(
+            "203.0.113.7:61000".parse()?,
+            "192.168.1.10:54400".parse()?,
+            "198.51.100.1:3478".parse()?,
+            u16::MAX,
+        );
+        let rtc_candidate = RtcIceCandidate::new(&candidate, "0", "ufrag");
+        assert!(
+            rtc_candidate
+                .candidate
+                .ends_with("typ srflx raddr 192.168.1.10 rport 54400")
+        );
+        assert_eq!(rtc_candidate.to_ice_candidate()?, Some(candidate));
+
+        // passive ICE-TCP candidates are signaled but remote tcp candidates are not used
+        let candidate = IceCandidate::passive_tcp_host("192.168.1.10".parse()?, 4434, 0x1fff);
+        let rtc_candidate = RtcIceCandidate::new(&candidate, "0", "ufrag");
+        assert_eq!(
+            rtc_candidate.candidate,
+            format!(
+                "candidate:{} 1 tcp 2124414975 192.168.1.10 4434 typ host tcptype passive",
+                candidate.foundation
+            )
+        );
+        assert_eq!(rtc_candidate.to_ice_candidate()?, None);
+
+        // browsers serialize `RTCIceCandidate` in camelCase
+        let json = r#"{"candidate":"candidate:2999745851 1 udp 1686052607 203.0.113.7 54400 typ srflx raddr 192.168.1.10 rport 54400 generation 0","sdpMid":"0","sdpMLineIndex":0,"usernameFragment":"abcd"}"#;
+        let rtc_candidate = serde_json::from_str::<RtcIceCandidate>(json)?;
+        let candidate = rtc_candidate.to_ice_candidate()?.unwrap();
+        assert_eq!(candidate.candidate_type, CandidateType::Srflx);
+        assert_eq!(candidate.addr(), "203.0.113.7:54400".parse()?);
+        assert_eq!(candidate.priority, 1686052607);
+        assert_eq!(candidate.foundation, "2999745851");
+        assert_eq!(candidate.base(), "192.168.1.10:54400".parse()?);
+
+        let mdns = RtcIceCandidate {
+            candidate: "candidate:3412421386 1 udp 2122262783 a6b3c1d2.local 54401 typ host"
+                .to_string(),
+            ..rtc_candidate.clone()
+        };
+        let candidate = mdns.to_ice_candidate()?.unwrap();
+        assert!(candidate.is_unresolved());
+        assert_eq!(candidate.mdns_name.as_deref(), Some("a6b3c1d2.local"));
+
+        // host candidates published over mDNS are signaled by name
+        let mut candidate = IceCandidate::host("192.168.1.10".parse()?, 54400, u16::MAX);
+        candidate.mdns_name = Some("a6b3c1d2.local".to_string());
+        let rtc_candidate = RtcIceCandidate::new(&candidate, "0", "ufrag");
+        assert!(
+            rtc_candidate
+                .candidate
+                .contains(" a6b3c1d2.local 54400 typ host")
+        );
+
+        let end_of_candidates = RtcIceCandidate::end_of_candidates("0", "abcd");
+        assert!(end_of_candidates.is_end_of_candidates());
+        assert_eq!(end_of_candidates.to_ice_candidate()?, None);
+        Ok(())
+    }
+}
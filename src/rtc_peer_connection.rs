@@ -1,135 +1,530 @@
 use crate::common::TransportMessage;
+use crate::common::error::MiniWebrtcRsError;
 use crate::data_channel::DataChannel;
-use crate::dtls::Fingerprint;
 use crate::dtls::manager::DtlsManager;
-use crate::ice::Peer;
-use crate::internal_event::InternalEvent;
+use crate::dtls::{DtlsEvent, DtlsRole, DtlsState};
+use crate::ice::{IceConnectionState, IceGatheringState, IceRole, Peer};
+use crate::internal_event::{EventQueue, InternalEvent};
+use crate::mdns::Mdns;
 use crate::media_stream_track::{
     MediaStreamTrack, MediaStreamTrackKind, MediaStreamTrackReadyState,
 };
+use crate::rtc_certificate::{RtcCertificate, RtcCertificateParams};
+use crate::rtc_configuration::{RtcBundlePolicy, RtcConfiguration, RtcRtcpMuxPolicy, TurnServer};
 use crate::rtc_event::{RtcEvent, RtcTrackEvent};
-use crate::rtc_sctp::RtcSctpTransport;
+use crate::rtc_ice_candidate::RtcIceCandidate;
+use crate::rtc_sctp::{RtcSctpTransport, RtcSctpTransportState};
+use crate::rtc_session_description::{
+    DescriptionSource, RtcSdpType, RtcSessionDescription, RtcSignalingState,
+};
 use crate::sctp::manager::SctpManager;
-use crate::sdp::MediaType;
+use crate::sdp::{FingerprintType, MediaType, Setup, TransportType};
 use crate::srtp::SrtpManager;
 use crate::srtp::packet::RtpPacket;
 use crate::{
-    ice::{IceAgent, IceCandidate},
+    ice::{IceAgent, IceCandidate, gather_host_addresses},
     signaling_server::SignalingServer,
-    stun::StunClient,
-    udp_server::UdpServer,
+    tcp_server::TcpServer,
+    turn::TurnClient,
+    udp_server::{UdpServer, UdpTransport, bind_udp_socket},
 };
 use anyhow::{Context, Result, anyhow};
-use local_ip_address::local_ip;
-use rcgen::generate_simple_self_signed;
 use std::collections::VecDeque;
-use std::net::ToSocketAddrs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::net::{UdpSocket, lookup_host};
 use tokio::select;
-use tokio::sync::{Mutex, mpsc};
+use tokio::sync::{Mutex, Notify, mpsc};
 use tokio::task::JoinHandle;
 use tracing::{debug, info, warn};
 
-const UDP_SERVER_PORT: u64 = 4433;
-const STUN_SERVER_ADDRESS: &'static str = "stun.l.google.com:19302";
+// https://developer.mozilla.org/en-US/docs/Web/API/RTCPeerConnection/connectionState
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerConnectionState {
+    New,
+    Connecting,
+    Connected,
+    Disconnected,
+    Failed,
+    Closed,
+}
+
+impl PeerConnectionState {
+    /// Aggregates the transport states; the sctp state only counts once a data channel exists.
+    // https://w3c.github.io/webrtc-pc/#rtcpeerconnectionstate-enum
+    pub fn from_transports(
+        ice: IceConnectionState,
+        dtls: DtlsState,
+        sctp: Option<RtcSctpTransportState>,
+    ) -> Self {
+        match (ice, dtls, sctp) {
+            (IceConnectionState::Closed, _, _) => Self::Closed,
+            (IceConnectionState::Failed, _, _) | (_, DtlsState::Failed, _) => Self::Failed,
+            (IceConnectionState::Disconnected, _, _) => Self::Disconnected,
+            (IceConnectionState::New, DtlsState::New | DtlsState::Closed, _) => Self::New,
+            (
+                IceConnectionState::Connected | IceConnectionState::Completed,
+                DtlsState::Connected | DtlsState::Closed,
+                None | Some(RtcSctpTransportState::Connected | RtcSctpTransportState::Closed),
+            ) => Self::Connected,
+            _ => Self::Connecting,
+        }
+    }
+}
+
+const LOCAL_SDP_MID: &str = "0";
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
 
 pub struct PeerConnection {
     pub sctp: Option<RtcSctpTransport>,
+    pub signaling_state: RtcSignalingState,
+    pub ice_connection_state: IceConnectionState,
+    /// Local candidates gathered so far, ending with end-of-candidates once gathering completes.
+    pub local_candidates: Vec<RtcIceCandidate>,
+    pub connection_state: PeerConnectionState,
+    pub dtls_state: DtlsState,
+    pub current_local_description: Option<RtcSessionDescription>,
+    pub pending_local_description: Option<RtcSessionDescription>,
+    pub current_remote_description: Option<RtcSessionDescription>,
+    pub pending_remote_description: Option<RtcSessionDescription>,
+    pub bundle_policy: RtcBundlePolicy,
+    pub rtcp_mux_policy: RtcRtcpMuxPolicy,
+    ice_agent: Arc<Mutex<IceAgent>>,
+    internal_event_queue: Arc<Mutex<EventQueue>>,
+    // wakes the event loop for events queued from outside of it
+    internal_event_notify: Arc<Notify>,
+    mdns: Option<Arc<Mdns>>,
+}
+
+impl PeerConnection {
+    pub fn new(
+        config: &RtcConfiguration,
+        ice_agent: Arc<Mutex<IceAgent>>,
+        internal_event_queue: Arc<Mutex<EventQueue>>,
+    ) -> Self {
+        Self {
+            sctp: None,
+            signaling_state: RtcSignalingState::Stable,
+            ice_connection_state: IceConnectionState::New,
+            local_candidates: vec![],
+            connection_state: PeerConnectionState::New,
+            dtls_state: DtlsState::New,
+            current_local_description: None,
+            pending_local_description: None,
+            current_remote_description: None,
+            pending_remote_description: None,
+            bundle_policy: config.bundle_policy,
+            rtcp_mux_policy: config.rtcp_mux_policy,
+            ice_agent,
+            internal_event_queue,
+            internal_event_notify: Arc::new(Notify::new()),
+            mdns: None,
+        }
+    }
+
+    pub fn local_description(&self) -> Option<&RtcSessionDescription> {
+        self.pending_local_description
+            .as_ref()
+            .or(self.current_local_description.as_ref())
+    }
+
+    pub fn remote_description(&self) -> Option<&RtcSessionDescription> {
+        self.pending_remote_description
+            .as_ref()
+            .or(self.current_remote_description.as_ref())
+    }
+
+    pub async fn create_offer(&self) -> Result<RtcSessionDescription> {
+        if !self.signaling_state.can_create_offer() {
+            Err(MiniWebrtcRsError::InvalidSignalingStateError {
+                state: format!("{:?}", self.signaling_state),
+                operation: "create offer".to_string(),
+            })?;
+        }
+        let offer = self.ice_agent.lock().await.generate_sdp_offer();
+        Ok(RtcSessionDescription::offer(offer))
+    }
+
+    pub async fn create_answer(&self) -> Result<RtcSessionDescription> {
+        let remote_offer = self
+            .remote_description()
+            .filter(|_| self.signaling_state.can_create_answer())
+            .ok_or(MiniWebrtcRsError::InvalidSignalingStateError {
+                state: format!("{:?}", self.signaling_state),
+                operation: "create answer".to_string(),
+            })?;
+        let answer = self
+            .ice_agent
+            .lock()
+            .await
+            .generate_sdp_answer(&remote_offer.sdp)?;
+        Ok(RtcSessionDescription::answer(answer))
+    }
+
+    pub async fn set_local_description(
+        &mut self,
+        description: RtcSessionDescription,
+    ) -> Result<()> {
+        self.set_description(DescriptionSource::Local, description)
+            .await
+    }
+
+    pub async fn set_remote_description(
+        &mut self,
+        description: RtcSessionDescription,
+    ) -> Result<()> {
+        self.set_description(DescriptionSource::Remote, description)
+            .await
+    }
+
+    // https://w3c.github.io/webrtc-pc/#dom-peerconnection-addicecandidate
+    pub async fn add_ice_candidate(&self, candidate: RtcIceCandidate) -> Result<()> {
+        let remote =
+            self.remote_description()
+                .ok_or(MiniWebrtcRsError::InvalidSignalingStateError {
+                    state: format!("{:?}", self.signaling_state),
+                    operation: "add ice candidate".to_string(),
+                })?;
+        if let Some(ufrag) = &candidate.username_fragment
+            && !remote.sdp.medias.iter().any(|media| &media.ufrag == ufrag)
+        {
+            return Err(anyhow!("ice candidate for unknown ufrag; {ufrag}"));
+        }
+
+        let mut ice_agent = self.ice_agent.lock().await;
+        if candidate.is_end_of_candidates() {
+            debug!("remote end-of-candidates received.");
+            ice_agent.remote_end_of_candidates = true;
+            return Ok(());
+        }
+        match candidate.to_ice_candidate()? {
+            Some(remote_candidate) if remote_candidate.is_unresolved() => {
+                resolve_remote_candidate(self.mdns.as_ref(), &self.ice_agent, remote_candidate)
+            }
+            Some(remote_candidate) => ice_agent.add_ice_candidate(remote_candidate, Instant::now()),
+            None => debug!("ignore unusable ice candidate; {}", candidate.candidate),
+        }
+        Ok(())
+    }
+
+    /// Discards a pending offer and returns to `Stable`.
+    pub fn rollback(&mut self) -> Result<()> {
+        self.signaling_state = self.signaling_state.rollback()?;
+        self.pending_local_description = None;
+        self.pending_remote_description = None;
+        Ok(())
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8829#section-5.10
+    async fn set_description(
+        &mut self,
+        source: DescriptionSource,
+        description: RtcSessionDescription,
+    ) -> Result<()> {
+        let next_state = self.signaling_state.next(source, description.sdp_type)?;
+        if source == DescriptionSource::Remote && self.rtcp_mux_policy == RtcRtcpMuxPolicy::Require
+        {
+            // https://datatracker.ietf.org/doc/html/rfc8829#section-5.10
+            if let Some(media) = description.sdp.medias.iter().find(|media| {
+                media.media_type != MediaType::Application && media.rtcp_mux.is_none()
+            }) {
+                return Err(anyhow!(
+                    "rtcp-mux is required by policy, but remote media is not muxed; media_id={}",
+                    media.media_id
+                ));
+            }
+        }
+        let (current, pending, other_current, other_pending) = match source {
+            DescriptionSource::Local => (
+                &mut self.current_local_description,
+                &mut self.pending_local_description,
+                &mut self.current_remote_description,
+                &mut self.pending_remote_description,
+            ),
+            DescriptionSource::Remote => (
+                &mut self.current_remote_description,
+                &mut self.pending_remote_description,
+                &mut self.current_local_description,
+                &mut self.pending_local_description,
+            ),
+        };
+
+        // https://datatracker.ietf.org/doc/html/rfc8445#section-6.1.1
+        if description.sdp_type == RtcSdpType::Offer {
+            self.ice_agent.lock().await.set_role(match source {
+                DescriptionSource::Local => IceRole::Controlling,
+                DescriptionSource::Remote => IceRole::Controlled,
+            });
+        }
+
+        match description.sdp_type {
+            RtcSdpType::Offer | RtcSdpType::Pranswer => {
+                *pending = Some(description);
+            }
+            RtcSdpType::Answer => {
+                *current = Some(description);
+                *pending = None;
+                if let Some(offer) = other_pending.take() {
+                    *other_current = Some(offer);
+                }
+            }
+        }
+        debug!(
+            "signaling state changed; {:?} -> {next_state:?}",
+            self.signaling_state
+        );
+        self.signaling_state = next_state;
+
+        // negotiation completed; apply the remote description to the transports.
+        if next_state == RtcSignalingState::Stable
+            && let Some(remote) = &self.current_remote_description
+        {
+            self.internal_event_queue
+                .lock()
+                .await
+                .push_back(InternalEvent::SetRemoteDescription(remote.sdp.clone()));
+            self.internal_event_notify.notify_one();
+        }
+        Ok(())
+    }
 }
 
 pub struct RtcPeerConnection {
     sctp_manager: Arc<Mutex<SctpManager>>,
     rtc_event_rx: mpsc::UnboundedReceiver<RtcEvent>,
     event_loop_handle: JoinHandle<Result<()>>,
-    signaling_server_handle: JoinHandle<Result<()>>,
+    gathering_handle: JoinHandle<()>,
+    signaling_server_handle: Option<JoinHandle<Result<()>>>,
     pc: Arc<Mutex<PeerConnection>>,
 }
 
 impl RtcPeerConnection {
-    pub async fn new() -> Result<Self> {
-        tracing_subscriber::fmt::init();
-
-        // Generate self-signed certificate
-        let certified_key = generate_simple_self_signed(vec!["localhost".to_string()])?;
-        let fingerprint = Fingerprint::new(certified_key.cert.der());
-
-        let local_ip = local_ip().unwrap();
-        info!("local_ip={local_ip:?}");
-
-        let mut stun_addrs = STUN_SERVER_ADDRESS
-            .to_socket_addrs()
-            .context("resolve STUN server address")?;
-        let stun_addr = stun_addrs
-            .find(|addr| addr.is_ipv4())
-            .or_else(|| stun_addrs.next())
-            .ok_or(anyhow!("resolved STUN server address list is empty"))?;
-        let stun_client = StunClient::new(stun_addr);
-
-        let mut ice_candidates = vec![IceCandidate {
-            ip: local_ip,
-            port: UDP_SERVER_PORT,
-        }];
-
-        match stun_client.binding_request().await {
-            Ok(mapped_address) => {
-                info!("mapped_address={mapped_address:?}");
-                ice_candidates.push(IceCandidate {
-                    ip: mapped_address.ip(),
-                    port: UDP_SERVER_PORT,
-                });
+    /// Creates a connection with its own udp socket and tcp listener bound per `config`.
+    pub async fn new(config: RtcConfiguration) -> Result<Self> {
+        let socket = bind_config_udp_socket(&config).context("bind udp socket")?;
+        let udp_addr = socket.local_addr()?;
+        let tcp_server = match config.tcp_port {
+            Some(port) => Some(
+                TcpServer::bind(SocketAddr::new(udp_addr.ip(), port))
+                    .await
+                    .context("bind tcp listener")?,
+            ),
+            None => None,
+        };
+        Self::with_servers(config, UdpServer::new(socket), tcp_server).await
+    }
+
+    /// Creates a connection on a udp socket shared with other connections.
+    /// `config.bind_address`, `config.port_range` and `config.tcp_port` are ignored.
+    pub async fn with_udp_server(
+        config: RtcConfiguration,
+        udp_server: Arc<UdpServer>,
+    ) -> Result<Self> {
+        Self::with_servers(config, udp_server, None).await
+    }
+
+    /// Creates a connection on a udp socket and a tcp listener shared with other connections;
+    /// passive ICE-TCP candidates are gathered when `tcp_server` is given.
+    /// `config.bind_address`, `config.port_range` and `config.tcp_port` are ignored.
+    pub async fn with_servers(
+        mut config: RtcConfiguration,
+        udp_server: Arc<UdpServer>,
+        tcp_server: Option<Arc<TcpServer>>,
+    ) -> Result<Self> {
+        if config.certificates.is_empty() {
+            let certificate = RtcCertificate::generate(&RtcCertificateParams::default())?;
+            config.certificates.push(certificate);
+        }
+        let now = SystemTime::now();
+        if let Some(certificate) = config
+            .certificates
+            .iter()
+            .find(|certificate| certificate.is_expired(now))
+        {
+            anyhow::bail!(
+                "certificate expired; fingerprint={}",
+                certificate.fingerprint(FingerprintType::Sha256).fingerprint
+            );
+        }
+        let fingerprints = config
+            .certificates
+            .iter()
+            .map(|certificate| certificate.fingerprint(FingerprintType::Sha256))
+            .collect::<Vec<_>>();
+
+        let local_addr = udp_server.local_addr()?;
+        let port = local_addr.port() as u64;
+
+        let (rtc_event_tx, rtc_event_rx) = mpsc::unbounded_channel::<RtcEvent>();
+        let mut ice_agent = IceAgent::new(vec![], fingerprints);
+        ice_agent.gathering_state = IceGatheringState::Gathering;
+        emit_rtc_event(
+            &rtc_event_tx,
+            RtcEvent::IceGatheringStateChange(IceGatheringState::Gathering),
+        );
+
+        let mdns = match config.mdns.take() {
+            Some(options) => Mdns::bind(options)
+                .inspect_err(|err| warn!("mDNS is disabled; {err:?}"))
+                .ok(),
+            None => None,
+        };
+        // https://datatracker.ietf.org/doc/html/draft-ietf-mmusic-mdns-ice-candidates#section-3.1.1
+        let host_name_publisher = mdns.as_ref().filter(|_| config.mdns_host_candidates);
+
+        let host_addresses = gather_host_addresses(local_addr.ip(), udp_server.is_dual_stack())?;
+        info!("host_addresses={host_addresses:?}");
+
+        let mut host_candidates = vec![];
+        // the first host candidate of each address family; the base of its srflx candidate
+        let mut srflx_bases: Vec<IceCandidate> = vec![];
+        for (index, ip) in host_addresses.into_iter().enumerate() {
+            // https://datatracker.ietf.org/doc/html/rfc8421#section-4
+            let mut host = IceCandidate::host(ip, port, u16::MAX - index as u16);
+            host.mdns_name = host_name_publisher.map(|mdns| mdns.publish(ip));
+            if !srflx_bases
+                .iter()
+                .any(|base| base.ip.is_ipv4() == host.ip.is_ipv4())
+            {
+                srflx_bases.push(host.clone());
             }
-            Err(err) => {
-                warn!("stun mapping failed; continue with host candidate only: {err}");
+            host_candidates.push(host);
+        }
+
+        // https://datatracker.ietf.org/doc/html/rfc6544#section-5.1
+        if let Some(tcp_server) = &tcp_server {
+            let tcp_addr = tcp_server.local_addr()?;
+            if tcp_addr.port() == local_addr.port() {
+                return Err(anyhow!(
+                    "tcp port must differ from the udp port; port={}",
+                    tcp_addr.port()
+                ));
+            }
+            for (index, ip) in gather_host_addresses(tcp_addr.ip(), tcp_server.is_dual_stack())?
+                .into_iter()
+                .enumerate()
+            {
+                let mut host = IceCandidate::passive_tcp_host(
+                    ip,
+                    tcp_addr.port() as u64,
+                    0x1fff - index as u16,
+                );
+                host.mdns_name = host_name_publisher.map(|mdns| mdns.publish(ip));
+                host_candidates.push(host);
             }
         }
 
-        let ice_agent = Arc::new(Mutex::new(IceAgent::new(
-            ice_candidates,
-            fingerprint.clone(),
-        )));
+        let ice_agent = Arc::new(Mutex::new(ice_agent));
 
         let internal_event_queue = Arc::new(Mutex::new(VecDeque::new()));
-        let (rtc_event_tx, rtc_event_rx) = mpsc::unbounded_channel::<RtcEvent>();
 
-        let pc = PeerConnection { sctp: None };
+        let mut pc = PeerConnection::new(&config, ice_agent.clone(), internal_event_queue.clone());
+        pc.mdns = mdns.clone();
+        let internal_event_notify = pc.internal_event_notify.clone();
         let pc = Arc::new(Mutex::new(pc));
 
-        let mut dtls_manager =
-            DtlsManager::new(certified_key, fingerprint, internal_event_queue.clone());
+        let mut dtls_manager = DtlsManager::new(config.certificates.clone());
+        dtls_manager.handshake_timeout = config.dtls_handshake_timeout;
+        dtls_manager.max_version = config.dtls_max_version;
+        dtls_manager.mtu = config.dtls_mtu;
+        dtls_manager.session_cache = config.dtls_session_cache.clone();
         let mut srtp_manager = SrtpManager::new(internal_event_queue.clone());
         let sctp_manager = SctpManager::new(internal_event_queue.clone(), pc.clone());
         let sctp_manager = Arc::new(Mutex::new(sctp_manager));
         let sctp_manager_clone = sctp_manager.clone();
 
-        let mut udp_server = UdpServer::new(
-            &format!("0.0.0.0:{UDP_SERVER_PORT}"),
+        let mut udp_transport = UdpTransport::new(
+            udp_server.clone(),
+            tcp_server,
             ice_agent.clone(),
+            &host_candidates,
             internal_event_queue.clone(),
         )
-        .await
-        .context("init udp server")?;
+        .await?;
+
+        // https://datatracker.ietf.org/doc/html/rfc8838#section-8
+        // candidates are trickled as they are gathered, after construction.
+        let (gathered_tx, mut gathered_rx) = mpsc::unbounded_channel();
+        let gathering_handle = tokio::spawn(gather_candidates(
+            host_candidates,
+            srflx_bases,
+            udp_server.clone(),
+            config.stun_server_addresses(),
+            config.turn_servers(),
+            gathered_tx,
+        ));
 
         let internal_event_queue_clone = internal_event_queue.clone();
+        let ice_agent_clone = ice_agent.clone();
+        let pc_clone = pc.clone();
 
         let event_loop_handle = tokio::spawn(async move {
             let sctp_manager = sctp_manager_clone;
+            let ice_agent = ice_agent_clone;
+            let pc = pc_clone;
+            let mut closing = false;
             loop {
                 let next_event = internal_event_queue_clone.lock().await.pop_front();
                 if let Some(event) = next_event {
                     match event {
-                        InternalEvent::SdpAnswer(answer) => {
-                            let remote_peers = answer
+                        InternalEvent::SetRemoteDescription(description) => {
+                            let remote_peers = description
                                 .medias
                                 .iter()
                                 .map(|media| Peer {
                                     ufrag: media.ufrag.clone(),
                                     pwd: media.pwd.clone(),
-                                    fingerprint: media.fingerprint_hash.clone(),
+                                    fingerprints: media.fingerprints(),
                                 })
                                 .collect::<Vec<_>>();
-                            udp_server.set_remote_peers(remote_peers).await;
+                            udp_transport.set_remote_peers(remote_peers).await;
+                            // https://datatracker.ietf.org/doc/html/rfc5763#section-5
+                            // a passive remote is the DTLS server; otherwise it is the client.
+                            dtls_manager.role =
+                                match description.medias.first().and_then(|media| media.setup) {
+                                    Some(Setup::Passive) => DtlsRole::Client,
+                                    _ => DtlsRole::Server,
+                                };
+                            // https://datatracker.ietf.org/doc/html/rfc8842#section-5.1
+                            if let Some(media) = description.medias.first() {
+                                let _ = dtls_manager
+                                    .set_remote_fingerprints(media.fingerprints(), Instant::now())
+                                    .inspect_err(|err| warn!("{err:?}"));
+                            }
+                            let mut unresolved = vec![];
+                            {
+                                let mut ice_agent = ice_agent.lock().await;
+                                // every media is bundled; candidates of the first media cover all.
+                                let candidates = description
+                                    .medias
+                                    .first()
+                                    .map(|media| media.candidates.clone())
+                                    .unwrap_or_default();
+                                for candidate in candidates.iter().filter(|candidate| {
+                                    candidate.transport_type == TransportType::Udp
+                                }) {
+                                    let candidate = IceCandidate::from(candidate);
+                                    if candidate.is_unresolved() {
+                                        unresolved.push(candidate);
+                                    } else {
+                                        ice_agent.add_remote_candidate(candidate);
+                                    }
+                                }
+                                if let Some(media) = description.medias.first() {
+                                    ice_agent.set_remote_description(media);
+                                }
+                                ice_agent.start_checks(Instant::now());
+                            }
+                            for candidate in unresolved {
+                                resolve_remote_candidate(mdns.as_ref(), &ice_agent, candidate);
+                            }
+                            let _ = udp_transport
+                                .handle_timeout()
+                                .await
+                                .inspect_err(|err| warn!("{err:?}"));
 
-                            for media in answer.medias {
+                            for media in description.medias {
                                 match media.media_type {
                                     MediaType::Video => {
                                         let (inbound_rtp_tx, inbound_rtp_rx) =
@@ -144,13 +539,12 @@ impl RtcPeerConnection {
                                             inbound_rtp_rx,
                                         };
 
-                                        if let Err(err) =
-                                            rtc_event_tx.send(RtcEvent::RtcTrack(RtcTrackEvent {
+                                        emit_rtc_event(
+                                            &rtc_event_tx,
+                                            RtcEvent::RtcTrack(RtcTrackEvent {
                                                 track: media_stream_tack,
-                                            }))
-                                        {
-                                            warn!("failed to emit rtc track event: {err}");
-                                        }
+                                            }),
+                                        );
                                     }
                                     _ => {
                                         debug!("ignore media type: {:?}", media.media_type);
@@ -159,13 +553,17 @@ impl RtcPeerConnection {
                             }
                         }
                         InternalEvent::InboundDtlsPacket(TransportMessage { peer_addr, data }) => {
-                            let _ = dtls_manager
-                                .handle_inbound_packet(&data, peer_addr)
-                                .await
-                                .inspect_err(|err| warn!("{err:?}"));
+                            // the DTLS peer follows only remotes authenticated by ICE.
+                            if ice_agent.lock().await.is_authenticated(peer_addr) {
+                                let _ = dtls_manager
+                                    .handle_inbound_packet(&data, peer_addr, Instant::now())
+                                    .inspect_err(|err| warn!("{err:?}"));
+                            } else {
+                                debug!("dtls packet from unauthenticated remote; peer={peer_addr}");
+                            }
                         }
                         InternalEvent::DtlsConnected(encryption_keys) => {
-                            srtp_manager.set_encryption_keys(encryption_keys);
+                            srtp_manager.set_encryption_keys(encryption_keys, dtls_manager.role);
                         }
                         InternalEvent::InboundRtpPacket(TransportMessage { peer_addr, data }) => {
                             let _ = srtp_manager
@@ -185,30 +583,82 @@ impl RtcPeerConnection {
                             data,
                         }) => {
                             let _ = dtls_manager
-                                .send_application_data(&data)
-                                .await
+                                .send_application_data(&data, Instant::now())
                                 .inspect_err(|err| warn!("{err:?}"));
                         }
                         InternalEvent::OutboundDtlsPacket(TransportMessage { peer_addr, data }) => {
-                            let _ = udp_server
+                            let _ = udp_transport
                                 .send(&data, peer_addr)
                                 .await
                                 .inspect_err(|err| warn!("{err:?}"));
                         }
+                        InternalEvent::Close => {
+                            closing = true;
+                            let _ = dtls_manager
+                                .close(Instant::now())
+                                .inspect_err(|err| warn!("{err:?}"));
+                        }
                     }
+                } else if closing {
+                    break;
                 } else {
+                    let next_timeout = udp_transport
+                        .next_timeout()
+                        .await
+                        .into_iter()
+                        .chain(dtls_manager.next_timeout())
+                        .min();
                     select! {
-                        _ = udp_server.recv() => {}
+                        result = udp_transport.recv() => match result {
+                            Some(result) => {
+                                let _ = result.inspect_err(|err| warn!("{err:?}"));
+                            }
+                            None => break,
+                        },
+                        Some(gathered) = gathered_rx.recv() => {
+                            add_gathered(gathered, &mut udp_transport, &ice_agent, &pc, &rtc_event_tx)
+                                .await;
+                        }
+                        _ = internal_event_notify.notified() => {}
+                        _ = sleep_until_timeout(next_timeout) => {
+                            let _ = udp_transport
+                                .handle_timeout()
+                                .await
+                                .inspect_err(|err| warn!("{err:?}"));
+                            let _ = dtls_manager
+                                .handle_timeout(Instant::now())
+                                .inspect_err(|err| warn!("{err:?}"));
+                        }
                     }
                 }
+                // the DTLS client starts the handshake once ICE selects a pair.
+                if dtls_manager.role == DtlsRole::Client
+                    && dtls_manager.state == DtlsState::New
+                    && let Some(peer_addr) = ice_agent.lock().await.selected_remote_addr()
+                {
+                    let _ = dtls_manager
+                        .connect(peer_addr, Instant::now())
+                        .inspect_err(|err| warn!("{err:?}"));
+                }
+                drain_dtls_manager(&mut dtls_manager, &internal_event_queue_clone).await;
+                update_connection_states(&pc, &ice_agent, dtls_manager.state, &rtc_event_tx).await;
             }
+            Ok(())
         });
 
-        let signaling_server = SignalingServer::new(ice_agent, internal_event_queue).await;
-        let signaling_server_handle = tokio::spawn(async move { signaling_server.run().await });
+        let signaling_server_handle = match config.signaling.take() {
+            Some(options) => {
+                let signaling_server = SignalingServer::new(pc.clone(), options)
+                    .await
+                    .context("init signaling server")?;
+                Some(tokio::spawn(async move { signaling_server.run().await }))
+            }
+            None => None,
+        };
 
         Ok(Self {
             event_loop_handle,
+            gathering_handle,
             signaling_server_handle,
             sctp_manager,
             rtc_event_rx,
@@ -220,13 +670,506 @@ impl RtcPeerConnection {
         self.rtc_event_rx.recv().await
     }
 
-    pub fn close(self) {
+    /// Sends close_notify to the remote, then stops the connection.
+    pub async fn close(mut self) {
+        {
+            let pc = self.pc.lock().await;
+            pc.internal_event_queue
+                .lock()
+                .await
+                .push_back(InternalEvent::Close);
+            pc.internal_event_notify.notify_one();
+        }
+        if tokio::time::timeout(CLOSE_TIMEOUT, &mut self.event_loop_handle)
+            .await
+            .is_err()
+        {
+            warn!("event loop did not stop in {CLOSE_TIMEOUT:?}");
+        }
+        // aborting the event loop drops the udp transport, which unregisters its route.
         self.event_loop_handle.abort();
-        self.signaling_server_handle.abort();
+        self.gathering_handle.abort();
+        if let Some(signaling_server_handle) = &self.signaling_server_handle {
+            signaling_server_handle.abort();
+        }
         drop(self);
     }
 
+    pub async fn signaling_state(&self) -> RtcSignalingState {
+        self.pc.lock().await.signaling_state
+    }
+
+    pub async fn ice_connection_state(&self) -> IceConnectionState {
+        self.pc.lock().await.ice_connection_state
+    }
+
+    pub async fn ice_gathering_state(&self) -> IceGatheringState {
+        self.pc.lock().await.ice_agent.lock().await.gathering_state
+    }
+
+    pub async fn connection_state(&self) -> PeerConnectionState {
+        self.pc.lock().await.connection_state
+    }
+
+    pub async fn dtls_state(&self) -> DtlsState {
+        self.pc.lock().await.dtls_state
+    }
+
+    pub async fn create_offer(&self) -> Result<RtcSessionDescription> {
+        self.pc.lock().await.create_offer().await
+    }
+
+    pub async fn create_answer(&self) -> Result<RtcSessionDescription> {
+        self.pc.lock().await.create_answer().await
+    }
+
+    pub async fn set_local_description(&self, description: RtcSessionDescription) -> Result<()> {
+        self.pc
+            .lock()
+            .await
+            .set_local_description(description)
+            .await
+    }
+
+    pub async fn set_remote_description(&self, description: RtcSessionDescription) -> Result<()> {
+        self.pc
+            .lock()
+            .await
+            .set_remote_description(description)
+            .await
+    }
+
+    pub async fn rollback(&self) -> Result<()> {
+        self.pc.lock().await.rollback()
+    }
+
+    pub async fn add_ice_candidate(&self, candidate: RtcIceCandidate) -> Result<()> {
+        self.pc.lock().await.add_ice_candidate(candidate).await
+    }
+
+    pub async fn local_candidates(&self) -> Vec<RtcIceCandidate> {
+        self.pc.lock().await.local_candidates.clone()
+    }
+
     pub async fn create_data_channel(&self) -> Result<DataChannel> {
         Ok(DataChannel::new(0, self.sctp_manager.clone()).await)
     }
 }
+
+// a candidate handed from the gathering task to the event loop
+enum Gathered {
+    Candidate(IceCandidate),
+    // the allocation of the relay candidate
+    Relay(Box<TurnClient>, IceCandidate),
+    Complete,
+}
+
+// Gathers host candidates, then server reflexive and relay candidates, and hands each one
+// to the event loop once gathered; end-of-candidates comes last.
+async fn gather_candidates(
+    host_candidates: Vec<IceCandidate>,
+    mut srflx_bases: Vec<IceCandidate>,
+    udp_server: Arc<UdpServer>,
+    stun_server_addresses: Vec<String>,
+    turn_servers: Vec<TurnServer>,
+    gathered_tx: mpsc::UnboundedSender<Gathered>,
+) {
+    let gathered = |gathered| {
+        if gathered_tx.send(gathered).is_err() {
+            debug!("event loop stopped; discard the gathered candidate.");
+        }
+    };
+    for host in host_candidates {
+        gathered(Gathered::Candidate(host));
+    }
+
+    for stun_server_address in stun_server_addresses {
+        if srflx_bases.is_empty() {
+            break;
+        }
+        let stun_addrs = match resolve_server_addresses(&stun_server_address).await {
+            Ok(stun_addrs) => stun_addrs,
+            Err(err) => {
+                warn!("{err:?}");
+                continue;
+            }
+        };
+        for stun_addr in stun_addrs {
+            let Some(index) = srflx_bases
+                .iter()
+                .position(|base| base.ip.is_ipv4() == stun_addr.is_ipv4())
+            else {
+                continue;
+            };
+            let base = &srflx_bases[index];
+            match udp_server.binding_request(stun_addr).await {
+                // https://datatracker.ietf.org/doc/html/rfc8445#section-5.1.3
+                // a mapping identical to the base is redundant with the host candidate.
+                Ok(mapped_address) if mapped_address == base.addr() => {
+                    info!("mapped_address is the host address; {mapped_address:?}");
+                    srflx_bases.remove(index);
+                }
+                Ok(mapped_address) => {
+                    info!("mapped_address={mapped_address:?}");
+                    gathered(Gathered::Candidate(IceCandidate::server_reflexive(
+                        mapped_address,
+                        base.addr(),
+                        stun_addr,
+                        base.local_preference(),
+                    )));
+                    srflx_bases.remove(index);
+                }
+                Err(err) => {
+                    warn!("stun mapping failed; server={stun_addr}: {err}");
+                }
+            }
+        }
+    }
+
+    for turn_server in turn_servers {
+        // ipv4 is preferred for relays.
+        let turn_addr = match resolve_server_addresses(&turn_server.address).await {
+            Ok(turn_addrs) => turn_addrs[0],
+            Err(err) => {
+                warn!("{err:?}");
+                continue;
+            }
+        };
+        match allocate_relay(turn_addr, &turn_server.username, &turn_server.credential).await {
+            Ok((turn_client, relayed_address, mapped_address)) => {
+                gathered(Gathered::Relay(
+                    Box::new(turn_client),
+                    IceCandidate::relayed(relayed_address, mapped_address, turn_addr, u16::MAX),
+                ));
+                break;
+            }
+            Err(err) => {
+                warn!(
+                    "turn allocation failed; server={}: {err}",
+                    turn_server.address
+                );
+            }
+        }
+    }
+    gathered(Gathered::Complete);
+}
+
+// Adds a gathered candidate to the agent and signals it; every media is bundled, so
+// candidates are signaled on the first media.
+async fn add_gathered(
+    gathered: Gathered,
+    udp_transport: &mut UdpTransport,
+    ice_agent: &Mutex<IceAgent>,
+    pc: &Mutex<PeerConnection>,
+    rtc_event_tx: &mpsc::UnboundedSender<RtcEvent>,
+) {
+    let candidate = match gathered {
+        Gathered::Candidate(candidate) => candidate,
+        // the relay routes checks from the relay candidate, so it is set before pairing.
+        Gathered::Relay(relay, candidate) => {
+            udp_transport.set_relay(*relay);
+            candidate
+        }
+        Gathered::Complete => {
+            let end_of_candidates = {
+                let mut ice_agent = ice_agent.lock().await;
+                ice_agent.gathering_state = IceGatheringState::Complete;
+                RtcIceCandidate::end_of_candidates(LOCAL_SDP_MID, &ice_agent.local_peer.ufrag)
+            };
+            pc.lock()
+                .await
+                .local_candidates
+                .push(end_of_candidates.clone());
+            emit_rtc_event(rtc_event_tx, RtcEvent::IceCandidate(end_of_candidates));
+            emit_rtc_event(
+                rtc_event_tx,
+                RtcEvent::IceGatheringStateChange(IceGatheringState::Complete),
+            );
+            return;
+        }
+    };
+    let rtc_candidate = {
+        let mut ice_agent = ice_agent.lock().await;
+        let rtc_candidate = RtcIceCandidate::new(
+            &ice_agent.signaled_candidate(&candidate),
+            LOCAL_SDP_MID,
+            &ice_agent.local_peer.ufrag,
+        );
+        ice_agent.add_local_candidate(candidate, Instant::now());
+        rtc_candidate
+    };
+    pc.lock().await.local_candidates.push(rtc_candidate.clone());
+    emit_rtc_event(rtc_event_tx, RtcEvent::IceCandidate(rtc_candidate));
+}
+
+/// Adds a remote candidate once its mDNS name resolves; the agent only pairs candidates
+/// with an ip.
+fn resolve_remote_candidate(
+    mdns: Option<&Arc<Mdns>>,
+    ice_agent: &Arc<Mutex<IceAgent>>,
+    mut candidate: IceCandidate,
+) {
+    let (Some(mdns), Some(name)) = (mdns.cloned(), candidate.mdns_name.clone()) else {
+        debug!(
+            "no mDNS resolver; ignore candidate {:?}",
+            candidate.mdns_name
+        );
+        return;
+    };
+    let ice_agent = ice_agent.clone();
+    tokio::spawn(async move {
+        match mdns.resolve(&name).await {
+            Ok(ip) => {
+                candidate.ip = ip;
+                ice_agent
+                    .lock()
+                    .await
+                    .add_ice_candidate(candidate, Instant::now());
+            }
+            Err(err) => warn!("{err:?}"),
+        }
+    });
+}
+
+fn emit_rtc_event(rtc_event_tx: &mpsc::UnboundedSender<RtcEvent>, event: RtcEvent) {
+    if rtc_event_tx.send(event).is_err() {
+        debug!("rtc event receiver dropped; discard the event.");
+    }
+}
+
+// Queues the datagrams and events of the DTLS endpoint as internal events.
+async fn drain_dtls_manager(
+    dtls_manager: &mut DtlsManager,
+    internal_event_queue: &Mutex<EventQueue>,
+) {
+    let mut internal_event_queue = internal_event_queue.lock().await;
+    while let Some(message) = dtls_manager.poll_transmit() {
+        internal_event_queue.push_back(InternalEvent::OutboundDtlsPacket(message));
+    }
+    while let Some(event) = dtls_manager.poll_event() {
+        match event {
+            DtlsEvent::Connected(Some(encryption_keys)) => {
+                internal_event_queue.push_back(InternalEvent::DtlsConnected(encryption_keys));
+            }
+            DtlsEvent::Connected(None) => warn!("use_srtp is not negotiated; srtp is disabled."),
+            // assuming all coming application data are sctp packets
+            DtlsEvent::ApplicationData(message) => {
+                internal_event_queue.push_back(InternalEvent::InboundSctpPacket(message));
+            }
+        }
+    }
+}
+
+// Emits state change events once the transports settle after each internal event.
+async fn update_connection_states(
+    pc: &Mutex<PeerConnection>,
+    ice_agent: &Mutex<IceAgent>,
+    dtls_state: DtlsState,
+    rtc_event_tx: &mpsc::UnboundedSender<RtcEvent>,
+) {
+    let ice_state = ice_agent.lock().await.state;
+    let mut pc = pc.lock().await;
+    if pc.ice_connection_state != ice_state {
+        info!(
+            "ice connection state changed; {:?} -> {ice_state:?}",
+            pc.ice_connection_state
+        );
+        pc.ice_connection_state = ice_state;
+        emit_rtc_event(rtc_event_tx, RtcEvent::IceConnectionStateChange(ice_state));
+    }
+
+    if pc.dtls_state != dtls_state {
+        info!("dtls state changed; {:?} -> {dtls_state:?}", pc.dtls_state);
+        pc.dtls_state = dtls_state;
+        emit_rtc_event(rtc_event_tx, RtcEvent::DtlsStateChange(dtls_state));
+    }
+
+    let sctp_state = pc.sctp.as_ref().map(|sctp| sctp.state);
+    let connection_state = PeerConnectionState::from_transports(ice_state, dtls_state, sctp_state);
+    if pc.connection_state != connection_state {
+        info!(
+            "connection state changed; {:?} -> {connection_state:?}",
+            pc.connection_state
+        );
+        pc.connection_state = connection_state;
+        emit_rtc_event(
+            rtc_event_tx,
+            RtcEvent::ConnectionStateChange(connection_state),
+        );
+    }
+}
+
+async fn sleep_until_timeout(timeout: Option<Instant>) {
+    match timeout {
+        Some(timeout) => tokio::time::sleep_until(timeout.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+fn bind_config_udp_socket(config: &RtcConfiguration) -> Result<UdpSocket> {
+    let mut last_err = None;
+    for port in config.port_range.clone() {
+        let result = match bind_udp_socket(SocketAddr::new(config.bind_address, port)) {
+            // hosts without IPv6 fall back to IPv4 only.
+            Err(err) if config.bind_address == IpAddr::V6(Ipv6Addr::UNSPECIFIED) => {
+                debug!("failed to bind dual-stack udp port {port}: {err}");
+                bind_udp_socket(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port))
+            }
+            result => result,
+        };
+        match result {
+            Ok(socket) => return Ok(socket),
+            Err(err) => {
+                debug!("failed to bind udp port {port}: {err}");
+                last_err = Some(err);
+            }
+        }
+    }
+    Err(match last_err {
+        Some(err) => anyhow!(err),
+        None => anyhow!("port range is empty; {:?}", config.port_range),
+    })
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8445#section-5.1.1.2
+async fn allocate_relay(
+    turn_addr: SocketAddr,
+    username: &str,
+    credential: &str,
+) -> Result<(TurnClient, SocketAddr, SocketAddr)> {
+    let mut turn_client = TurnClient::bind(turn_addr, username, credential).await?;
+    let relayed_address = turn_client.allocate().await?;
+    let mapped_address = turn_client
+        .mapped_address()
+        .ok_or(anyhow!("turn allocation has no mapped address"))?;
+    Ok((turn_client, relayed_address, mapped_address))
+}
+
+// resolves `host:port` of a STUN or TURN server; ipv4 addresses come first.
+async fn resolve_server_addresses(address: &str) -> Result<Vec<SocketAddr>> {
+    let mut server_addrs = lookup_host(address)
+        .await
+        .with_context(|| format!("resolve ICE server address; {address}"))?
+        .collect::<Vec<_>>();
+    if server_addrs.is_empty() {
+        return Err(anyhow!(
+            "resolved ICE server address list is empty; {address}"
+        ));
+    }
+    server_addrs.sort_by_key(|addr| !addr.is_ipv4());
+    Ok(server_addrs)
+}
+
+#[cfg(test)]
+mod rtc_peer_connection_tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_state_from_transports() {
+        use IceConnectionState as Ice;
+        use PeerConnectionState::*;
+
+        let state = PeerConnectionState::from_transports;
+        assert_eq!(state(Ice::New, DtlsState::New, None), New);
+        assert_eq!(state(Ice::Checking, DtlsState::New, None), Connecting);
+        assert_eq!(
+            state(Ice::Connected, DtlsState::Connecting, None),
+            Connecting
+        );
+        assert_eq!(state(Ice::Completed, DtlsState::Connected, None), Connected);
+        assert_eq!(
+            state(
+                Ice::Connected,
+                DtlsState::Connected,
+                Some(RtcSctpTransportState::Connecting)
+            ),
+            Connecting
+        );
+        assert_eq!(
+            state(
+                Ice::Connected,
+                DtlsState::Connected,
+                Some(RtcSctpTransportState::Connected)
+            ),
+            Connected
+        );
+        assert_eq!(
+            state(Ice::Disconnected, DtlsState::Connected, None),
+            Disconnected
+        );
+        assert_eq!(state(Ice::Connected, DtlsState::Failed, None), Failed);
+        assert_eq!(state(Ice::Failed, DtlsState::Connected, None), Failed);
+        assert_eq!(state(Ice::Closed, DtlsState::Connected, None), Closed);
+    }
+
+    fn loopback_config() -> RtcConfiguration {
+        RtcConfiguration {
+            ice_servers: vec![],
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port_range: 0..=0,
+            tcp_port: None,
+            ..Default::default()
+        }
+    }
+
+    async fn wait_for(pc: &mut RtcPeerConnection, expected: fn(&RtcEvent) -> bool) -> Result<()> {
+        loop {
+            let event = tokio::time::timeout(Duration::from_secs(10), pc.recv())
+                .await?
+                .ok_or(anyhow!("event loop stopped"))?;
+            if expected(&event) {
+                return Ok(());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connections_on_shared_udp_server() -> Result<()> {
+        let gathered = |event: &RtcEvent| {
+            matches!(
+                event,
+                RtcEvent::IceGatheringStateChange(IceGatheringState::Complete)
+            )
+        };
+        let connected = |event: &RtcEvent| {
+            matches!(
+                event,
+                RtcEvent::ConnectionStateChange(PeerConnectionState::Connected)
+            )
+        };
+        let udp_server = UdpServer::bind("127.0.0.1:0".parse()?).await?;
+        let mut connections = vec![];
+        for _ in 0..2 {
+            let mut offerer = RtcPeerConnection::new(loopback_config()).await?;
+            let mut answerer =
+                RtcPeerConnection::with_udp_server(loopback_config(), udp_server.clone()).await?;
+            wait_for(&mut offerer, gathered).await?;
+            wait_for(&mut answerer, gathered).await?;
+            let offer = offerer.create_offer().await?;
+            offerer.set_local_description(offer.clone()).await?;
+            answerer.set_remote_description(offer).await?;
+            let answer = answerer.create_answer().await?;
+            answerer.set_local_description(answer.clone()).await?;
+            offerer.set_remote_description(answer).await?;
+            connections.push((offerer, answerer));
+        }
+        for (offerer, answerer) in &mut connections {
+            wait_for(offerer, connected).await?;
+            wait_for(answerer, connected).await?;
+        }
+
+        // each connection on the shared socket only talks to its own remote.
+        let mut offerer_addrs = vec![];
+        for (offerer, _) in &connections {
+            let offerer = offerer.pc.lock().await;
+            offerer_addrs.push(offerer.ice_agent.lock().await.ice_candidates[0].addr());
+        }
+        for (index, (_, answerer)) in connections.iter().enumerate() {
+            let answerer = answerer.pc.lock().await;
+            let ice_agent = answerer.ice_agent.lock().await;
+            assert_eq!(ice_agent.selected_remote_addr(), Some(offerer_addrs[index]));
+            assert!(!ice_agent.is_authenticated(offerer_addrs[1 - index]));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+use anyhow::Result;
+
+use crate::{common::error::MiniWebrtcRsError, sdp::SdpMessage};
+
+// https://developer.mozilla.org/en-US/docs/Web/API/RTCSessionDescription/type
+// `rollback` is not a description type here; use `RtcPeerConnection::rollback` instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtcSdpType {
+    Offer,
+    Pranswer,
+    Answer,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtcSessionDescription {
+    pub sdp_type: RtcSdpType,
+    pub sdp: SdpMessage,
+}
+
+impl RtcSessionDescription {
+    pub fn offer(sdp: SdpMessage) -> Self {
+        Self {
+            sdp_type: RtcSdpType::Offer,
+            sdp,
+        }
+    }
+
+    pub fn answer(sdp: SdpMessage) -> Self {
+        Self {
+            sdp_type: RtcSdpType::Answer,
+            sdp,
+        }
+    }
+
+    pub fn pranswer(sdp: SdpMessage) -> Self {
+        Self {
+            sdp_type: RtcSdpType::Pranswer,
+            sdp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DescriptionSource {
+    Local,
+    Remote,
+}
+
+// https://developer.mozilla.org/en-US/docs/Web/API/RTCPeerConnection/signalingState
+// https://datatracker.ietf.org/doc/html/rfc8829#section-3.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtcSignalingState {
+    Stable,
+    HaveLocalOffer,
+    HaveRemoteOffer,
+    HaveLocalPranswer,
+    HaveRemotePranswer,
+    Closed,
+}
+
+impl RtcSignalingState {
+    /// Returns the state after applying a description, or an error if the
+    /// description is not allowed in the current state.
+    pub fn next(self, source: DescriptionSource, sdp_type: RtcSdpType) -> Result<Self> {
+        use DescriptionSource::*;
+        use RtcSdpType::*;
+        use RtcSignalingState::*;
+
+        let next = match (self, source, sdp_type) {
+            (Stable | HaveLocalOffer, Local, Offer) => HaveLocalOffer,
+            (Stable | HaveRemoteOffer, Remote, Offer) => HaveRemoteOffer,
+            (HaveLocalOffer | HaveRemotePranswer, Remote, Pranswer) => HaveRemotePranswer,
+            (HaveLocalOffer | HaveRemotePranswer, Remote, Answer) => Stable,
+            (HaveRemoteOffer | HaveLocalPranswer, Local, Pranswer) => HaveLocalPranswer,
+            (HaveRemoteOffer | HaveLocalPranswer, Local, Answer) => Stable,
+            _ => Err(MiniWebrtcRsError::InvalidSignalingStateError {
+                state: format!("{self:?}"),
+                operation: format!("set {source:?} {sdp_type:?}").to_lowercase(),
+            })?,
+        };
+        Ok(next)
+    }
+
+    /// Returns the state after discarding a pending offer.
+    pub fn rollback(self) -> Result<Self> {
+        match self {
+            Self::HaveLocalOffer | Self::HaveRemoteOffer => Ok(Self::Stable),
+            _ => Err(MiniWebrtcRsError::InvalidSignalingStateError {
+                state: format!("{self:?}"),
+                operation: "rollback".to_string(),
+            })?,
+        }
+    }
+
+    pub fn can_create_offer(self) -> bool {
+        matches!(self, Self::Stable | Self::HaveLocalOffer)
+    }
+
+    pub fn can_create_answer(self) -> bool {
+        matches!(self, Self::HaveRemoteOffer | Self::HaveLocalPranswer)
+    }
+}
+
+#[cfg(test)]
+mod rtc_session_description_tests {
+    use super::*;
+    use DescriptionSource::*;
+    use RtcSdpType::*;
+    use RtcSignalingState::*;
+
+    #[test]
+    fn test_offerer_transitions() -> Result<()> {
+        let state = Stable.next(Local, Offer)?;
+        assert_eq!(state, HaveLocalOffer);
+        let state = state.next(Remote, Pranswer)?;
+        assert_eq!(state, HaveRemotePranswer);
+        assert_eq!(state.next(Remote, Answer)?, Stable);
+        Ok(())
+    }
+
+    #[test]
+    fn test_answerer_transitions() -> Result<()> {
+        let state = Stable.next(Remote, Offer)?;
+        assert_eq!(state, HaveRemoteOffer);
+        assert!(state.can_create_answer());
+        let state = state.next(Local, Pranswer)?;
+        assert_eq!(state, HaveLocalPranswer);
+        assert_eq!(state.next(Local, Answer)?, Stable);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reject_out_of_order_description() {
+        let err = Stable.next(Remote, Answer).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<MiniWebrtcRsError>(),
+            Some(MiniWebrtcRsError::InvalidSignalingStateError { .. })
+        ));
+        assert!(HaveLocalOffer.next(Remote, Offer).is_err());
+        assert!(HaveRemoteOffer.next(Local, Offer).is_err());
+        assert!(Closed.next(Local, Offer).is_err());
+    }
+
+    #[test]
+    fn test_rollback() -> Result<()> {
+        assert_eq!(HaveLocalOffer.rollback()?, Stable);
+        assert_eq!(HaveRemoteOffer.rollback()?, Stable);
+        assert!(Stable.rollback().is_err());
+        assert!(HaveLocalPranswer.rollback().is_err());
+        Ok(())
+    }
+}
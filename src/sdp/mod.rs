@@ -0,0 +1,185 @@
+pub mod session_description;
+
+use std::fmt;
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sdp::session_description::SdpFingerprint;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SdpMessage {
+    pub session_id: String,
+    pub medias: Vec<SdpMedia>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SdpMedia {
+    pub media_id: String,
+    pub media_type: MediaType,
+    pub stream_id: String,
+    pub track_id: String,
+    pub direction: MediaDirection,
+    pub ufrag: String,
+    pub pwd: String,
+    pub fingerprint_type: FingerprintType,
+    pub fingerprint_hash: String,
+    /// Fingerprints of the other certificates, signaled as further `a=fingerprint` lines.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_fingerprints: Vec<SdpFingerprint>,
+    pub setup: Option<Setup>,
+    pub candidates: Vec<SdpMediaCandidate>,
+    pub end_of_candidates: Option<String>,
+    /// `a=ice-options`, e.g. `trickle`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ice_options: Vec<String>,
+    pub payloads: String,
+    pub rtp: Vec<Rtp>,
+
+    pub rtcp_mux: Option<String>,
+    pub protocol: String,
+
+    pub sctp_port: Option<u64>,
+    pub max_message_size: Option<u64>,
+}
+
+impl SdpMedia {
+    /// Every `a=fingerprint` of the media.
+    pub fn fingerprints(&self) -> Vec<SdpFingerprint> {
+        let fingerprint = SdpFingerprint {
+            hash_function: self.fingerprint_type,
+            fingerprint: self.fingerprint_hash.clone(),
+        };
+        [vec![fingerprint], self.extra_fingerprints.clone()].concat()
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8838#section-4
+    pub fn supports_trickle(&self) -> bool {
+        self.ice_options.iter().any(|option| option == "trickle")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaType {
+    Video,
+    Audio,
+    Application,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaDirection {
+    Sendrecv,
+    Sendonly,
+    Recvonly,
+    Inactive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FingerprintType {
+    #[serde(rename = "sha-1")]
+    Sha1,
+    #[serde(rename = "sha-256")]
+    Sha256,
+    #[serde(rename = "sha-384")]
+    Sha384,
+    #[serde(rename = "sha-512")]
+    Sha512,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc4145#section-4
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Setup {
+    Active,
+    Passive,
+    Actpass,
+    Holdconn,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SdpMediaCandidate {
+    pub foundation: String,
+    pub component: u16,
+    /// Serialized as `ip` like the browser app expects, though it may be an mDNS name.
+    #[serde(rename = "ip")]
+    pub address: CandidateAddress,
+    pub port: u64,
+    pub candidate_type: CandidateType,
+    pub transport_type: TransportType,
+    pub tcp_type: Option<TcpType>,
+    pub priority: u32,
+    pub related_address: Option<IpAddr>,
+    pub related_port: Option<u64>,
+    pub generation: Option<u32>,
+}
+
+/// Connection address of a candidate; browsers hide the ip of host candidates behind a
+/// random mDNS name.
+// https://datatracker.ietf.org/doc/html/draft-ietf-mmusic-mdns-ice-candidates#section-3.1.1
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum CandidateAddress {
+    Ip(IpAddr),
+    Mdns(String),
+}
+
+impl CandidateAddress {
+    /// Returns `None` for names other than `.local` ones.
+    pub fn decode(address: &str) -> Option<Self> {
+        match address.parse::<IpAddr>() {
+            Ok(ip) => Some(Self::Ip(ip)),
+            Err(_) if address.to_ascii_lowercase().ends_with(".local") => {
+                Some(Self::Mdns(address.to_string()))
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for CandidateAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ip(ip) => write!(f, "{ip}"),
+            Self::Mdns(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CandidateType {
+    Host,
+    Srflx,
+    Prflx,
+    Relay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportType {
+    Udp,
+    Tcp,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc6544#section-4.5
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TcpType {
+    Active,
+    Passive,
+    So,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub struct Rtp {
+    pub payload: u32,
+    pub codec: String,
+    pub rate: u32,
+}
@@ -0,0 +1,1438 @@
+use std::net::IpAddr;
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::sdp::{
+    CandidateAddress, CandidateType, FingerprintType, MediaDirection, MediaType, Rtp, SdpMedia,
+    SdpMediaCandidate, SdpMessage, Setup, TcpType, TransportType,
+};
+
+// Text SDP codec.
+// https://datatracker.ietf.org/doc/html/rfc8866
+// https://datatracker.ietf.org/doc/html/rfc8829 (JSEP)
+
+const LINE_SEPARATOR: &str = "\r\n";
+const DISCARD_PORT: u16 = 9;
+const BUNDLE_SEMANTICS: &str = "BUNDLE";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionDescription {
+    pub version: u8,
+    pub origin: Origin,
+    pub session_name: String,
+    pub session_information: Option<String>,
+    pub connection: Option<ConnectionData>,
+    pub bandwidths: Vec<Bandwidth>,
+    pub timing: Timing,
+    pub attributes: Vec<SdpAttribute>,
+    pub media_descriptions: Vec<MediaDescription>,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8866#section-5.2
+#[derive(Debug, Clone, PartialEq)]
+pub struct Origin {
+    pub username: String,
+    pub session_id: String,
+    pub session_version: u64,
+    pub network_type: String,
+    pub address_type: String,
+    pub unicast_address: String,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8866#section-5.7
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionData {
+    pub network_type: String,
+    pub address_type: String,
+    pub address: String,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8866#section-5.8
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bandwidth {
+    pub bandwidth_type: String,
+    pub bandwidth: u64,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8866#section-5.9
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timing {
+    pub start_time: u64,
+    pub stop_time: u64,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8866#section-5.14
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaDescription {
+    pub media: String,
+    pub port: u16,
+    pub protocol: String,
+    pub formats: Vec<String>,
+    pub connection: Option<ConnectionData>,
+    pub bandwidths: Vec<Bandwidth>,
+    pub attributes: Vec<SdpAttribute>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SdpAttribute {
+    // https://datatracker.ietf.org/doc/html/rfc5888#section-5
+    Group(Group),
+    // https://datatracker.ietf.org/doc/html/draft-ietf-mmusic-msid-05
+    MsidSemantic(MsidSemantic),
+    // https://datatracker.ietf.org/doc/html/rfc8839#section-5.4
+    IceLite,
+    IceUfrag(String),
+    IcePwd(String),
+    IceOptions(Vec<String>),
+    // https://datatracker.ietf.org/doc/html/rfc8122#section-5
+    Fingerprint(SdpFingerprint),
+    // https://datatracker.ietf.org/doc/html/rfc4145#section-4
+    Setup(Setup),
+    // https://datatracker.ietf.org/doc/html/rfc5888#section-4
+    Mid(String),
+    Direction(MediaDirection),
+    // https://datatracker.ietf.org/doc/html/rfc5761#section-5
+    RtcpMux,
+    // https://datatracker.ietf.org/doc/html/rfc5506#section-5
+    RtcpRsize,
+    // https://datatracker.ietf.org/doc/html/rfc8866#section-6.6
+    Rtpmap(Rtpmap),
+    // https://datatracker.ietf.org/doc/html/rfc8866#section-6.15
+    Fmtp(Fmtp),
+    // https://datatracker.ietf.org/doc/html/rfc4585#section-4.2
+    RtcpFb(RtcpFb),
+    // https://datatracker.ietf.org/doc/html/rfc8285#section-7
+    Extmap(Extmap),
+    // https://datatracker.ietf.org/doc/html/rfc5576#section-4.1
+    Ssrc(Ssrc),
+    SsrcGroup(SsrcGroup),
+    // https://datatracker.ietf.org/doc/html/rfc8830#section-2
+    Msid(Msid),
+    // https://datatracker.ietf.org/doc/html/rfc8841#section-5
+    SctpPort(u16),
+    MaxMessageSize(u64),
+    // https://datatracker.ietf.org/doc/html/rfc8839#section-5.1
+    Candidate(SdpCandidate),
+    EndOfCandidates,
+    Other { name: String, value: Option<String> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub semantics: String,
+    pub identification_tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MsidSemantic {
+    pub semantic: String,
+    pub identifiers: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SdpFingerprint {
+    pub hash_function: FingerprintType,
+    pub fingerprint: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rtpmap {
+    pub payload_type: u8,
+    pub encoding_name: String,
+    pub clock_rate: u32,
+    pub encoding_parameters: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fmtp {
+    pub payload_type: u8,
+    pub parameters: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtcpFb {
+    pub payload_type: Option<u8>, // None means wildcard `*`
+    pub feedback_type: String,
+    pub parameter: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extmap {
+    pub id: u16,
+    pub direction: Option<MediaDirection>,
+    pub uri: String,
+    pub extension_attributes: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ssrc {
+    pub ssrc: u32,
+    pub attribute: String,
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SsrcGroup {
+    pub semantics: String,
+    pub ssrcs: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Msid {
+    pub stream_id: String,
+    pub track_id: Option<String>,
+}
+
+// candidate-attribute = "candidate" ":" foundation SP component-id SP transport SP
+//                       priority SP connection-address SP port SP cand-type
+//                       [SP rel-addr] [SP rel-port] *(SP cand-extension)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdpCandidate {
+    pub foundation: String,
+    pub component: u16,
+    pub transport: TransportType,
+    pub priority: u32,
+    pub address: String,
+    pub port: u16,
+    pub candidate_type: String,
+    pub related_address: Option<String>,
+    pub related_port: Option<u16>,
+    pub tcp_type: Option<String>,
+    pub extensions: Vec<(String, String)>,
+}
+
+impl SessionDescription {
+    pub fn decode(sdp: &str) -> Result<Self> {
+        let mut version: Option<u8> = None;
+        let mut origin: Option<Origin> = None;
+        let mut session_name: Option<String> = None;
+        let mut session_information = None;
+        let mut connection = None;
+        let mut bandwidths = vec![];
+        let mut timing: Option<Timing> = None;
+        let mut attributes = vec![];
+        let mut media_descriptions: Vec<MediaDescription> = vec![];
+
+        for (i, line) in sdp.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .filter(|(key, _)| key.len() == 1)
+                .ok_or(anyhow!("invalid sdp line {}; `{line}`", i + 1))?;
+
+            let parsed: Result<()> = (|| {
+                if let Some(media) = media_descriptions.last_mut() {
+                    match key {
+                        "m" => media_descriptions.push(MediaDescription::decode_media_line(value)?),
+                        "c" => media.connection = Some(ConnectionData::decode(value)?),
+                        "b" => media.bandwidths.push(Bandwidth::decode(value)?),
+                        "a" => media.attributes.push(SdpAttribute::decode(value)?),
+                        "i" | "k" => debug!("ignore media level sdp line; {line}"),
+                        _ => anyhow::bail!("unexpected media level key `{key}`"),
+                    }
+                    return Ok(());
+                }
+
+                match key {
+                    "v" => version = Some(value.parse()?),
+                    "o" => origin = Some(Origin::decode(value)?),
+                    "s" => session_name = Some(value.to_string()),
+                    "i" => session_information = Some(value.to_string()),
+                    "c" => connection = Some(ConnectionData::decode(value)?),
+                    "b" => bandwidths.push(Bandwidth::decode(value)?),
+                    "t" => timing = Some(Timing::decode(value)?),
+                    "a" => attributes.push(SdpAttribute::decode(value)?),
+                    "m" => media_descriptions.push(MediaDescription::decode_media_line(value)?),
+                    "u" | "e" | "p" | "r" | "z" | "k" => {
+                        debug!("ignore session level sdp line; {line}")
+                    }
+                    _ => anyhow::bail!("unexpected session level key `{key}`"),
+                }
+                Ok(())
+            })();
+            parsed.with_context(|| format!("invalid sdp line {}; `{line}`", i + 1))?;
+        }
+
+        Ok(Self {
+            version: version.ok_or(anyhow!("sdp version line (v=) not found."))?,
+            origin: origin.ok_or(anyhow!("sdp origin line (o=) not found."))?,
+            session_name: session_name.ok_or(anyhow!("sdp session name line (s=) not found."))?,
+            session_information,
+            connection,
+            bandwidths,
+            timing: timing.ok_or(anyhow!("sdp timing line (t=) not found."))?,
+            attributes,
+            media_descriptions,
+        })
+    }
+
+    pub fn encode(&self) -> String {
+        let mut lines = vec![
+            format!("v={}", self.version),
+            format!("o={}", self.origin.encode()),
+            format!("s={}", self.session_name),
+        ];
+        if let Some(information) = &self.session_information {
+            lines.push(format!("i={information}"));
+        }
+        if let Some(connection) = &self.connection {
+            lines.push(format!("c={}", connection.encode()));
+        }
+        for bandwidth in &self.bandwidths {
+            lines.push(format!("b={}", bandwidth.encode()));
+        }
+        lines.push(format!("t={}", self.timing.encode()));
+        for attribute in &self.attributes {
+            lines.push(format!("a={}", attribute.encode()));
+        }
+        for media in &self.media_descriptions {
+            media.encode_lines(&mut lines);
+        }
+
+        let mut sdp = lines.join(LINE_SEPARATOR);
+        sdp.push_str(LINE_SEPARATOR);
+        sdp
+    }
+
+    /// Finds an attribute at media level first and falls back to session level,
+    /// since some stacks (e.g. Firefox) hoist ice-ufrag/fingerprint to the session.
+    fn find_attribute<'a, T>(
+        &'a self,
+        media: &'a MediaDescription,
+        f: impl Fn(&'a SdpAttribute) -> Option<T>,
+    ) -> Option<T> {
+        media
+            .attributes
+            .iter()
+            .find_map(&f)
+            .or_else(|| self.attributes.iter().find_map(&f))
+    }
+}
+
+impl Origin {
+    fn decode(value: &str) -> Result<Self> {
+        let fields = value.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 6 {
+            anyhow::bail!("origin requires 6 fields; received {}", fields.len());
+        }
+        Ok(Self {
+            username: fields[0].to_string(),
+            session_id: fields[1].to_string(),
+            session_version: fields[2].parse()?,
+            network_type: fields[3].to_string(),
+            address_type: fields[4].to_string(),
+            unicast_address: fields[5].to_string(),
+        })
+    }
+
+    fn encode(&self) -> String {
+        format!(
+            "{} {} {} {} {} {}",
+            self.username,
+            self.session_id,
+            self.session_version,
+            self.network_type,
+            self.address_type,
+            self.unicast_address
+        )
+    }
+}
+
+impl ConnectionData {
+    fn decode(value: &str) -> Result<Self> {
+        let fields = value.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 3 {
+            anyhow::bail!(
+                "connection data requires 3 fields; received {}",
+                fields.len()
+            );
+        }
+        Ok(Self {
+            network_type: fields[0].to_string(),
+            address_type: fields[1].to_string(),
+            address: fields[2].to_string(),
+        })
+    }
+
+    fn encode(&self) -> String {
+        format!(
+            "{} {} {}",
+            self.network_type, self.address_type, self.address
+        )
+    }
+}
+
+impl Bandwidth {
+    fn decode(value: &str) -> Result<Self> {
+        let (bandwidth_type, bandwidth) = value
+            .split_once(':')
+            .ok_or(anyhow!("bandwidth requires `<bwtype>:<bandwidth>`"))?;
+        Ok(Self {
+            bandwidth_type: bandwidth_type.to_string(),
+            bandwidth: bandwidth.parse()?,
+        })
+    }
+
+    fn encode(&self) -> String {
+        format!("{}:{}", self.bandwidth_type, self.bandwidth)
+    }
+}
+
+impl Timing {
+    fn decode(value: &str) -> Result<Self> {
+        let (start_time, stop_time) = value
+            .split_once(' ')
+            .ok_or(anyhow!("timing requires `<start-time> <stop-time>`"))?;
+        Ok(Self {
+            start_time: start_time.parse()?,
+            stop_time: stop_time.parse()?,
+        })
+    }
+
+    fn encode(&self) -> String {
+        format!("{} {}", self.start_time, self.stop_time)
+    }
+}
+
+impl MediaDescription {
+    fn decode_media_line(value: &str) -> Result<Self> {
+        let fields = value.split_whitespace().collect::<Vec<_>>();
+        if fields.len() < 3 {
+            anyhow::bail!(
+                "media line requires at least 3 fields; received {}",
+                fields.len()
+            );
+        }
+        // `<port>/<number of ports>` is not used by WebRTC; keep the base port only.
+        let port = fields[1].split('/').next().unwrap_or_default().parse()?;
+        Ok(Self {
+            media: fields[0].to_string(),
+            port,
+            protocol: fields[2].to_string(),
+            formats: fields[3..].iter().map(|f| f.to_string()).collect(),
+            connection: None,
+            bandwidths: vec![],
+            attributes: vec![],
+        })
+    }
+
+    fn encode_lines(&self, lines: &mut Vec<String>) {
+        let mut media_line = format!("m={} {} {}", self.media, self.port, self.protocol);
+        for format in &self.formats {
+            media_line.push(' ');
+            media_line.push_str(format);
+        }
+        lines.push(media_line);
+        if let Some(connection) = &self.connection {
+            lines.push(format!("c={}", connection.encode()));
+        }
+        for bandwidth in &self.bandwidths {
+            lines.push(format!("b={}", bandwidth.encode()));
+        }
+        for attribute in &self.attributes {
+            lines.push(format!("a={}", attribute.encode()));
+        }
+    }
+
+    pub fn mid(&self) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                SdpAttribute::Mid(mid) => Some(mid.as_str()),
+                _ => None,
+            })
+    }
+}
+
+impl SdpAttribute {
+    pub fn decode(value: &str) -> Result<Self> {
+        let (name, value) = match value.split_once(':') {
+            Some((name, value)) => (name, Some(value)),
+            None => (value, None),
+        };
+        let required = || value.ok_or(anyhow!("attribute `{name}` requires a value"));
+
+        let attribute = match name {
+            "group" => {
+                let mut fields = required()?.split_whitespace();
+                Self::Group(Group {
+                    semantics: fields
+                        .next()
+                        .ok_or(anyhow!("group requires semantics"))?
+                        .to_string(),
+                    identification_tags: fields.map(|f| f.to_string()).collect(),
+                })
+            }
+            "msid-semantic" => {
+                let mut fields = required()?.split_whitespace();
+                Self::MsidSemantic(MsidSemantic {
+                    semantic: fields
+                        .next()
+                        .ok_or(anyhow!("msid-semantic requires semantic"))?
+                        .to_string(),
+                    identifiers: fields.map(|f| f.to_string()).collect(),
+                })
+            }
+            "ice-lite" => Self::IceLite,
+            "ice-ufrag" => Self::IceUfrag(required()?.to_string()),
+            "ice-pwd" => Self::IcePwd(required()?.to_string()),
+            "ice-options" => Self::IceOptions(
+                required()?
+                    .split_whitespace()
+                    .map(|f| f.to_string())
+                    .collect(),
+            ),
+            "fingerprint" => {
+                let (hash_function, fingerprint) = required()?
+                    .split_once(' ')
+                    .ok_or(anyhow!("fingerprint requires `<hash-func> <fingerprint>`"))?;
+                match decode_fingerprint_type(hash_function) {
+                    Some(hash_function) => Self::Fingerprint(SdpFingerprint {
+                        hash_function,
+                        fingerprint: fingerprint.trim().to_string(),
+                    }),
+                    None => Self::Other {
+                        name: name.to_string(),
+                        value: value.map(|v| v.to_string()),
+                    },
+                }
+            }
+            "setup" => Self::Setup(decode_setup(required()?)?),
+            "mid" => Self::Mid(required()?.to_string()),
+            "sendrecv" => Self::Direction(MediaDirection::Sendrecv),
+            "sendonly" => Self::Direction(MediaDirection::Sendonly),
+            "recvonly" => Self::Direction(MediaDirection::Recvonly),
+            "inactive" => Self::Direction(MediaDirection::Inactive),
+            "rtcp-mux" => Self::RtcpMux,
+            "rtcp-rsize" => Self::RtcpRsize,
+            "rtpmap" => Self::Rtpmap(Rtpmap::decode(required()?)?),
+            "fmtp" => {
+                let (payload_type, parameters) = required()?
+                    .split_once(' ')
+                    .ok_or(anyhow!("fmtp requires `<format> <parameters>`"))?;
+                Self::Fmtp(Fmtp {
+                    payload_type: payload_type.parse()?,
+                    parameters: parameters.to_string(),
+                })
+            }
+            "rtcp-fb" => {
+                let mut fields = required()?.splitn(3, ' ');
+                let payload_type = match fields.next() {
+                    Some("*") => None,
+                    Some(payload_type) => Some(payload_type.parse()?),
+                    None => anyhow::bail!("rtcp-fb requires a format"),
+                };
+                Self::RtcpFb(RtcpFb {
+                    payload_type,
+                    feedback_type: fields
+                        .next()
+                        .ok_or(anyhow!("rtcp-fb requires a feedback type"))?
+                        .to_string(),
+                    parameter: fields.next().map(|f| f.to_string()),
+                })
+            }
+            "extmap" => Self::Extmap(Extmap::decode(required()?)?),
+            "ssrc" => {
+                let (ssrc, attribute) = required()?
+                    .split_once(' ')
+                    .ok_or(anyhow!("ssrc requires `<ssrc-id> <attribute>`"))?;
+                let (attribute, value) = match attribute.split_once(':') {
+                    Some((attribute, value)) => (attribute, Some(value.to_string())),
+                    None => (attribute, None),
+                };
+                Self::Ssrc(Ssrc {
+                    ssrc: ssrc.parse()?,
+                    attribute: attribute.to_string(),
+                    value,
+                })
+            }
+            "ssrc-group" => {
+                let mut fields = required()?.split_whitespace();
+                Self::SsrcGroup(SsrcGroup {
+                    semantics: fields
+                        .next()
+                        .ok_or(anyhow!("ssrc-group requires semantics"))?
+                        .to_string(),
+                    ssrcs: fields.map(|f| f.parse()).collect::<Result<_, _>>()?,
+                })
+            }
+            "msid" => {
+                let mut fields = required()?.split_whitespace();
+                Self::Msid(Msid {
+                    stream_id: fields
+                        .next()
+                        .ok_or(anyhow!("msid requires a stream id"))?
+                        .to_string(),
+                    track_id: fields.next().map(|f| f.to_string()),
+                })
+            }
+            "sctp-port" => Self::SctpPort(required()?.trim().parse()?),
+            "max-message-size" => Self::MaxMessageSize(required()?.trim().parse()?),
+            "candidate" => Self::Candidate(SdpCandidate::decode(required()?)?),
+            "end-of-candidates" => Self::EndOfCandidates,
+            _ => Self::Other {
+                name: name.to_string(),
+                value: value.map(|v| v.to_string()),
+            },
+        };
+        Ok(attribute)
+    }
+
+    pub fn encode(&self) -> String {
+        match self {
+            Self::Group(group) => {
+                let mut value = format!("group:{}", group.semantics);
+                for tag in &group.identification_tags {
+                    value.push(' ');
+                    value.push_str(tag);
+                }
+                value
+            }
+            Self::MsidSemantic(msid_semantic) => {
+                let mut value = format!("msid-semantic:{}", msid_semantic.semantic);
+                for identifier in &msid_semantic.identifiers {
+                    value.push(' ');
+                    value.push_str(identifier);
+                }
+                value
+            }
+            Self::IceLite => "ice-lite".to_string(),
+            Self::IceUfrag(ufrag) => format!("ice-ufrag:{ufrag}"),
+            Self::IcePwd(pwd) => format!("ice-pwd:{pwd}"),
+            Self::IceOptions(options) => format!("ice-options:{}", options.join(" ")),
+            Self::Fingerprint(fingerprint) => format!(
+                "fingerprint:{} {}",
+                encode_fingerprint_type(fingerprint.hash_function),
+                fingerprint.fingerprint
+            ),
+            Self::Setup(setup) => format!("setup:{}", encode_setup(*setup)),
+            Self::Mid(mid) => format!("mid:{mid}"),
+            Self::Direction(direction) => encode_direction(*direction).to_string(),
+            Self::RtcpMux => "rtcp-mux".to_string(),
+            Self::RtcpRsize => "rtcp-rsize".to_string(),
+            Self::Rtpmap(rtpmap) => format!("rtpmap:{}", rtpmap.encode()),
+            Self::Fmtp(fmtp) => format!("fmtp:{} {}", fmtp.payload_type, fmtp.parameters),
+            Self::RtcpFb(rtcp_fb) => {
+                let payload_type = rtcp_fb
+                    .payload_type
+                    .map(|pt| pt.to_string())
+                    .unwrap_or("*".to_string());
+                match &rtcp_fb.parameter {
+                    Some(parameter) => format!(
+                        "rtcp-fb:{payload_type} {} {parameter}",
+                        rtcp_fb.feedback_type
+                    ),
+                    None => format!("rtcp-fb:{payload_type} {}", rtcp_fb.feedback_type),
+                }
+            }
+            Self::Extmap(extmap) => format!("extmap:{}", extmap.encode()),
+            Self::Ssrc(ssrc) => match &ssrc.value {
+                Some(value) => format!("ssrc:{} {}:{value}", ssrc.ssrc, ssrc.attribute),
+                None => format!("ssrc:{} {}", ssrc.ssrc, ssrc.attribute),
+            },
+            Self::SsrcGroup(ssrc_group) => {
+                let mut value = format!("ssrc-group:{}", ssrc_group.semantics);
+                for ssrc in &ssrc_group.ssrcs {
+                    value.push_str(&format!(" {ssrc}"));
+                }
+                value
+            }
+            Self::Msid(msid) => match &msid.track_id {
+                Some(track_id) => format!("msid:{} {track_id}", msid.stream_id),
+                None => format!("msid:{}", msid.stream_id),
+            },
+            Self::SctpPort(port) => format!("sctp-port:{port}"),
+            Self::MaxMessageSize(size) => format!("max-message-size:{size}"),
+            Self::Candidate(candidate) => format!("candidate:{}", candidate.encode()),
+            Self::EndOfCandidates => "end-of-candidates".to_string(),
+            Self::Other { name, value } => match value {
+                Some(value) => format!("{name}:{value}"),
+                None => name.clone(),
+            },
+        }
+    }
+}
+
+impl Rtpmap {
+    fn decode(value: &str) -> Result<Self> {
+        let (payload_type, encoding) = value
+            .split_once(' ')
+            .ok_or(anyhow!("rtpmap requires `<payload type> <encoding>`"))?;
+        let mut encoding = encoding.trim().split('/');
+        Ok(Self {
+            payload_type: payload_type.parse()?,
+            encoding_name: encoding
+                .next()
+                .ok_or(anyhow!("rtpmap requires an encoding name"))?
+                .to_string(),
+            clock_rate: encoding
+                .next()
+                .ok_or(anyhow!("rtpmap requires a clock rate"))?
+                .parse()?,
+            encoding_parameters: encoding.next().map(|p| p.parse()).transpose()?,
+        })
+    }
+
+    fn encode(&self) -> String {
+        match self.encoding_parameters {
+            Some(parameters) => format!(
+                "{} {}/{}/{parameters}",
+                self.payload_type, self.encoding_name, self.clock_rate
+            ),
+            None => format!(
+                "{} {}/{}",
+                self.payload_type, self.encoding_name, self.clock_rate
+            ),
+        }
+    }
+}
+
+impl Extmap {
+    fn decode(value: &str) -> Result<Self> {
+        let mut fields = value.splitn(3, ' ');
+        let (id, direction) = match fields
+            .next()
+            .ok_or(anyhow!("extmap requires an id"))?
+            .split_once('/')
+        {
+            Some((id, direction)) => (id, Some(decode_direction(direction)?)),
+            None => (value.split(' ').next().unwrap_or_default(), None),
+        };
+        Ok(Self {
+            id: id.parse()?,
+            direction,
+            uri: fields
+                .next()
+                .ok_or(anyhow!("extmap requires an uri"))?
+                .to_string(),
+            extension_attributes: fields.next().map(|f| f.to_string()),
+        })
+    }
+
+    fn encode(&self) -> String {
+        let mut value = self.id.to_string();
+        if let Some(direction) = self.direction {
+            value.push('/');
+            value.push_str(encode_direction(direction));
+        }
+        value.push(' ');
+        value.push_str(&self.uri);
+        if let Some(extension_attributes) = &self.extension_attributes {
+            value.push(' ');
+            value.push_str(extension_attributes);
+        }
+        value
+    }
+}
+
+impl SdpCandidate {
+    pub fn decode(value: &str) -> Result<Self> {
+        let fields = value.split_whitespace().collect::<Vec<_>>();
+        if fields.len() < 8 || fields[6] != "typ" {
+            anyhow::bail!(
+                "candidate requires `<foundation> <component> <transport> <priority> <address> <port> typ <type>`"
+            );
+        }
+
+        let mut candidate = Self {
+            foundation: fields[0].to_string(),
+            component: fields[1].parse()?,
+            transport: decode_transport_type(fields[2])?,
+            priority: fields[3].parse()?,
+            address: fields[4].to_string(),
+            port: fields[5].parse()?,
+            candidate_type: fields[7].to_string(),
+            related_address: None,
+            related_port: None,
+            tcp_type: None,
+            extensions: vec![],
+        };
+
+        let mut rest = fields[8..].chunks(2);
+        for pair in rest.by_ref() {
+            let [name, value] = pair else {
+                anyhow::bail!("candidate extension `{}` has no value", pair[0]);
+            };
+            match *name {
+                "raddr" => candidate.related_address = Some(value.to_string()),
+                "rport" => candidate.related_port = Some(value.parse()?),
+                "tcptype" => candidate.tcp_type = Some(value.to_string()),
+                _ => candidate
+                    .extensions
+                    .push((name.to_string(), value.to_string())),
+            }
+        }
+
+        Ok(candidate)
+    }
+
+    pub fn encode(&self) -> String {
+        let mut value = format!(
+            "{} {} {} {} {} {} typ {}",
+            self.foundation,
+            self.component,
+            encode_transport_type(self.transport),
+            self.priority,
+            self.address,
+            self.port,
+            self.candidate_type
+        );
+        if let Some(related_address) = &self.related_address {
+            value.push_str(&format!(" raddr {related_address}"));
+        }
+        if let Some(related_port) = self.related_port {
+            value.push_str(&format!(" rport {related_port}"));
+        }
+        if let Some(tcp_type) = &self.tcp_type {
+            value.push_str(&format!(" tcptype {tcp_type}"));
+        }
+        for (name, extension) in &self.extensions {
+            value.push_str(&format!(" {name} {extension}"));
+        }
+        value
+    }
+}
+
+fn decode_fingerprint_type(value: &str) -> Option<FingerprintType> {
+    match value.to_ascii_lowercase().as_str() {
+        "sha-1" => Some(FingerprintType::Sha1),
+        "sha-256" => Some(FingerprintType::Sha256),
+        "sha-384" => Some(FingerprintType::Sha384),
+        "sha-512" => Some(FingerprintType::Sha512),
+        _ => None,
+    }
+}
+
+fn encode_fingerprint_type(value: FingerprintType) -> &'static str {
+    match value {
+        FingerprintType::Sha1 => "sha-1",
+        FingerprintType::Sha256 => "sha-256",
+        FingerprintType::Sha384 => "sha-384",
+        FingerprintType::Sha512 => "sha-512",
+    }
+}
+
+fn decode_setup(value: &str) -> Result<Setup> {
+    match value.trim() {
+        "active" => Ok(Setup::Active),
+        "passive" => Ok(Setup::Passive),
+        "actpass" => Ok(Setup::Actpass),
+        "holdconn" => Ok(Setup::Holdconn),
+        _ => Err(anyhow!("invalid setup `{value}`")),
+    }
+}
+
+fn encode_setup(value: Setup) -> &'static str {
+    match value {
+        Setup::Active => "active",
+        Setup::Passive => "passive",
+        Setup::Actpass => "actpass",
+        Setup::Holdconn => "holdconn",
+    }
+}
+
+fn decode_direction(value: &str) -> Result<MediaDirection> {
+    match value {
+        "sendrecv" => Ok(MediaDirection::Sendrecv),
+        "sendonly" => Ok(MediaDirection::Sendonly),
+        "recvonly" => Ok(MediaDirection::Recvonly),
+        "inactive" => Ok(MediaDirection::Inactive),
+        _ => Err(anyhow!("invalid direction `{value}`")),
+    }
+}
+
+fn encode_direction(value: MediaDirection) -> &'static str {
+    match value {
+        MediaDirection::Sendrecv => "sendrecv",
+        MediaDirection::Sendonly => "sendonly",
+        MediaDirection::Recvonly => "recvonly",
+        MediaDirection::Inactive => "inactive",
+    }
+}
+
+fn decode_transport_type(value: &str) -> Result<TransportType> {
+    match value.to_ascii_lowercase().as_str() {
+        "udp" => Ok(TransportType::Udp),
+        "tcp" => Ok(TransportType::Tcp),
+        _ => Err(anyhow!("invalid candidate transport `{value}`")),
+    }
+}
+
+fn encode_transport_type(value: TransportType) -> &'static str {
+    match value {
+        TransportType::Udp => "udp",
+        TransportType::Tcp => "tcp",
+    }
+}
+
+pub fn decode_tcp_type(value: &str) -> Result<TcpType> {
+    match value {
+        "active" => Ok(TcpType::Active),
+        "passive" => Ok(TcpType::Passive),
+        "so" => Ok(TcpType::So),
+        _ => Err(anyhow!("invalid tcptype `{value}`")),
+    }
+}
+
+pub fn encode_tcp_type(value: TcpType) -> &'static str {
+    match value {
+        TcpType::Active => "active",
+        TcpType::Passive => "passive",
+        TcpType::So => "so",
+    }
+}
+
+pub fn decode_candidate_type(value: &str) -> Result<CandidateType> {
+    match value {
+        "host" => Ok(CandidateType::Host),
+        "srflx" => Ok(CandidateType::Srflx),
+        "prflx" => Ok(CandidateType::Prflx),
+        "relay" => Ok(CandidateType::Relay),
+        _ => Err(anyhow!("invalid candidate type `{value}`")),
+    }
+}
+
+pub fn encode_candidate_type(value: CandidateType) -> &'static str {
+    match value {
+        CandidateType::Host => "host",
+        CandidateType::Srflx => "srflx",
+        CandidateType::Prflx => "prflx",
+        CandidateType::Relay => "relay",
+    }
+}
+
+fn decode_media_type(value: &str) -> Result<MediaType> {
+    match value {
+        "video" => Ok(MediaType::Video),
+        "audio" => Ok(MediaType::Audio),
+        "application" => Ok(MediaType::Application),
+        _ => Err(anyhow!("unsupported media type `{value}`")),
+    }
+}
+
+fn encode_media_type(value: MediaType) -> &'static str {
+    match value {
+        MediaType::Video => "video",
+        MediaType::Audio => "audio",
+        MediaType::Application => "application",
+    }
+}
+
+impl TryFrom<&SdpMessage> for SessionDescription {
+    type Error = anyhow::Error;
+
+    fn try_from(message: &SdpMessage) -> Result<Self> {
+        let mids = message
+            .medias
+            .iter()
+            .map(|media| media.media_id.clone())
+            .collect::<Vec<_>>();
+
+        let media_descriptions = message
+            .medias
+            .iter()
+            .map(|media| {
+                let media_id = &media.media_id;
+                let mut attributes = vec![
+                    SdpAttribute::IceUfrag(media.ufrag.clone()),
+                    SdpAttribute::IcePwd(media.pwd.clone()),
+                ];
+                if !media.ice_options.is_empty() {
+                    attributes.push(SdpAttribute::IceOptions(media.ice_options.clone()));
+                }
+                // https://datatracker.ietf.org/doc/html/rfc8122#section-5
+                attributes.extend(
+                    media
+                        .fingerprints()
+                        .into_iter()
+                        .map(SdpAttribute::Fingerprint),
+                );
+                attributes.push(SdpAttribute::Setup(media.setup.unwrap_or(Setup::Actpass)));
+                attributes.push(SdpAttribute::Mid(media.media_id.clone()));
+                if media.media_type != MediaType::Application {
+                    attributes.push(SdpAttribute::Direction(media.direction));
+                    attributes.push(SdpAttribute::Msid(Msid {
+                        stream_id: media.stream_id.clone(),
+                        track_id: Some(media.track_id.clone()),
+                    }));
+                }
+                if media.rtcp_mux.is_some() {
+                    attributes.push(SdpAttribute::RtcpMux);
+                }
+                for rtp in &media.rtp {
+                    attributes.push(SdpAttribute::Rtpmap(Rtpmap {
+                        payload_type: u8::try_from(rtp.payload).map_err(|_| {
+                            anyhow!(
+                                "rtp payload type out of range; mid={media_id}, payload={}",
+                                rtp.payload
+                            )
+                        })?,
+                        encoding_name: rtp.codec.clone(),
+                        clock_rate: rtp.rate,
+                        encoding_parameters: None,
+                    }));
+                }
+                if let Some(sctp_port) = media.sctp_port {
+                    let sctp_port = u16::try_from(sctp_port).map_err(|_| {
+                        anyhow!("sctp-port out of range; mid={media_id}, port={sctp_port}")
+                    })?;
+                    attributes.push(SdpAttribute::SctpPort(sctp_port));
+                }
+                if let Some(max_message_size) = media.max_message_size {
+                    attributes.push(SdpAttribute::MaxMessageSize(max_message_size));
+                }
+                for candidate in &media.candidates {
+                    let port = u16::try_from(candidate.port).map_err(|_| {
+                        anyhow!(
+                            "candidate port out of range; mid={media_id}, port={}",
+                            candidate.port
+                        )
+                    })?;
+                    let related_port = candidate
+                        .related_port
+                        .map(u16::try_from)
+                        .transpose()
+                        .map_err(|_| {
+                            anyhow!(
+                                "candidate related port out of range; mid={media_id}, port={:?}",
+                                candidate.related_port
+                            )
+                        })?;
+                    attributes.push(SdpAttribute::Candidate(SdpCandidate {
+                        foundation: candidate.foundation.clone(),
+                        component: candidate.component,
+                        transport: candidate.transport_type,
+                        priority: candidate.priority,
+                        address: candidate.address.to_string(),
+                        port,
+                        candidate_type: encode_candidate_type(candidate.candidate_type).to_string(),
+                        related_address: candidate.related_address.map(|ip| ip.to_string()),
+                        related_port,
+                        tcp_type: candidate
+                            .tcp_type
+                            .map(|tcp_type| encode_tcp_type(tcp_type).to_string()),
+                        extensions: candidate
+                            .generation
+                            .map(|generation| {
+                                vec![("generation".to_string(), generation.to_string())]
+                            })
+                            .unwrap_or_default(),
+                    }));
+                }
+
+                if media.end_of_candidates.is_some() {
+                    attributes.push(SdpAttribute::EndOfCandidates);
+                }
+
+                Ok(MediaDescription {
+                    media: encode_media_type(media.media_type).to_string(),
+                    port: DISCARD_PORT,
+                    protocol: media.protocol.clone(),
+                    formats: media
+                        .payloads
+                        .split_whitespace()
+                        .map(|f| f.to_string())
+                        .collect(),
+                    connection: Some(ConnectionData {
+                        network_type: "IN".to_string(),
+                        address_type: "IP4".to_string(),
+                        address: "0.0.0.0".to_string(),
+                    }),
+                    bandwidths: vec![],
+                    attributes,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            version: 0,
+            origin: Origin {
+                username: "-".to_string(),
+                session_id: message.session_id.clone(),
+                session_version: 2,
+                network_type: "IN".to_string(),
+                address_type: "IP4".to_string(),
+                unicast_address: "127.0.0.1".to_string(),
+            },
+            session_name: "-".to_string(),
+            session_information: None,
+            connection: None,
+            bandwidths: vec![],
+            timing: Timing {
+                start_time: 0,
+                stop_time: 0,
+            },
+            attributes: vec![SdpAttribute::Group(Group {
+                semantics: BUNDLE_SEMANTICS.to_string(),
+                identification_tags: mids,
+            })],
+            media_descriptions,
+        })
+    }
+}
+
+impl TryFrom<&SessionDescription> for SdpMessage {
+    type Error = anyhow::Error;
+
+    fn try_from(description: &SessionDescription) -> Result<Self> {
+        let mut medias = vec![];
+        for (i, media) in description.media_descriptions.iter().enumerate() {
+            let media_type = decode_media_type(&media.media)?;
+            let media_id = media
+                .mid()
+                .map(|mid| mid.to_string())
+                .unwrap_or(i.to_string());
+            // media-level fingerprints replace the session-level ones.
+            let fingerprints = [&media.attributes, &description.attributes]
+                .into_iter()
+                .map(|attributes| {
+                    attributes
+                        .iter()
+                        .filter_map(|attribute| match attribute {
+                            SdpAttribute::Fingerprint(fingerprint) => Some(fingerprint.clone()),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .find(|fingerprints| !fingerprints.is_empty())
+                .unwrap_or_default();
+            let (fingerprint, extra_fingerprints) = fingerprints
+                .split_first()
+                .ok_or(anyhow!("fingerprint not found; mid={media_id}"))?;
+            let (stream_id, track_id) = media
+                .attributes
+                .iter()
+                .find_map(|attribute| match attribute {
+                    SdpAttribute::Msid(msid) => Some((
+                        msid.stream_id.clone(),
+                        msid.track_id.clone().unwrap_or_default(),
+                    )),
+                    _ => None,
+                })
+                .unwrap_or_default();
+
+            let candidates = media
+                .attributes
+                .iter()
+                .filter_map(|attribute| match attribute {
+                    SdpAttribute::Candidate(candidate) => Some(candidate),
+                    _ => None,
+                })
+                .map(|candidate| {
+                    Ok(SdpMediaCandidate {
+                        foundation: candidate.foundation.clone(),
+                        component: candidate.component,
+                        address: CandidateAddress::decode(&candidate.address).ok_or(anyhow!(
+                            "invalid candidate address; mid={media_id}, address={}",
+                            candidate.address
+                        ))?,
+                        port: candidate.port as u64,
+                        candidate_type: decode_candidate_type(&candidate.candidate_type)?,
+                        transport_type: candidate.transport,
+                        tcp_type: candidate
+                            .tcp_type
+                            .as_deref()
+                            .map(decode_tcp_type)
+                            .transpose()?,
+                        priority: candidate.priority,
+                        related_address: candidate
+                            .related_address
+                            .as_ref()
+                            .map(|address| address.parse::<IpAddr>())
+                            .transpose()?,
+                        related_port: candidate.related_port.map(|port| port as u64),
+                        generation: candidate
+                            .extensions
+                            .iter()
+                            .find(|(name, _)| name == "generation")
+                            .and_then(|(_, generation)| generation.parse().ok()),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            medias.push(SdpMedia {
+                media_id: media_id.clone(),
+                media_type,
+                stream_id,
+                track_id,
+                direction: media
+                    .attributes
+                    .iter()
+                    .find_map(|attribute| match attribute {
+                        SdpAttribute::Direction(direction) => Some(*direction),
+                        _ => None,
+                    })
+                    .unwrap_or(MediaDirection::Sendrecv),
+                ufrag: description
+                    .find_attribute(media, |attribute| match attribute {
+                        SdpAttribute::IceUfrag(ufrag) => Some(ufrag.clone()),
+                        _ => None,
+                    })
+                    .ok_or(anyhow!("ice-ufrag not found; mid={media_id}"))?,
+                pwd: description
+                    .find_attribute(media, |attribute| match attribute {
+                        SdpAttribute::IcePwd(pwd) => Some(pwd.clone()),
+                        _ => None,
+                    })
+                    .ok_or(anyhow!("ice-pwd not found; mid={media_id}"))?,
+                fingerprint_type: fingerprint.hash_function,
+                fingerprint_hash: fingerprint.fingerprint.clone(),
+                extra_fingerprints: extra_fingerprints.to_vec(),
+                setup: description.find_attribute(media, |attribute| match attribute {
+                    SdpAttribute::Setup(setup) => Some(*setup),
+                    _ => None,
+                }),
+                candidates,
+                end_of_candidates: description.find_attribute(media, |attribute| match attribute {
+                    SdpAttribute::EndOfCandidates => Some("end-of-candidates".to_string()),
+                    _ => None,
+                }),
+                ice_options: description
+                    .find_attribute(media, |attribute| match attribute {
+                        SdpAttribute::IceOptions(options) => Some(options.clone()),
+                        _ => None,
+                    })
+                    .unwrap_or_default(),
+                payloads: media.formats.join(" "),
+                rtp: media
+                    .attributes
+                    .iter()
+                    .filter_map(|attribute| match attribute {
+                        SdpAttribute::Rtpmap(rtpmap) => Some(Rtp {
+                            payload: rtpmap.payload_type as u32,
+                            codec: rtpmap.encoding_name.clone(),
+                            rate: rtpmap.clock_rate,
+                        }),
+                        _ => None,
+                    })
+                    .collect(),
+                rtcp_mux: media
+                    .attributes
+                    .contains(&SdpAttribute::RtcpMux)
+                    .then(|| "rtcp-mux".to_string()),
+                protocol: media.protocol.clone(),
+                sctp_port: media
+                    .attributes
+                    .iter()
+                    .find_map(|attribute| match attribute {
+                        SdpAttribute::SctpPort(port) => Some(*port as u64),
+                        _ => None,
+                    }),
+                max_message_size: media
+                    .attributes
+                    .iter()
+                    .find_map(|attribute| match attribute {
+                        SdpAttribute::MaxMessageSize(size) => Some(*size),
+                        _ => None,
+                    }),
+            });
+        }
+
+        Ok(Self {
+            session_id: description.origin.session_id.clone(),
+            medias,
+        })
+    }
+}
+
+#[cfg(test)]
+mod session_description_tests {
+    use super::*;
+
+    // Trimmed answer produced by Chrome's RTCPeerConnection.createAnswer().
+    const CHROME_ANSWER: &str = "v=0\r\n\
+o=- 4611731400430051336 2 IN IP4 127.0.0.1\r\n\
+s=-\r\n\
+t=0 0\r\n\
+a=group:BUNDLE 0 1\r\n\
+a=extmap-allow-mixed\r\n\
+a=msid-semantic: WMS\r\n\
+m=video 9 UDP/TLS/RTP/SAVPF 96 97\r\n\
+c=IN IP4 0.0.0.0\r\n\
+a=rtcp:9 IN IP4 0.0.0.0\r\n\
+a=candidate:1467250027 1 udp 2122260223 192.168.1.10 54400 typ host generation 0 network-id 1\r\n\
+a=candidate:2999745851 1 udp 1686052607 203.0.113.7 54400 typ srflx raddr 192.168.1.10 rport 54400 generation 0\r\n\
+a=candidate:3412421386 1 udp 2122262783 a6b3c1d2-0000-4d3e-9f0a-1234567890ab.local 54401 typ host generation 0\r\n\
+a=ice-ufrag:EsAw\r\n\
+a=ice-pwd:bP+XJMM09aR8AiX1jdukzR6Y\r\n\
+a=ice-options:trickle\r\n\
+a=fingerprint:sha-256 D2:B9:31:8F:DF:24:D8:0E:ED:D2:EF:25:9E:AF:6F:B8:34:AE:53:9C:E6:F3:8F:F2:64:15:FA:E8:7F:53:2D:38\r\n\
+a=setup:active\r\n\
+a=mid:0\r\n\
+a=extmap:1 urn:ietf:params:rtp-hdrext:toffset\r\n\
+a=extmap:3/sendonly http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time\r\n\
+a=sendonly\r\n\
+a=msid:stream0 7a3e5c1b-track\r\n\
+a=rtcp-mux\r\n\
+a=rtcp-rsize\r\n\
+a=rtpmap:96 VP8/90000\r\n\
+a=rtcp-fb:96 goog-remb\r\n\
+a=rtcp-fb:96 nack pli\r\n\
+a=rtpmap:97 rtx/90000\r\n\
+a=fmtp:97 apt=96\r\n\
+a=ssrc-group:FID 1001 1002\r\n\
+a=ssrc:1001 cname:abcd\r\n\
+a=ssrc:1001 msid:stream0 7a3e5c1b-track\r\n\
+a=ssrc:1002 cname:abcd\r\n\
+m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n\
+c=IN IP4 0.0.0.0\r\n\
+a=ice-ufrag:EsAw\r\n\
+a=ice-pwd:bP+XJMM09aR8AiX1jdukzR6Y\r\n\
+a=ice-options:trickle\r\n\
+a=fingerprint:sha-256 D2:B9:31:8F:DF:24:D8:0E:ED:D2:EF:25:9E:AF:6F:B8:34:AE:53:9C:E6:F3:8F:F2:64:15:FA:E8:7F:53:2D:38\r\n\
+a=setup:active\r\n\
+a=mid:1\r\n\
+a=sctp-port:5000\r\n\
+a=max-message-size:262144\r\n";
+
+    #[test]
+    fn test_decode_chrome_answer() -> Result<()> {
+        let description = SessionDescription::decode(CHROME_ANSWER)?;
+        assert_eq!(description.origin.session_id, "4611731400430051336");
+        assert_eq!(description.media_descriptions.len(), 2);
+
+        let video = &description.media_descriptions[0];
+        assert_eq!(video.formats, vec!["96", "97"]);
+        assert!(video.attributes.contains(&SdpAttribute::Rtpmap(Rtpmap {
+            payload_type: 96,
+            encoding_name: "VP8".to_string(),
+            clock_rate: 90000,
+            encoding_parameters: None,
+        })));
+        assert!(video.attributes.contains(&SdpAttribute::RtcpFb(RtcpFb {
+            payload_type: Some(96),
+            feedback_type: "nack".to_string(),
+            parameter: Some("pli".to_string()),
+        })));
+        assert!(video.attributes.contains(&SdpAttribute::Extmap(Extmap {
+            id: 3,
+            direction: Some(MediaDirection::Sendonly),
+            uri: "http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time".to_string(),
+            extension_attributes: None,
+        })));
+        assert!(
+            video
+                .attributes
+                .contains(&SdpAttribute::SsrcGroup(SsrcGroup {
+                    semantics: "FID".to_string(),
+                    ssrcs: vec![1001, 1002],
+                }))
+        );
+
+        let srflx = video
+            .attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                SdpAttribute::Candidate(candidate) if candidate.candidate_type == "srflx" => {
+                    Some(candidate)
+                }
+                _ => None,
+            })
+            .ok_or(anyhow!("srflx candidate not found"))?;
+        assert_eq!(srflx.related_address.as_deref(), Some("192.168.1.10"));
+        assert_eq!(srflx.related_port, Some(54400));
+        assert_eq!(
+            srflx.extensions,
+            vec![("generation".to_string(), "0".to_string())]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let description = SessionDescription::decode(CHROME_ANSWER)?;
+        let encoded = description.encode();
+        assert_eq!(SessionDescription::decode(&encoded)?, description);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_to_sdp_message() -> Result<()> {
+        let description = SessionDescription::decode(CHROME_ANSWER)?;
+        let message = SdpMessage::try_from(&description)?;
+
+        assert_eq!(message.medias.len(), 2);
+        let video = &message.medias[0];
+        assert_eq!(video.media_type, MediaType::Video);
+        assert_eq!(video.ufrag, "EsAw");
+        assert_eq!(video.pwd, "bP+XJMM09aR8AiX1jdukzR6Y");
+        assert_eq!(video.setup, Some(Setup::Active));
+        assert_eq!(video.direction, MediaDirection::Sendonly);
+        assert_eq!(video.stream_id, "stream0");
+        assert_eq!(video.track_id, "7a3e5c1b-track");
+        assert_eq!(video.rtcp_mux.as_deref(), Some("rtcp-mux"));
+        assert!(video.supports_trickle());
+        assert_eq!(video.candidates.len(), 3);
+        assert_eq!(video.candidates[0].port, 54400);
+        assert_eq!(video.candidates[0].candidate_type, CandidateType::Host);
+        let srflx = &video.candidates[1];
+        assert_eq!(srflx.candidate_type, CandidateType::Srflx);
+        assert_eq!(srflx.foundation, "2999745851");
+        assert_eq!(srflx.priority, 1686052607);
+        assert_eq!(srflx.related_address, Some("192.168.1.10".parse()?));
+        assert_eq!(srflx.related_port, Some(54400));
+        assert_eq!(srflx.generation, Some(0));
+        assert_eq!(
+            video.candidates[2].address,
+            CandidateAddress::Mdns("a6b3c1d2-0000-4d3e-9f0a-1234567890ab.local".to_string())
+        );
+
+        let application = &message.medias[1];
+        assert_eq!(application.sctp_port, Some(5000));
+        assert_eq!(application.max_message_size, Some(262144));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sdp_message_round_trip() -> Result<()> {
+        let description = SessionDescription::decode(CHROME_ANSWER)?;
+        let message = SdpMessage::try_from(&description)?;
+        let encoded = SessionDescription::try_from(&message)?.encode();
+        let decoded = SdpMessage::try_from(&SessionDescription::decode(&encoded)?)?;
+        assert_eq!(decoded, message);
+
+        // every certificate is signaled as its own `a=fingerprint`
+        let mut message = message;
+        message.medias[0].extra_fingerprints = vec![SdpFingerprint {
+            hash_function: FingerprintType::Sha384,
+            fingerprint: "AB:CD:EF".to_string(),
+        }];
+        let encoded = SessionDescription::try_from(&message)?.encode();
+        assert_eq!(encoded.matches("a=fingerprint:").count(), 3);
+        let decoded = SdpMessage::try_from(&SessionDescription::decode(&encoded)?)?;
+        assert_eq!(decoded, message);
+
+        // the browser app reads and sends the candidate address as `ip`
+        let json = serde_json::to_value(&message)?;
+        assert!(json["medias"][0]["candidates"][0]["ip"].is_string());
+        assert_eq!(serde_json::from_value::<SdpMessage>(json)?, message);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reject_values_out_of_range() -> Result<()> {
+        let message = SdpMessage::try_from(&SessionDescription::decode(CHROME_ANSWER)?)?;
+
+        let mut invalid = message.clone();
+        invalid.medias[0].candidates[0].port = 70000;
+        assert!(SessionDescription::try_from(&invalid).is_err());
+
+        let mut invalid = message.clone();
+        invalid.medias[1].sctp_port = Some(u16::MAX as u64 + 1);
+        assert!(SessionDescription::try_from(&invalid).is_err());
+
+        // a candidate that fails to decode fails the description instead of being dropped
+        let sdp = CHROME_ANSWER.replace("typ srflx", "typ unknown");
+        assert!(SdpMessage::try_from(&SessionDescription::decode(&sdp)?).is_err());
+        let sdp =
+            CHROME_ANSWER.replace("192.168.1.10 54400 typ host", "example.com 54400 typ host");
+        assert!(SdpMessage::try_from(&SessionDescription::decode(&sdp)?).is_err());
+        Ok(())
+    }
+}
@@ -4,7 +4,7 @@ use anyhow::Result;
 use axum::{
     Json, Router,
     extract::State,
-    http::{HeaderValue, Method, StatusCode},
+    http::{HeaderValue, Method, StatusCode, header},
     response::IntoResponse,
     routing::{get, post},
 };
@@ -14,9 +14,12 @@ use tower_http::cors::{Any, CorsLayer};
 use tracing::info;
 
 use crate::{
-    ice::{IceAgent, Peer},
-    internal_event::{EventQueue, InternalEvent::SdpAnswer},
-    sdp::SdpMessage,
+    common::error::MiniWebrtcRsError,
+    rtc_configuration::SignalingOptions,
+    rtc_ice_candidate::RtcIceCandidate,
+    rtc_peer_connection::PeerConnection,
+    rtc_session_description::{RtcSdpType, RtcSessionDescription},
+    sdp::{SdpMessage, session_description::SessionDescription},
 };
 
 pub struct SignalingServer {
@@ -25,8 +28,7 @@ pub struct SignalingServer {
 }
 
 struct AppState {
-    ice_agent: Arc<Mutex<IceAgent>>,
-    internal_event_queue: Arc<Mutex<EventQueue>>,
+    pc: Arc<Mutex<PeerConnection>>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -35,35 +37,37 @@ struct SimpleResponse {
 }
 
 impl SignalingServer {
-    pub async fn new(
-        ice_agent: Arc<Mutex<IceAgent>>,
-        internal_event_queue: Arc<Mutex<EventQueue>>,
-    ) -> Self {
-        let shared_state = Arc::new(AppState {
-            ice_agent,
-            internal_event_queue,
-        });
+    pub async fn new(pc: Arc<Mutex<PeerConnection>>, options: SignalingOptions) -> Result<Self> {
+        let shared_state = Arc::new(AppState { pc });
 
+        let allowed_origins = options
+            .allowed_origins
+            .iter()
+            .map(|origin| HeaderValue::from_str(origin))
+            .collect::<Result<Vec<_>, _>>()?;
         let cors = CorsLayer::new()
-            .allow_origin(HeaderValue::from_static("http://localhost:5173"))
+            .allow_origin(allowed_origins)
             .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
             .allow_headers(Any);
 
         let app = Router::new()
             .route("/", get(handle_get_offer))
             .route("/", post(handle_post_answer))
+            .route("/local-offer", post(handle_post_local_offer))
+            .route("/offer", post(handle_post_offer))
+            .route("/sdp", get(handle_get_sdp_offer))
+            .route("/sdp", post(handle_post_sdp_answer))
+            .route("/sdp/local-offer", post(handle_post_sdp_local_offer))
+            .route("/sdp/offer", post(handle_post_sdp_offer))
+            .route("/candidate", post(handle_post_candidate))
+            .route("/candidates", get(handle_get_candidates))
             .layer(cors)
             .with_state(shared_state);
 
-        let listener = tokio::net::TcpListener::bind("127.0.0.1:3001")
-            .await
-            .unwrap();
-        info!(
-            "signaling server listening on {}",
-            listener.local_addr().unwrap()
-        );
+        let listener = tokio::net::TcpListener::bind(options.bind_address).await?;
+        info!("signaling server listening on {}", listener.local_addr()?);
 
-        Self { app, listener }
+        Ok(Self { app, listener })
     }
 
     pub async fn run(self) -> Result<()> {
@@ -71,14 +75,104 @@ impl SignalingServer {
     }
 }
 
-async fn handle_get_offer(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let ice_agent = state.ice_agent.lock().await;
-    let sdp_offer = ice_agent.generate_sdp_offer();
+type ErrorResponse = (StatusCode, Json<SimpleResponse>);
+
+fn error_response(err: anyhow::Error) -> ErrorResponse {
+    // out-of-order descriptions are a conflict with the current signaling state
+    let status = match err.downcast_ref::<MiniWebrtcRsError>() {
+        Some(MiniWebrtcRsError::InvalidSignalingStateError { .. }) => StatusCode::CONFLICT,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    (
+        status,
+        Json(SimpleResponse {
+            message: format!("{err:#}"),
+        }),
+    )
+}
+
+fn ok_response(message: &str) -> (StatusCode, Json<SimpleResponse>) {
+    (
+        StatusCode::OK,
+        Json(SimpleResponse {
+            message: message.to_string(),
+        }),
+    )
+}
+
+fn decode_sdp(body: &str) -> Result<SdpMessage> {
+    SdpMessage::try_from(&SessionDescription::decode(body)?)
+}
+
+async fn create_local_offer(state: &AppState) -> Result<SdpMessage> {
+    let mut pc = state.pc.lock().await;
+    let offer = pc.create_offer().await?;
+    pc.set_local_description(offer.clone()).await?;
+    Ok(offer.sdp)
+}
+
+// reading the offer must not restart negotiation, so GET only serves one already set
+async fn local_offer(state: &AppState) -> Result<SdpMessage, ErrorResponse> {
+    match state.pc.lock().await.local_description() {
+        Some(description) if description.sdp_type == RtcSdpType::Offer => {
+            Ok(description.sdp.clone())
+        }
+        _ => Err((
+            StatusCode::NOT_FOUND,
+            Json(SimpleResponse {
+                message: "no local offer; create one with POST".to_string(),
+            }),
+        )),
+    }
+}
+
+async fn accept_remote_offer(state: &AppState, offer: SdpMessage) -> Result<SdpMessage> {
+    let mut pc = state.pc.lock().await;
+    pc.set_remote_description(RtcSessionDescription::offer(offer))
+        .await?;
+    let answer = match pc.create_answer().await {
+        Ok(answer) => answer,
+        Err(err) => {
+            pc.rollback()?;
+            return Err(err);
+        }
+    };
+    pc.set_local_description(answer.clone()).await?;
+    Ok(answer.sdp)
+}
+
+async fn accept_remote_answer(state: &AppState, answer: SdpMessage) -> Result<()> {
+    state
+        .pc
+        .lock()
+        .await
+        .set_remote_description(RtcSessionDescription::answer(answer))
+        .await
+}
+
+async fn handle_get_offer(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SdpMessage>, ErrorResponse> {
+    let sdp_offer = local_offer(&state).await?;
     info!(
         "GET / signaling: served offer; medias={}",
         sdp_offer.medias.len()
     );
-    Json(sdp_offer)
+    Ok(Json(sdp_offer))
+}
+
+async fn handle_post_local_offer(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SdpMessage>, ErrorResponse> {
+    let sdp_offer = create_local_offer(&state)
+        .await
+        .inspect_err(|err| info!("POST /local-offer signaling: failed to create offer; {err:#}"))
+        .map_err(error_response)?;
+    info!(
+        "POST /local-offer signaling: created offer; medias={}",
+        sdp_offer.medias.len()
+    );
+    Ok(Json(sdp_offer))
 }
 
 async fn handle_post_answer(
@@ -95,16 +189,134 @@ async fn handle_post_answer(
         answer.medias.len()
     );
 
-    state
-        .internal_event_queue
-        .lock()
+    match accept_remote_answer(&state, answer).await {
+        Ok(()) => ok_response("post answer succeeded."),
+        Err(err) => {
+            info!("POST / signaling: rejected answer; {err:#}");
+            error_response(err)
+        }
+    }
+}
+
+async fn handle_post_offer(
+    State(state): State<Arc<AppState>>,
+    Json(offer): Json<SdpMessage>,
+) -> Result<Json<SdpMessage>, ErrorResponse> {
+    info!(
+        "POST /offer signaling: received offer; session_id={}, medias={}",
+        offer.session_id,
+        offer.medias.len()
+    );
+    let answer = accept_remote_offer(&state, offer)
         .await
-        .push_back(SdpAnswer(answer));
+        .inspect_err(|err| info!("POST /offer signaling: rejected offer; {err:#}"))
+        .map_err(error_response)?;
+    Ok(Json(answer))
+}
 
-    (
-        StatusCode::OK,
-        Json(SimpleResponse {
-            message: "post answer succeeded.".to_string(),
-        }),
-    )
+async fn handle_get_sdp_offer(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, ErrorResponse> {
+    let sdp_offer = local_offer(&state).await?;
+    info!(
+        "GET /sdp signaling: served offer; medias={}",
+        sdp_offer.medias.len()
+    );
+    Ok((
+        [(header::CONTENT_TYPE, "application/sdp")],
+        SessionDescription::try_from(&sdp_offer)
+            .map_err(error_response)?
+            .encode(),
+    ))
+}
+
+async fn handle_post_sdp_local_offer(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, ErrorResponse> {
+    let sdp_offer = create_local_offer(&state)
+        .await
+        .inspect_err(|err| {
+            info!("POST /sdp/local-offer signaling: failed to create offer; {err:#}")
+        })
+        .map_err(error_response)?;
+    info!(
+        "POST /sdp/local-offer signaling: created offer; medias={}",
+        sdp_offer.medias.len()
+    );
+    Ok((
+        [(header::CONTENT_TYPE, "application/sdp")],
+        SessionDescription::try_from(&sdp_offer)
+            .map_err(error_response)?
+            .encode(),
+    ))
+}
+
+async fn handle_post_sdp_answer(
+    State(state): State<Arc<AppState>>,
+    body: String,
+) -> (StatusCode, Json<SimpleResponse>) {
+    let answer = match decode_sdp(&body) {
+        Ok(answer) => answer,
+        Err(e) => {
+            info!("POST /sdp signaling: invalid answer; {e:#}");
+            return error_response(e.context("invalid sdp answer"));
+        }
+    };
+
+    info!(
+        "POST /sdp signaling: received answer; session_id={}, medias={}",
+        answer.session_id,
+        answer.medias.len()
+    );
+
+    match accept_remote_answer(&state, answer).await {
+        Ok(()) => ok_response("post answer succeeded."),
+        Err(err) => {
+            info!("POST /sdp signaling: rejected answer; {err:#}");
+            error_response(err)
+        }
+    }
+}
+
+async fn handle_post_sdp_offer(
+    State(state): State<Arc<AppState>>,
+    body: String,
+) -> Result<impl IntoResponse, ErrorResponse> {
+    let offer = decode_sdp(&body)
+        .inspect_err(|err| info!("POST /sdp/offer signaling: invalid offer; {err:#}"))
+        .map_err(|err| error_response(err.context("invalid sdp offer")))?;
+    info!(
+        "POST /sdp/offer signaling: received offer; session_id={}, medias={}",
+        offer.session_id,
+        offer.medias.len()
+    );
+    let answer = accept_remote_offer(&state, offer)
+        .await
+        .inspect_err(|err| info!("POST /sdp/offer signaling: rejected offer; {err:#}"))
+        .map_err(error_response)?;
+    Ok((
+        [(header::CONTENT_TYPE, "application/sdp")],
+        SessionDescription::try_from(&answer)
+            .map_err(error_response)?
+            .encode(),
+    ))
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8838#section-11
+async fn handle_post_candidate(
+    State(state): State<Arc<AppState>>,
+    Json(candidate): Json<RtcIceCandidate>,
+) -> (StatusCode, Json<SimpleResponse>) {
+    info!("POST /candidate signaling: received candidate; {candidate:?}");
+    match state.pc.lock().await.add_ice_candidate(candidate).await {
+        Ok(()) => ok_response("post candidate succeeded."),
+        Err(err) => {
+            info!("POST /candidate signaling: rejected candidate; {err:#}");
+            error_response(err)
+        }
+    }
+}
+
+async fn handle_get_candidates(State(state): State<Arc<AppState>>) -> Json<Vec<RtcIceCandidate>> {
+    Json(state.pc.lock().await.local_candidates.clone())
 }
@@ -5,6 +5,7 @@ use aes_gcm::{Aes128Gcm, Key, KeyInit, Nonce};
 use anyhow::{Result, anyhow};
 
 use crate::common::buffer::BufWriter;
+use crate::dtls::HashAlgorithm;
 use crate::dtls::crypto::prf_p_hash;
 use crate::srtp::header::RtpHeader;
 use crate::srtp::packet::RtpPacket;
@@ -336,6 +337,7 @@ const PRF_DTLS_SRTP_EXPORTER_LABEL: &str = "EXTRACTOR-dtls_srtp";
 // https://datatracker.ietf.org/doc/html/rfc5764#section-4.2
 // https://datatracker.ietf.org/doc/html/rfc5705
 pub fn generate_keying_material(
+    hash: HashAlgorithm,
     master_secret: &[u8],
     client_random: &[u8],
     server_random: &[u8],
@@ -347,5 +349,5 @@ pub fn generate_keying_material(
         server_random.to_vec(),
     ]
     .concat();
-    prf_p_hash(master_secret, &seed, requested_bytes)
+    prf_p_hash(hash, master_secret, &seed, requested_bytes)
 }
@@ -3,6 +3,7 @@ use tokio::sync::{Mutex, mpsc::UnboundedSender};
 
 use crate::{
     common::buffer::BufReader,
+    dtls::DtlsRole,
     internal_event::InternalEvent,
     srtp::{
         SrtpSsrcState,
@@ -40,12 +41,22 @@ impl SrtpManager {
         self.media_track_tx = Some(media_track_tx);
     }
 
-    pub fn set_encryption_keys(&mut self, srtp_encryption_keys: SrtpEncryptionKeys) {
-        // use client key and salt to decrypt data from client
-        self.gcm = Some(SrtpGcm::new(
-            &srtp_encryption_keys.client_master_key,
-            &srtp_encryption_keys.client_master_salt,
-        ));
+    pub fn set_encryption_keys(
+        &mut self,
+        srtp_encryption_keys: SrtpEncryptionKeys,
+        role: DtlsRole,
+    ) {
+        // use the key and salt of the remote dtls role to decrypt data from the remote peer
+        self.gcm = Some(match role {
+            DtlsRole::Server => SrtpGcm::new(
+                &srtp_encryption_keys.client_master_key,
+                &srtp_encryption_keys.client_master_salt,
+            ),
+            DtlsRole::Client => SrtpGcm::new(
+                &srtp_encryption_keys.server_master_key,
+                &srtp_encryption_keys.server_master_salt,
+            ),
+        });
     }
 
     pub fn handle_inbound_packet(&mut self, data: &[u8], _peer_addr: SocketAddr) -> Result<()> {
@@ -5,7 +5,7 @@ use rand::Rng;
 use sha1::Sha1;
 use std::{
     collections::HashMap,
-    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
 };
 use tokio::net::UdpSocket;
 use tokio::time::{Duration, timeout};
@@ -46,11 +46,16 @@ const ATTRIBUTE_HEADER_BYTES: usize = 4;
 const HMAC_SIGNATURE_BYTES: usize = 20;
 const FINGERPRINT_BYTES: usize = 4;
 const FINGERPRINT_XOR_MASK: u32 = 0x5354554e;
+// attribute types from 0x8000 may be ignored by agents that do not understand them.
+const COMPREHENSION_OPTIONAL: u16 = 0x8000;
 
 pub struct StunMessage {
     pub message_type: StunMessageType,
     pub transaction_id: Vec<u8>, // 12 bytes
     pub attributes: HashMap<AttributeType, Attribute>,
+    /// Comprehension-required attribute types this codec does not know.
+    // https://datatracker.ietf.org/doc/html/rfc8489#section-14
+    pub unknown_attributes: Vec<u16>,
     pub raw: Vec<u8>,
 }
 
@@ -69,7 +74,40 @@ impl StunMessage {
         cookie == MAGIC_COOKIE
     }
 
+    /// Decodes the attribute of `attribute_type` if the message has one.
+    pub fn attribute(&self, attribute_type: AttributeType) -> Result<Option<StunAttribute>> {
+        self.attributes
+            .get(&attribute_type)
+            .map(|attribute| {
+                StunAttribute::decode(attribute_type, &attribute.value, &self.transaction_id)
+            })
+            .transpose()
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8489#section-14.7
+    pub fn verify_fingerprint(&self) -> Result<bool> {
+        let fingerprint = self
+            .attributes
+            .get(&AttributeType::Fingerprint)
+            .ok_or(anyhow!("fingerprint attribute does not exist."))?;
+        if fingerprint.value.len() != FINGERPRINT_BYTES
+            || fingerprint.offset_in_message + ATTRIBUTE_HEADER_BYTES + FINGERPRINT_BYTES
+                != self.raw.len()
+        {
+            // FINGERPRINT must be the last attribute.
+            return Ok(false);
+        }
+        let checksum =
+            Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&self.raw[..fingerprint.offset_in_message]);
+        Ok((checksum ^ FINGERPRINT_XOR_MASK).to_be_bytes()[..] == fingerprint.value[..])
+    }
+
     pub fn verify_message_integrity(&self, pwd: String) -> Result<bool> {
+        self.verify_message_integrity_with_key(pwd.as_bytes())
+    }
+
+    /// Verifies MESSAGE-INTEGRITY with an explicit HMAC key, e.g. a TURN long-term credential key.
+    pub fn verify_message_integrity_with_key(&self, key: &[u8]) -> Result<bool> {
         let message_integrity = self
             .attributes
             .get(&AttributeType::MessageIntegrity)
@@ -104,7 +142,7 @@ impl StunMessage {
         raw_message[2] = (message_length >> 8) as u8;
         raw_message[3] = message_length as u8;
 
-        let calculated_message_integrity = hmac_sha1(pwd.as_bytes(), &raw_message);
+        let calculated_message_integrity = hmac_sha1(key, &raw_message);
         Ok(calculated_message_integrity == message_integrity.value)
     }
 
@@ -141,6 +179,7 @@ impl StunMessage {
         reader.read_exact(&mut transaction_id)?;
 
         let mut attributes: HashMap<AttributeType, Attribute> = HashMap::new();
+        let mut unknown_attributes = vec![];
 
         let mut remaining = message_length;
         while remaining > 0 {
@@ -151,7 +190,11 @@ impl StunMessage {
             }
 
             let offset = reader.pos;
-            let attr_type = AttributeType::from(reader.read_u16()?);
+            let raw_attr_type = reader.read_u16()?;
+            let attr_type = AttributeType::from(raw_attr_type);
+            if attr_type == AttributeType::Unsupported && raw_attr_type < COMPREHENSION_OPTIONAL {
+                unknown_attributes.push(raw_attr_type);
+            }
             let attr_length = reader.read_u16()? as usize;
             remaining -= ATTRIBUTE_HEADER_BYTES;
 
@@ -195,6 +238,7 @@ impl StunMessage {
             message_type,
             transaction_id,
             attributes,
+            unknown_attributes,
             raw: reader.buf[..HEADER_BYTES + message_length].to_vec(),
         })
     }
@@ -210,6 +254,13 @@ pub struct StunMessageType {
 #[try_from(type = "u16")]
 pub enum StunMessageMethod {
     Binding = 0x0001,
+    // https://datatracker.ietf.org/doc/html/rfc8656#section-17
+    Allocate = 0x0003,
+    Refresh = 0x0004,
+    Send = 0x0006,
+    Data = 0x0007,
+    CreatePermission = 0x0008,
+    ChannelBind = 0x0009,
 }
 
 #[derive(TryFromPrimitive, Clone, Copy)]
@@ -225,13 +276,239 @@ pub enum StunMessageClass {
 #[from(type = "u16", default = "Unsupported")]
 pub enum AttributeType {
     Unsupported = 0x0000,
+    MappedAddress = 0x0001,
     Username = 0x0006,
     Password = 0x0007,
     MessageIntegrity = 0x0008,
     ErrorCode = 0x0009,
     UnknownAttributes = 0x000a,
+    // https://datatracker.ietf.org/doc/html/rfc8656#section-18
+    ChannelNumber = 0x000c,
+    Lifetime = 0x000d,
+    XorPeerAddress = 0x0012,
+    Data = 0x0013,
+    Realm = 0x0014,
+    Nonce = 0x0015,
+    XorRelayedAddress = 0x0016,
+    RequestedTransport = 0x0019,
+    // https://datatracker.ietf.org/doc/html/rfc8489#section-18.3
+    MessageIntegritySha256 = 0x001c,
+    PasswordAlgorithm = 0x001d,
+    Userhash = 0x001e,
     XorMappedAddress = 0x0020,
+    // https://datatracker.ietf.org/doc/html/rfc8445#section-16.1
+    Priority = 0x0024,
+    UseCandidate = 0x0025,
+    PasswordAlgorithms = 0x8002,
+    AlternateDomain = 0x8003,
+    Software = 0x8022,
+    AlternateServer = 0x8023,
     Fingerprint = 0x8028,
+    IceControlled = 0x8029,
+    IceControlling = 0x802a,
+}
+
+/// Decoded value of an attribute.
+// https://datatracker.ietf.org/doc/html/rfc8489#section-14
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StunAttribute {
+    MappedAddress(SocketAddr),
+    Username(String),
+    MessageIntegrity(Vec<u8>),
+    ErrorCode {
+        code: u16,
+        reason: String,
+    },
+    UnknownAttributes(Vec<u16>),
+    ChannelNumber(u16),
+    /// Seconds.
+    Lifetime(u32),
+    XorPeerAddress(SocketAddr),
+    Data(Vec<u8>),
+    Realm(String),
+    Nonce(String),
+    XorRelayedAddress(SocketAddr),
+    /// IANA protocol number; 17 for udp.
+    RequestedTransport(u8),
+    MessageIntegritySha256(Vec<u8>),
+    PasswordAlgorithm(PasswordAlgorithm),
+    Userhash(Vec<u8>),
+    XorMappedAddress(SocketAddr),
+    Priority(u32),
+    UseCandidate,
+    PasswordAlgorithms(Vec<PasswordAlgorithm>),
+    AlternateDomain(String),
+    Software(String),
+    AlternateServer(SocketAddr),
+    Fingerprint(u32),
+    IceControlled(u64),
+    IceControlling(u64),
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8489#section-14.12
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordAlgorithm {
+    pub algorithm: u16,
+    pub parameters: Vec<u8>,
+}
+
+impl StunAttribute {
+    pub fn attribute_type(&self) -> AttributeType {
+        match self {
+            Self::MappedAddress(_) => AttributeType::MappedAddress,
+            Self::Username(_) => AttributeType::Username,
+            Self::MessageIntegrity(_) => AttributeType::MessageIntegrity,
+            Self::ErrorCode { .. } => AttributeType::ErrorCode,
+            Self::UnknownAttributes(_) => AttributeType::UnknownAttributes,
+            Self::ChannelNumber(_) => AttributeType::ChannelNumber,
+            Self::Lifetime(_) => AttributeType::Lifetime,
+            Self::XorPeerAddress(_) => AttributeType::XorPeerAddress,
+            Self::Data(_) => AttributeType::Data,
+            Self::Realm(_) => AttributeType::Realm,
+            Self::Nonce(_) => AttributeType::Nonce,
+            Self::XorRelayedAddress(_) => AttributeType::XorRelayedAddress,
+            Self::RequestedTransport(_) => AttributeType::RequestedTransport,
+            Self::MessageIntegritySha256(_) => AttributeType::MessageIntegritySha256,
+            Self::PasswordAlgorithm(_) => AttributeType::PasswordAlgorithm,
+            Self::Userhash(_) => AttributeType::Userhash,
+            Self::XorMappedAddress(_) => AttributeType::XorMappedAddress,
+            Self::Priority(_) => AttributeType::Priority,
+            Self::UseCandidate => AttributeType::UseCandidate,
+            Self::PasswordAlgorithms(_) => AttributeType::PasswordAlgorithms,
+            Self::AlternateDomain(_) => AttributeType::AlternateDomain,
+            Self::Software(_) => AttributeType::Software,
+            Self::AlternateServer(_) => AttributeType::AlternateServer,
+            Self::Fingerprint(_) => AttributeType::Fingerprint,
+            Self::IceControlled(_) => AttributeType::IceControlled,
+            Self::IceControlling(_) => AttributeType::IceControlling,
+        }
+    }
+
+    /// Encodes the value without the attribute header and padding;
+    /// XOR addresses are masked with `transaction_id`.
+    pub fn encode(&self, transaction_id: &[u8]) -> Vec<u8> {
+        match self {
+            Self::MappedAddress(addr) | Self::AlternateServer(addr) => encode_address(*addr),
+            Self::XorMappedAddress(addr)
+            | Self::XorPeerAddress(addr)
+            | Self::XorRelayedAddress(addr) => encode_xor_mapped_address(*addr, transaction_id),
+            Self::Username(value)
+            | Self::Realm(value)
+            | Self::Nonce(value)
+            | Self::AlternateDomain(value)
+            | Self::Software(value) => value.as_bytes().to_vec(),
+            Self::MessageIntegrity(value)
+            | Self::MessageIntegritySha256(value)
+            | Self::Userhash(value)
+            | Self::Data(value) => value.clone(),
+            Self::ErrorCode { code, reason } => encode_error_code(*code, reason),
+            Self::UnknownAttributes(attribute_types) => attribute_types
+                .iter()
+                .flat_map(|attribute_type| attribute_type.to_be_bytes())
+                .collect(),
+            Self::ChannelNumber(channel_number) => [channel_number.to_be_bytes(), [0, 0]].concat(),
+            Self::Lifetime(value) | Self::Priority(value) | Self::Fingerprint(value) => {
+                value.to_be_bytes().to_vec()
+            }
+            Self::RequestedTransport(protocol) => vec![*protocol, 0, 0, 0],
+            Self::PasswordAlgorithm(algorithm) => encode_password_algorithm(algorithm),
+            Self::PasswordAlgorithms(algorithms) => algorithms
+                .iter()
+                .flat_map(encode_password_algorithm)
+                .collect(),
+            Self::UseCandidate => vec![],
+            Self::IceControlled(tie_breaker) | Self::IceControlling(tie_breaker) => {
+                tie_breaker.to_be_bytes().to_vec()
+            }
+        }
+    }
+
+    pub fn decode(
+        attribute_type: AttributeType,
+        value: &[u8],
+        transaction_id: &[u8],
+    ) -> Result<Self> {
+        let string = || -> Result<String> { Ok(String::from_utf8(value.to_vec())?) };
+        let u32_value = || -> Result<u32> {
+            Ok(u32::from_be_bytes(value.try_into().map_err(|_| {
+                anyhow!("invalid attribute length; length={}", value.len())
+            })?))
+        };
+        let u64_value = || -> Result<u64> {
+            Ok(u64::from_be_bytes(value.try_into().map_err(|_| {
+                anyhow!("invalid attribute length; length={}", value.len())
+            })?))
+        };
+        let attribute = match attribute_type {
+            AttributeType::MappedAddress => Self::MappedAddress(decode_address(value)?),
+            AttributeType::AlternateServer => Self::AlternateServer(decode_address(value)?),
+            AttributeType::XorMappedAddress => {
+                Self::XorMappedAddress(decode_xor_mapped_address(value, transaction_id)?)
+            }
+            AttributeType::XorPeerAddress => {
+                Self::XorPeerAddress(decode_xor_mapped_address(value, transaction_id)?)
+            }
+            AttributeType::XorRelayedAddress => {
+                Self::XorRelayedAddress(decode_xor_mapped_address(value, transaction_id)?)
+            }
+            AttributeType::Username => Self::Username(string()?),
+            AttributeType::Realm => Self::Realm(string()?),
+            AttributeType::Nonce => Self::Nonce(string()?),
+            AttributeType::AlternateDomain => Self::AlternateDomain(string()?),
+            AttributeType::Software => Self::Software(string()?),
+            AttributeType::MessageIntegrity => Self::MessageIntegrity(value.to_vec()),
+            AttributeType::MessageIntegritySha256 => Self::MessageIntegritySha256(value.to_vec()),
+            AttributeType::Userhash => Self::Userhash(value.to_vec()),
+            AttributeType::Data => Self::Data(value.to_vec()),
+            AttributeType::ErrorCode => Self::ErrorCode {
+                code: decode_error_code(value)?,
+                reason: String::from_utf8_lossy(&value[4..]).to_string(),
+            },
+            AttributeType::UnknownAttributes => Self::UnknownAttributes(
+                value
+                    .chunks_exact(2)
+                    .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
+                    .collect(),
+            ),
+            AttributeType::ChannelNumber => {
+                let value = value.get(..2).ok_or(anyhow!(
+                    "invalid channel number length; length={}",
+                    value.len()
+                ))?;
+                Self::ChannelNumber(u16::from_be_bytes([value[0], value[1]]))
+            }
+            AttributeType::Lifetime => Self::Lifetime(u32_value()?),
+            AttributeType::Priority => Self::Priority(u32_value()?),
+            AttributeType::Fingerprint => Self::Fingerprint(u32_value()?),
+            AttributeType::RequestedTransport => Self::RequestedTransport(
+                *value
+                    .first()
+                    .ok_or(anyhow!("requested transport attribute is empty."))?,
+            ),
+            AttributeType::PasswordAlgorithm => {
+                let mut reader = BufReader::new(value);
+                Self::PasswordAlgorithm(decode_password_algorithm(&mut reader)?)
+            }
+            AttributeType::PasswordAlgorithms => {
+                let mut reader = BufReader::new(value);
+                let mut algorithms = vec![];
+                while reader.rest_len() > 0 {
+                    algorithms.push(decode_password_algorithm(&mut reader)?);
+                }
+                Self::PasswordAlgorithms(algorithms)
+            }
+            AttributeType::UseCandidate => Self::UseCandidate,
+            AttributeType::IceControlled => Self::IceControlled(u64_value()?),
+            AttributeType::IceControlling => Self::IceControlling(u64_value()?),
+            AttributeType::Unsupported | AttributeType::Password => {
+                return Err(anyhow!(
+                    "attribute has no typed value; type=0x{:04x}",
+                    attribute_type as u16
+                ));
+            }
+        };
+        Ok(attribute)
+    }
 }
 
 pub struct Attribute {
@@ -280,6 +557,11 @@ impl StunMessageBuilder {
         self.writer.write_u8_at(message_length as u8, 3);
     }
 
+    pub fn add_attribute(self, attribute: &StunAttribute) -> Self {
+        let value = attribute.encode(&self.transaction_id);
+        self.add_attr(attribute.attribute_type(), &value)
+    }
+
     pub fn add_attr(mut self, attr_type: AttributeType, value: &[u8]) -> Self {
         self.attributes.insert(
             attr_type,
@@ -313,11 +595,17 @@ impl StunMessageBuilder {
             message_type: self.message_type,
             transaction_id: self.transaction_id,
             attributes: self.attributes,
+            unknown_attributes: vec![],
             raw: self.writer.buf(),
         }
     }
 
-    pub fn build(mut self, pwd: String) -> StunMessage {
+    pub fn build(self, pwd: String) -> StunMessage {
+        self.build_with_key(pwd.as_bytes())
+    }
+
+    /// Appends MESSAGE-INTEGRITY keyed with `key` and FINGERPRINT.
+    pub fn build_with_key(mut self, key: &[u8]) -> StunMessage {
         self = self.add_attr(
             AttributeType::MessageIntegrity,
             &vec![0u8; HMAC_SIGNATURE_BYTES],
@@ -337,7 +625,7 @@ impl StunMessageBuilder {
 
         let mut data = self.writer.buf();
         data.truncate(mi_offset);
-        let message_integrity = hmac_sha1(pwd.as_bytes(), &data[..]);
+        let message_integrity = hmac_sha1(key, &data[..]);
         let mi_value_start = mi_offset + ATTRIBUTE_HEADER_BYTES;
         self.writer
             .write_bytes_at(&message_integrity, mi_value_start);
@@ -364,6 +652,7 @@ impl StunMessageBuilder {
             message_type: self.message_type,
             transaction_id: self.transaction_id,
             attributes: self.attributes,
+            unknown_attributes: vec![],
             raw: self.writer.buf(),
         }
     }
@@ -403,7 +692,11 @@ impl StunClient {
         )
         .build_without_integrity();
 
-        let socket = UdpSocket::bind("0.0.0.0:0".parse::<SocketAddr>()?).await?;
+        let unspecified = match self.to {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0)).await?;
         const MAX_DATAGRAM_SIZE: usize = 65_507;
         socket.connect(&self.to).await?;
         info!("stun client: socket connected");
@@ -423,37 +716,231 @@ impl StunClient {
             .get(&AttributeType::XorMappedAddress)
             .ok_or(anyhow!("xor mapped address attribute not found."))?;
 
-        let xor_mask = {
-            let mut buf = MAGIC_COOKIE.to_be_bytes().to_vec();
-            buf.extend_from_slice(&transaction_id);
-            buf
-        };
+        decode_xor_mapped_address(&xor_mapped_address_attr.value, &transaction_id)
+    }
+}
 
-        let mut mapped_ip_addr: Vec<u8> = vec![];
-        for i in 0..xor_mapped_address_attr.value.len() - 4 {
-            mapped_ip_addr.push(xor_mapped_address_attr.value[i + 4] ^ xor_mask[i]);
+// https://datatracker.ietf.org/doc/html/rfc5389#section-15.2
+pub fn encode_xor_mapped_address(addr: SocketAddr, transaction_id: &[u8]) -> Vec<u8> {
+    let xor_mask = xor_mask(transaction_id);
+    let mut writer = BufWriter::new();
+    writer.write_u8(0);
+    let ip_addr_bytes = match addr.ip() {
+        IpAddr::V4(ip) => {
+            writer.write_u8(IpFamily::V4 as u8);
+            ip.octets().to_vec()
         }
-        let ip_family = IpFamily::try_from(xor_mapped_address_attr.value[1])?;
-        let xor_port = u16::from_be_bytes(xor_mapped_address_attr.value[2..4].try_into()?);
-        let port = xor_port ^ u16::from_be_bytes(xor_mask[..2].try_into()?);
-
-        let sock_addr = match ip_family {
-            IpFamily::V4 => {
-                let ip = Ipv4Addr::from(<[u8; 4]>::try_from(&mapped_ip_addr[..4])?);
-                SocketAddr::from((ip, port))
-            }
-            IpFamily::V6 => {
-                let ip = Ipv6Addr::from(<[u8; 16]>::try_from(&mapped_ip_addr[..16])?);
-                SocketAddr::from((ip, port))
-            }
-        };
-        Ok(sock_addr)
+        IpAddr::V6(ip) => {
+            writer.write_u8(IpFamily::V6 as u8);
+            ip.octets().to_vec()
+        }
+    };
+    writer.write_u16(addr.port() ^ ((MAGIC_COOKIE >> 16) as u16));
+    for (octet, mask) in ip_addr_bytes.iter().zip(xor_mask) {
+        writer.write_u8(octet ^ mask);
+    }
+    writer.buf()
+}
+
+pub fn decode_xor_mapped_address(value: &[u8], transaction_id: &[u8]) -> Result<SocketAddr> {
+    if value.len() < 8 {
+        return Err(anyhow!(
+            "invalid xor mapped address length; length={}",
+            value.len()
+        ));
+    }
+    let xor_mask = xor_mask(transaction_id);
+
+    let mapped_ip_addr = value[4..]
+        .iter()
+        .zip(&xor_mask)
+        .map(|(octet, mask)| octet ^ mask)
+        .collect::<Vec<_>>();
+    let ip_family = IpFamily::try_from(value[1])?;
+    let xor_port = u16::from_be_bytes(value[2..4].try_into()?);
+    let port = xor_port ^ u16::from_be_bytes(xor_mask[..2].try_into()?);
+
+    let sock_addr = match ip_family {
+        IpFamily::V4 => {
+            let ip = Ipv4Addr::from(<[u8; 4]>::try_from(&mapped_ip_addr[..4])?);
+            SocketAddr::from((ip, port))
+        }
+        IpFamily::V6 => {
+            let ip = Ipv6Addr::from(<[u8; 16]>::try_from(mapped_ip_addr.get(..16).ok_or(
+                anyhow!(
+                    "invalid xor mapped ipv6 address length; length={}",
+                    value.len()
+                ),
+            )?)?);
+            SocketAddr::from((ip, port))
+        }
+    };
+    Ok(sock_addr)
+}
+
+// https://datatracker.ietf.org/doc/html/rfc5389#section-15.6
+pub fn encode_error_code(code: u16, reason_phrase: &str) -> Vec<u8> {
+    let mut writer = BufWriter::new();
+    writer.write_u16(0);
+    writer.write_u8((code / 100) as u8);
+    writer.write_u8((code % 100) as u8);
+    writer.write_bytes(reason_phrase.as_bytes());
+    writer.buf()
+}
+
+pub fn decode_error_code(value: &[u8]) -> Result<u16> {
+    if value.len() < 4 {
+        return Err(anyhow!("invalid error code length; length={}", value.len()));
     }
+    Ok((value[2] & 0x07) as u16 * 100 + value[3] as u16)
 }
 
-fn generate_transaction_id() -> Vec<u8> {
+// https://datatracker.ietf.org/doc/html/rfc8489#section-14.1
+pub fn encode_address(addr: SocketAddr) -> Vec<u8> {
+    let mut writer = BufWriter::new();
+    writer.write_u8(0);
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            writer.write_u8(IpFamily::V4 as u8);
+            writer.write_u16(addr.port());
+            writer.write_bytes(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            writer.write_u8(IpFamily::V6 as u8);
+            writer.write_u16(addr.port());
+            writer.write_bytes(&ip.octets());
+        }
+    }
+    writer.buf()
+}
+
+pub fn decode_address(value: &[u8]) -> Result<SocketAddr> {
+    let mut reader = BufReader::new(value);
+    let _reserved = reader.read_u8()?;
+    let ip_family = IpFamily::try_from(reader.read_u8()?)?;
+    let port = reader.read_u16()?;
+    let ip = match ip_family {
+        IpFamily::V4 => {
+            let mut octets = [0u8; 4];
+            reader.read_exact(&mut octets)?;
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+        IpFamily::V6 => {
+            let mut octets = [0u8; 16];
+            reader.read_exact(&mut octets)?;
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+    };
+    Ok(SocketAddr::new(ip, port))
+}
+
+fn encode_password_algorithm(algorithm: &PasswordAlgorithm) -> Vec<u8> {
+    let mut writer = BufWriter::new();
+    writer.write_u16(algorithm.algorithm);
+    writer.write_u16(algorithm.parameters.len() as u16);
+    writer.write_bytes(&algorithm.parameters);
+    let padding = (4 - (algorithm.parameters.len() % 4)) % 4;
+    writer.write_bytes(&vec![0u8; padding]);
+    writer.buf()
+}
+
+fn decode_password_algorithm(reader: &mut BufReader) -> Result<PasswordAlgorithm> {
+    let algorithm = reader.read_u16()?;
+    let length = reader.read_u16()? as usize;
+    let mut parameters = vec![0u8; length];
+    reader.read_exact(&mut parameters)?;
+    let mut padding = vec![0u8; (4 - (length % 4)) % 4];
+    reader.read_exact(&mut padding)?;
+    Ok(PasswordAlgorithm {
+        algorithm,
+        parameters,
+    })
+}
+
+fn xor_mask(transaction_id: &[u8]) -> Vec<u8> {
+    let mut buf = MAGIC_COOKIE.to_be_bytes().to_vec();
+    buf.extend_from_slice(transaction_id);
+    buf
+}
+
+pub fn generate_transaction_id() -> Vec<u8> {
     let mut rng = rand::rng();
     let mut transaction_id = [0u8; 12];
     rng.fill_bytes(&mut transaction_id);
     transaction_id.into()
 }
+
+#[cfg(test)]
+mod stun_tests {
+    use super::*;
+
+    fn binding_request() -> StunMessageBuilder {
+        StunMessageBuilder::new(
+            StunMessageType {
+                method: StunMessageMethod::Binding,
+                class: StunMessageClass::Request,
+            },
+            generate_transaction_id(),
+        )
+    }
+
+    #[test]
+    fn test_attribute_round_trip() -> Result<()> {
+        let attributes = [
+            StunAttribute::XorMappedAddress("[2001:db8::1]:5000".parse()?),
+            StunAttribute::MappedAddress("192.0.2.1:3478".parse()?),
+            StunAttribute::ErrorCode {
+                code: 487,
+                reason: "Role Conflict".to_string(),
+            },
+            StunAttribute::Priority(0x6e0001ff),
+            StunAttribute::IceControlling(0x0123456789abcdef),
+            StunAttribute::UseCandidate,
+            StunAttribute::UnknownAttributes(vec![0x0031, 0x0032]),
+            StunAttribute::PasswordAlgorithms(vec![PasswordAlgorithm {
+                algorithm: 0x0002,
+                parameters: vec![],
+            }]),
+        ];
+        let message = attributes
+            .iter()
+            .fold(binding_request(), |builder, attribute| {
+                builder.add_attribute(attribute)
+            })
+            .build("pwd".to_string());
+
+        let decoded = StunMessage::decode(&mut BufReader::new(&message.raw))?;
+        for attribute in attributes {
+            assert_eq!(
+                decoded.attribute(attribute.attribute_type())?,
+                Some(attribute)
+            );
+        }
+        assert!(decoded.verify_message_integrity("pwd".to_string())?);
+        assert!(decoded.verify_fingerprint()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fingerprint_mismatch() -> Result<()> {
+        let mut raw = binding_request().build("pwd".to_string()).raw;
+        let last = raw.len() - 1;
+        raw[last] ^= 0xff;
+
+        let decoded = StunMessage::decode(&mut BufReader::new(&raw))?;
+        assert!(!decoded.verify_fingerprint()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_attributes() -> Result<()> {
+        let mut raw = binding_request().build_without_integrity().raw;
+        // a comprehension-required and a comprehension-optional unknown attribute
+        raw.extend_from_slice(&[0x00, 0x31, 0x00, 0x00, 0x80, 0x31, 0x00, 0x00]);
+        raw[3] = 8;
+
+        let decoded = StunMessage::decode(&mut BufReader::new(&raw))?;
+        assert_eq!(decoded.unknown_attributes, vec![0x0031]);
+        Ok(())
+    }
+}
@@ -0,0 +1,743 @@
+use anyhow::{Result, anyhow};
+use local_ip_address::{local_ip, local_ipv6};
+use rand::distr::{Alphanumeric, SampleString};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::task::JoinHandle;
+use tokio::time::interval;
+use tracing::{debug, info, warn};
+
+use crate::common::TransportMessage;
+use crate::common::buffer::BufReader;
+use crate::stun::{
+    AttributeType, StunMessage, StunMessageBuilder, StunMessageClass, StunMessageMethod,
+    StunMessageType, encode_error_code, encode_xor_mapped_address, generate_transaction_id,
+};
+use crate::turn::{
+    CHANNEL_LIFETIME, DEFAULT_LIFETIME, MAX_CHANNEL_NUMBER, MIN_CHANNEL_NUMBER,
+    PERMISSION_LIFETIME, REQUESTED_TRANSPORT_UDP, STALE_NONCE, UNAUTHORIZED, decode_channel_data,
+    decode_peer_data, encode_channel_data, is_channel_data, long_term_key, read_address_attr,
+    read_string_attr,
+};
+use crate::udp_server::{bind_udp_socket, canonical_addr, socket_peer_addr};
+
+const DEFAULT_BIND_ADDRESS: &str = "0.0.0.0:3478";
+pub const DEFAULT_REALM: &str = "mini-webrtc-rs";
+// https://datatracker.ietf.org/doc/html/rfc8656#section-7.2
+const MAX_LIFETIME: Duration = Duration::from_secs(3600);
+// https://datatracker.ietf.org/doc/html/rfc8489#section-9.2
+const NONCE_LIFETIME: Duration = Duration::from_secs(3600);
+const NONCE_LENGTH: usize = 16;
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+// https://datatracker.ietf.org/doc/html/rfc8656#section-19
+const BAD_REQUEST: u16 = 400;
+const ALLOCATION_MISMATCH: u16 = 437;
+const UNSUPPORTED_TRANSPORT_PROTOCOL: u16 = 442;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StunServerOptions {
+    pub bind_address: SocketAddr,
+    /// Only STUN Binding is served when `None`.
+    pub turn: Option<TurnRelayOptions>,
+}
+
+impl Default for StunServerOptions {
+    fn default() -> Self {
+        Self {
+            bind_address: DEFAULT_BIND_ADDRESS.parse().unwrap(),
+            turn: None,
+        }
+    }
+}
+
+/// Minimal TURN relay with a single static long-term credential.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TurnRelayOptions {
+    pub realm: String,
+    pub username: String,
+    pub password: String,
+    /// Relayed addresses are allocated on this ip; the local ip is advertised when unspecified.
+    pub relay_ip: IpAddr,
+    /// A request with an older nonce is answered with 438 (Stale Nonce).
+    pub nonce_lifetime: Duration,
+}
+
+impl TurnRelayOptions {
+    pub fn new(username: &str, password: &str) -> Self {
+        Self {
+            realm: DEFAULT_REALM.to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            relay_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            nonce_lifetime: NONCE_LIFETIME,
+        }
+    }
+}
+
+struct ServerAllocation {
+    relayed_address: SocketAddr,
+    expires_at: Instant,
+    // peer ip -> expiry of the permission
+    permissions: HashMap<IpAddr, Instant>,
+    // channel number -> (peer address, expiry of the binding)
+    channels: HashMap<u16, (SocketAddr, Instant)>,
+    relay: Arc<UdpSocket>,
+    relay_loop_handle: JoinHandle<()>,
+}
+
+impl ServerAllocation {
+    fn is_permitted(&self, peer: IpAddr, now: Instant) -> bool {
+        self.permissions
+            .get(&peer)
+            .is_some_and(|expires_at| *expires_at > now)
+    }
+
+    fn channel_of(&self, peer: SocketAddr) -> Option<u16> {
+        self.channels
+            .iter()
+            .find(|(_, (bound, _))| *bound == peer)
+            .map(|(channel_number, _)| *channel_number)
+    }
+}
+
+impl Drop for ServerAllocation {
+    fn drop(&mut self) {
+        self.relay_loop_handle.abort();
+    }
+}
+
+#[derive(Default)]
+struct RelayState {
+    nonce: String,
+    nonce_expires_at: Option<Instant>,
+    // client address -> its allocation; one allocation per 5-tuple
+    allocations: HashMap<SocketAddr, ServerAllocation>,
+}
+
+impl RelayState {
+    // https://datatracker.ietf.org/doc/html/rfc8489#section-9.2.4
+    fn current_nonce(&mut self, now: Instant, lifetime: Duration) -> String {
+        if self.nonce_expires_at.is_none_or(|at| at <= now) {
+            self.nonce = Alphanumeric.sample_string(&mut rand::rng(), NONCE_LENGTH);
+            self.nonce_expires_at = Some(now + lifetime);
+        }
+        self.nonce.clone()
+    }
+}
+
+/// STUN server answering Binding requests from any client, optionally acting as a TURN relay
+/// so that tests and on-prem deployments need no public STUN/TURN server.
+// https://datatracker.ietf.org/doc/html/rfc8489
+// https://datatracker.ietf.org/doc/html/rfc8656
+pub struct StunServer {
+    socket: Arc<UdpSocket>,
+    turn: Option<TurnRelayOptions>,
+    key: Vec<u8>,
+    state: Arc<StdMutex<RelayState>>,
+}
+
+impl StunServer {
+    pub async fn bind(options: StunServerOptions) -> Result<Self> {
+        let socket = bind_udp_socket(options.bind_address)?;
+        info!("Stun Server listening on {}", socket.local_addr()?);
+        let key = options
+            .turn
+            .as_ref()
+            .map(|turn| long_term_key(&turn.username, &turn.realm, &turn.password))
+            .unwrap_or_default();
+        Ok(Self {
+            socket: Arc::new(socket),
+            turn: options.turn,
+            key,
+            state: Arc::new(StdMutex::new(RelayState::default())),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        let mut buf = vec![0u8; 65535];
+        let mut expiry_check = interval(EXPIRY_CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                received = self.socket.recv_from(&mut buf) => {
+                    let (len, from) = match received {
+                        Ok((len, from)) => (len, canonical_addr(from)),
+                        Err(err) => {
+                            debug!("failed to receive udp datagram: {err}");
+                            continue;
+                        }
+                    };
+                    let _ = self
+                        .handle_datagram(&buf[..len], from)
+                        .await
+                        .inspect_err(|err| debug!("{err:?}"));
+                }
+                _ = expiry_check.tick() => self.remove_expired(Instant::now()),
+            }
+        }
+    }
+
+    async fn handle_datagram(&self, data: &[u8], from: SocketAddr) -> Result<()> {
+        if self.turn.is_some() && is_channel_data(data) {
+            let (channel_number, data) = decode_channel_data(data)?;
+            let peer = {
+                let state = self.state.lock().unwrap();
+                state.allocations.get(&from).and_then(|allocation| {
+                    let (peer, _) = allocation.channels.get(&channel_number)?;
+                    Some((allocation.relay.clone(), *peer))
+                })
+            };
+            return match peer {
+                Some((relay, peer)) => send_to(&relay, &data, peer).await,
+                None => Err(anyhow!(
+                    "channel data on unbound channel; client={from}, channel_number=0x{channel_number:04x}"
+                )),
+            };
+        }
+        if !StunMessage::is_stun_message(data) {
+            return Err(anyhow!("ignored non-stun datagram; client={from}"));
+        }
+
+        let message = StunMessage::decode(&mut BufReader::new(data))?;
+        let response = match (message.message_type.class, message.message_type.method) {
+            (StunMessageClass::Request, StunMessageMethod::Binding) => {
+                binding_response(&message, from)
+            }
+            (StunMessageClass::Indication, StunMessageMethod::Send) if self.turn.is_some() => {
+                return self.handle_send_indication(&message, from).await;
+            }
+            (
+                StunMessageClass::Request,
+                StunMessageMethod::Allocate
+                | StunMessageMethod::Refresh
+                | StunMessageMethod::CreatePermission
+                | StunMessageMethod::ChannelBind,
+            ) if self.turn.is_some() => self.handle_turn_request(&message, from).await?,
+            (StunMessageClass::Request, _) => error_response(&message, BAD_REQUEST, None),
+            _ => return Ok(()),
+        };
+        send_to(&self.socket, &response, from).await
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8656#section-11.2
+    async fn handle_send_indication(&self, message: &StunMessage, from: SocketAddr) -> Result<()> {
+        let TransportMessage { peer_addr, data } = decode_peer_data(message)?;
+        let relay = {
+            let state = self.state.lock().unwrap();
+            state
+                .allocations
+                .get(&from)
+                .filter(|allocation| allocation.is_permitted(peer_addr.ip(), Instant::now()))
+                .map(|allocation| allocation.relay.clone())
+        };
+        match relay {
+            Some(relay) => send_to(&relay, &data, peer_addr).await,
+            None => Err(anyhow!(
+                "send indication without allocation or permission; client={from}, peer={peer_addr}"
+            )),
+        }
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8489#section-9.2.4
+    async fn handle_turn_request(
+        &self,
+        message: &StunMessage,
+        from: SocketAddr,
+    ) -> Result<Vec<u8>> {
+        let Some(turn) = &self.turn else {
+            return Ok(error_response(message, BAD_REQUEST, None));
+        };
+        let now = Instant::now();
+        let nonce = self
+            .state
+            .lock()
+            .unwrap()
+            .current_nonce(now, turn.nonce_lifetime);
+        let challenge = |code| {
+            let response = StunMessageBuilder::new(
+                StunMessageType {
+                    method: message.message_type.method,
+                    class: StunMessageClass::ErrorResponse,
+                },
+                message.transaction_id.clone(),
+            )
+            .add_attr(AttributeType::ErrorCode, &encode_error_code(code, ""))
+            .add_attr(AttributeType::Realm, turn.realm.as_bytes())
+            .add_attr(AttributeType::Nonce, nonce.as_bytes())
+            .build_without_integrity();
+            response.raw
+        };
+
+        let username = read_string_attr(message, AttributeType::Username);
+        if !message
+            .attributes
+            .contains_key(&AttributeType::MessageIntegrity)
+            || username.as_deref() != Some(turn.username.as_str())
+            || !message.verify_message_integrity_with_key(&self.key)?
+        {
+            return Ok(challenge(UNAUTHORIZED));
+        }
+        if read_string_attr(message, AttributeType::Nonce).as_deref() != Some(nonce.as_str()) {
+            return Ok(challenge(STALE_NONCE));
+        }
+
+        let result = match message.message_type.method {
+            StunMessageMethod::Allocate => self.allocate(message, from, turn, now).await,
+            StunMessageMethod::Refresh => self.refresh(message, from, now),
+            StunMessageMethod::CreatePermission => self.create_permission(message, from, now),
+            StunMessageMethod::ChannelBind => self.channel_bind(message, from, now),
+            _ => Err(BAD_REQUEST),
+        };
+        Ok(match result {
+            Ok(attributes) => {
+                let mut builder = StunMessageBuilder::new(
+                    StunMessageType {
+                        method: message.message_type.method,
+                        class: StunMessageClass::SuccessResponse,
+                    },
+                    message.transaction_id.clone(),
+                );
+                for (attr_type, value) in attributes {
+                    builder = builder.add_attr(attr_type, &value);
+                }
+                builder.build_with_key(&self.key).raw
+            }
+            Err(code) => error_response(message, code, Some(&self.key)),
+        })
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8656#section-7.2
+    async fn allocate(
+        &self,
+        message: &StunMessage,
+        from: SocketAddr,
+        turn: &TurnRelayOptions,
+        now: Instant,
+    ) -> Result<Vec<(AttributeType, Vec<u8>)>, u16> {
+        if self.state.lock().unwrap().allocations.contains_key(&from) {
+            return Err(ALLOCATION_MISMATCH);
+        }
+        match message.attributes.get(&AttributeType::RequestedTransport) {
+            Some(attr) if attr.value.first() == Some(&REQUESTED_TRANSPORT_UDP) => {}
+            Some(_) => return Err(UNSUPPORTED_TRANSPORT_PROTOCOL),
+            None => return Err(BAD_REQUEST),
+        }
+        let relay = match UdpSocket::bind(SocketAddr::new(turn.relay_ip, 0)).await {
+            Ok(relay) => Arc::new(relay),
+            Err(err) => {
+                warn!("failed to bind relay socket: {err}");
+                return Err(BAD_REQUEST);
+            }
+        };
+        let relayed_address = match relay.local_addr() {
+            Ok(addr) if addr.ip().is_unspecified() => {
+                let local_ip = match addr {
+                    SocketAddr::V4(_) => local_ip(),
+                    SocketAddr::V6(_) => local_ipv6(),
+                };
+                match local_ip {
+                    Ok(ip) => SocketAddr::new(ip, addr.port()),
+                    Err(_) => return Err(BAD_REQUEST),
+                }
+            }
+            Ok(addr) => addr,
+            Err(_) => return Err(BAD_REQUEST),
+        };
+        let lifetime = requested_lifetime(message);
+        let relay_loop_handle = tokio::spawn(relay_loop(
+            relay.clone(),
+            from,
+            self.socket.clone(),
+            self.state.clone(),
+        ));
+        self.state.lock().unwrap().allocations.insert(
+            from,
+            ServerAllocation {
+                relayed_address,
+                expires_at: now + lifetime,
+                permissions: HashMap::new(),
+                channels: HashMap::new(),
+                relay,
+                relay_loop_handle,
+            },
+        );
+        info!("turn allocation created; client={from}, relayed_address={relayed_address}");
+
+        let transaction_id = &message.transaction_id;
+        Ok(vec![
+            (
+                AttributeType::XorRelayedAddress,
+                encode_xor_mapped_address(relayed_address, transaction_id),
+            ),
+            (
+                AttributeType::XorMappedAddress,
+                encode_xor_mapped_address(from, transaction_id),
+            ),
+            (
+                AttributeType::Lifetime,
+                (lifetime.as_secs() as u32).to_be_bytes().to_vec(),
+            ),
+        ])
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8656#section-8.2
+    fn refresh(
+        &self,
+        message: &StunMessage,
+        from: SocketAddr,
+        now: Instant,
+    ) -> Result<Vec<(AttributeType, Vec<u8>)>, u16> {
+        let mut state = self.state.lock().unwrap();
+        if !state.allocations.contains_key(&from) {
+            return Err(ALLOCATION_MISMATCH);
+        }
+        let lifetime = requested_lifetime(message);
+        if lifetime.is_zero() {
+            info!("turn allocation deleted; client={from}");
+            state.allocations.remove(&from);
+        } else if let Some(allocation) = state.allocations.get_mut(&from) {
+            allocation.expires_at = now + lifetime;
+        }
+        Ok(vec![(
+            AttributeType::Lifetime,
+            (lifetime.as_secs() as u32).to_be_bytes().to_vec(),
+        )])
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8656#section-10.2
+    fn create_permission(
+        &self,
+        message: &StunMessage,
+        from: SocketAddr,
+        now: Instant,
+    ) -> Result<Vec<(AttributeType, Vec<u8>)>, u16> {
+        let peer =
+            read_address_attr(message, AttributeType::XorPeerAddress).map_err(|_| BAD_REQUEST)?;
+        let mut state = self.state.lock().unwrap();
+        let allocation = state
+            .allocations
+            .get_mut(&from)
+            .ok_or(ALLOCATION_MISMATCH)?;
+        allocation
+            .permissions
+            .insert(peer.ip(), now + PERMISSION_LIFETIME);
+        Ok(vec![])
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8656#section-12.2
+    fn channel_bind(
+        &self,
+        message: &StunMessage,
+        from: SocketAddr,
+        now: Instant,
+    ) -> Result<Vec<(AttributeType, Vec<u8>)>, u16> {
+        let peer =
+            read_address_attr(message, AttributeType::XorPeerAddress).map_err(|_| BAD_REQUEST)?;
+        let channel_number = message
+            .attributes
+            .get(&AttributeType::ChannelNumber)
+            .and_then(|attr| attr.value.get(..2))
+            .map(|value| u16::from_be_bytes([value[0], value[1]]))
+            .filter(|number| (MIN_CHANNEL_NUMBER..=MAX_CHANNEL_NUMBER).contains(number))
+            .ok_or(BAD_REQUEST)?;
+        let mut state = self.state.lock().unwrap();
+        let allocation = state
+            .allocations
+            .get_mut(&from)
+            .ok_or(ALLOCATION_MISMATCH)?;
+        // a channel stays bound to one peer and a peer to one channel.
+        let bound_peer = allocation
+            .channels
+            .get(&channel_number)
+            .map(|(peer, _)| *peer);
+        let bound_channel = allocation.channel_of(peer);
+        if bound_peer.is_some_and(|bound| bound != peer)
+            || bound_channel.is_some_and(|bound| bound != channel_number)
+        {
+            return Err(BAD_REQUEST);
+        }
+        allocation
+            .channels
+            .insert(channel_number, (peer, now + CHANNEL_LIFETIME));
+        allocation
+            .permissions
+            .insert(peer.ip(), now + PERMISSION_LIFETIME);
+        Ok(vec![])
+    }
+
+    fn remove_expired(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.allocations.retain(|client, allocation| {
+            allocation
+                .permissions
+                .retain(|_, expires_at| *expires_at > now);
+            allocation
+                .channels
+                .retain(|_, (_, expires_at)| *expires_at > now);
+            let alive = allocation.expires_at > now;
+            if !alive {
+                info!(
+                    "turn allocation expired; client={client}, relayed_address={}",
+                    allocation.relayed_address
+                );
+            }
+            alive
+        });
+    }
+}
+
+impl Drop for StunServer {
+    fn drop(&mut self) {
+        // stops the relay loops, which hold the state themselves.
+        self.state.lock().unwrap().allocations.clear();
+    }
+}
+
+// Forwards datagrams from permitted peers to the client; over a channel when one is bound.
+// https://datatracker.ietf.org/doc/html/rfc8656#section-11.3
+async fn relay_loop(
+    relay: Arc<UdpSocket>,
+    client: SocketAddr,
+    socket: Arc<UdpSocket>,
+    state: Arc<StdMutex<RelayState>>,
+) {
+    let mut buf = vec![0u8; 65535];
+    loop {
+        let (len, peer) = match relay.recv_from(&mut buf).await {
+            Ok((len, peer)) => (len, canonical_addr(peer)),
+            Err(err) => {
+                debug!("failed to receive relayed datagram: {err}");
+                continue;
+            }
+        };
+        let data = &buf[..len];
+        let channel_number = {
+            let state = state.lock().unwrap();
+            let Some(allocation) = state.allocations.get(&client) else {
+                return;
+            };
+            if !allocation.is_permitted(peer.ip(), Instant::now()) {
+                debug!("no permission for peer; drop relayed datagram; peer={peer}");
+                continue;
+            }
+            allocation.channel_of(peer)
+        };
+        let message = match channel_number {
+            Some(channel_number) => encode_channel_data(channel_number, data),
+            None => {
+                let transaction_id = generate_transaction_id();
+                StunMessageBuilder::new(
+                    StunMessageType {
+                        method: StunMessageMethod::Data,
+                        class: StunMessageClass::Indication,
+                    },
+                    transaction_id.clone(),
+                )
+                .add_attr(
+                    AttributeType::XorPeerAddress,
+                    &encode_xor_mapped_address(peer, &transaction_id),
+                )
+                .add_attr(AttributeType::Data, data)
+                .build_without_integrity()
+                .raw
+            }
+        };
+        if let Err(err) = send_to(&socket, &message, client).await {
+            debug!("failed to send relayed datagram to {client}: {err}");
+        }
+    }
+}
+
+// `socket` may be a dual-stack IPv6 socket that reaches IPv4 peers by mapped addresses.
+async fn send_to(socket: &UdpSocket, data: &[u8], to: SocketAddr) -> Result<()> {
+    socket
+        .send_to(data, socket_peer_addr(socket.local_addr()?, to))
+        .await?;
+    Ok(())
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8489#section-3
+fn binding_response(message: &StunMessage, from: SocketAddr) -> Vec<u8> {
+    StunMessageBuilder::new(
+        StunMessageType {
+            method: StunMessageMethod::Binding,
+            class: StunMessageClass::SuccessResponse,
+        },
+        message.transaction_id.clone(),
+    )
+    .add_attr(
+        AttributeType::XorMappedAddress,
+        &encode_xor_mapped_address(from, &message.transaction_id),
+    )
+    .build_without_integrity()
+    .raw
+}
+
+fn error_response(message: &StunMessage, code: u16, key: Option<&[u8]>) -> Vec<u8> {
+    let builder = StunMessageBuilder::new(
+        StunMessageType {
+            method: message.message_type.method,
+            class: StunMessageClass::ErrorResponse,
+        },
+        message.transaction_id.clone(),
+    )
+    .add_attr(AttributeType::ErrorCode, &encode_error_code(code, ""));
+    match key {
+        Some(key) => builder.build_with_key(key).raw,
+        None => builder.build_without_integrity().raw,
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8656#section-7.2
+fn requested_lifetime(message: &StunMessage) -> Duration {
+    message
+        .attributes
+        .get(&AttributeType::Lifetime)
+        .and_then(|attr| <[u8; 4]>::try_from(attr.value.as_slice()).ok())
+        .map(|value| Duration::from_secs(u32::from_be_bytes(value) as u64))
+        .map(|lifetime| {
+            if lifetime.is_zero() {
+                lifetime
+            } else {
+                lifetime.clamp(DEFAULT_LIFETIME, MAX_LIFETIME)
+            }
+        })
+        .unwrap_or(DEFAULT_LIFETIME)
+}
+
+#[cfg(test)]
+mod stun_server_tests {
+    use super::*;
+    use crate::stun::decode_error_code;
+    use crate::turn::TurnClient;
+    use crate::udp_server::UdpServer;
+    use tokio::time::{sleep, timeout};
+
+    async fn spawn_stun_server(turn: Option<TurnRelayOptions>) -> Result<SocketAddr> {
+        let server = StunServer::bind(StunServerOptions {
+            bind_address: "127.0.0.1:0".parse()?,
+            turn,
+        })
+        .await?;
+        let server_addr = server.local_addr()?;
+        tokio::spawn(async move { server.run().await });
+        Ok(server_addr)
+    }
+
+    #[tokio::test]
+    async fn test_binding() -> Result<()> {
+        let server_addr = spawn_stun_server(None).await?;
+        let udp_server = UdpServer::bind("127.0.0.1:0".parse()?).await?;
+        let mapped_address = udp_server.binding_request(server_addr).await?;
+        assert_eq!(mapped_address, udp_server.local_addr()?);
+
+        // TURN requests are rejected when the relay is disabled
+        let mut turn_client = TurnClient::bind(server_addr, "user", "pass").await?;
+        assert!(turn_client.allocate().await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_turn_relay() -> Result<()> {
+        let turn = TurnRelayOptions {
+            relay_ip: "127.0.0.1".parse()?,
+            ..TurnRelayOptions::new("user", "pass")
+        };
+        let server_addr = spawn_stun_server(Some(turn)).await?;
+        let mut turn_client = TurnClient::bind(server_addr, "user", "pass").await?;
+        let relayed_address = turn_client.allocate().await?;
+        assert_eq!(relayed_address.ip(), server_addr.ip());
+
+        let peer = UdpSocket::bind("127.0.0.1:0").await?;
+        let peer_addr = peer.local_addr()?;
+        let mut buf = vec![0u8; 1500];
+
+        turn_client.send_to(b"hello", peer_addr).await?;
+        let (len, from) = timeout(Duration::from_secs(1), peer.recv_from(&mut buf)).await??;
+        assert_eq!((&buf[..len], from), (&b"hello"[..], relayed_address));
+        peer.send_to(b"world", relayed_address).await?;
+        let message = timeout(Duration::from_secs(1), turn_client.recv())
+            .await?
+            .ok_or(anyhow!("turn client closed"))?;
+        assert_eq!(
+            (message.peer_addr, message.data),
+            (peer_addr, b"world".to_vec())
+        );
+
+        turn_client.channel_bind(peer_addr).await?;
+        turn_client.send_to(b"over channel", peer_addr).await?;
+        let (len, _) = timeout(Duration::from_secs(1), peer.recv_from(&mut buf)).await??;
+        assert_eq!(&buf[..len], b"over channel");
+        peer.send_to(b"back over channel", relayed_address).await?;
+        let message = timeout(Duration::from_secs(1), turn_client.recv())
+            .await?
+            .ok_or(anyhow!("turn client closed"))?;
+        assert_eq!(message.data, b"back over channel");
+
+        // a second allocation from the same client is a mismatch; deleting it frees the 5-tuple
+        assert!(turn_client.allocate().await.is_err());
+        turn_client.refresh(Duration::ZERO).await?;
+        turn_client.allocate().await?;
+
+        let mut wrong_client = TurnClient::bind(server_addr, "user", "wrong").await?;
+        assert!(wrong_client.allocate().await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stale_nonce() -> Result<()> {
+        let turn = TurnRelayOptions {
+            relay_ip: "127.0.0.1".parse()?,
+            nonce_lifetime: Duration::from_millis(200),
+            ..TurnRelayOptions::new("user", "pass")
+        };
+        let key = long_term_key(&turn.username, &turn.realm, &turn.password);
+        let server_addr = spawn_stun_server(Some(turn)).await?;
+        let socket = UdpSocket::bind("127.0.0.1:0").await?;
+        let refresh = async |nonce: Option<&str>| -> Result<StunMessage> {
+            let builder = StunMessageBuilder::new(
+                StunMessageType {
+                    method: StunMessageMethod::Refresh,
+                    class: StunMessageClass::Request,
+                },
+                generate_transaction_id(),
+            );
+            let request = match nonce {
+                Some(nonce) => builder
+                    .add_attr(AttributeType::Username, b"user")
+                    .add_attr(AttributeType::Realm, DEFAULT_REALM.as_bytes())
+                    .add_attr(AttributeType::Nonce, nonce.as_bytes())
+                    .build_with_key(&key),
+                None => builder.build_without_integrity(),
+            };
+            socket.send_to(&request.raw, server_addr).await?;
+            let mut buf = vec![0u8; 1500];
+            let (len, _) = timeout(Duration::from_secs(1), socket.recv_from(&mut buf)).await??;
+            StunMessage::decode(&mut BufReader::new(&buf[..len]))
+        };
+        let error_code = |response: &StunMessage| {
+            decode_error_code(&response.attributes[&AttributeType::ErrorCode].value)
+        };
+
+        let challenge = refresh(None).await?;
+        assert_eq!(error_code(&challenge)?, UNAUTHORIZED);
+        let nonce = read_string_attr(&challenge, AttributeType::Nonce);
+        sleep(Duration::from_millis(300)).await;
+        let stale = refresh(nonce.as_deref()).await?;
+        assert_eq!(error_code(&stale)?, STALE_NONCE);
+        assert_ne!(read_string_attr(&stale, AttributeType::Nonce), nonce);
+
+        // the client retries with the fresh nonce of the 438 response
+        let mut turn_client = TurnClient::bind(server_addr, "user", "pass").await?;
+        turn_client.allocate().await?;
+        sleep(Duration::from_millis(300)).await;
+        turn_client.refresh(DEFAULT_LIFETIME).await?;
+        Ok(())
+    }
+}
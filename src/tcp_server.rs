@@ -0,0 +1,300 @@
+use crate::common::TransportMessage;
+use crate::stun::StunMessage;
+use crate::udp_server::{canonical_addr, local_ufrag_of_stun_message};
+use anyhow::{Result, anyhow};
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tracing::{debug, info};
+
+const LISTEN_BACKLOG: i32 = 128;
+// a connectivity check is retransmitted for about as long before it fails.
+const FIRST_FRAME_TIMEOUT: Duration = Duration::from_secs(40);
+
+// Routes are removed from `UdpTransport::drop`, which cannot await; hence the std mutex.
+#[derive(Default)]
+struct Routes {
+    // local ufrag -> inbound channel of the connection
+    by_ufrag: HashMap<String, mpsc::UnboundedSender<TransportMessage>>,
+    // remote address of an accepted tcp connection -> (local ufrag, outbound frames)
+    connections: HashMap<SocketAddr, (String, mpsc::UnboundedSender<Vec<u8>>)>,
+}
+
+/// TCP listener of passive ICE-TCP candidates shared by any number of connections.
+/// The first frame of an accepted tcp connection has to be a stun binding request; the local
+/// ufrag in its USERNAME routes every frame of the tcp connection.
+// https://datatracker.ietf.org/doc/html/rfc6544
+pub struct TcpServer {
+    local_addr: SocketAddr,
+    dual_stack: bool,
+    routes: Arc<StdMutex<Routes>>,
+    accept_loop_handle: JoinHandle<()>,
+}
+
+impl TcpServer {
+    pub fn new(listener: TcpListener) -> Result<Arc<Self>> {
+        let local_addr = listener.local_addr()?;
+        let dual_stack = local_addr.is_ipv6()
+            && SockRef::from(&listener)
+                .only_v6()
+                .is_ok_and(|only_v6| !only_v6);
+        info!("Tcp Server listening on {}", local_addr);
+
+        let routes = Arc::new(StdMutex::new(Routes::default()));
+        let accept_loop_handle = tokio::spawn(accept_loop(listener, routes.clone()));
+
+        Ok(Arc::new(TcpServer {
+            local_addr,
+            dual_stack,
+            routes,
+            accept_loop_handle,
+        }))
+    }
+
+    pub async fn bind(addr: SocketAddr) -> Result<Arc<Self>> {
+        Self::new(bind_tcp_listener(addr)?)
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+
+    /// Whether the listener is bound to `[::]` and accepts IPv4 connections too.
+    pub fn is_dual_stack(&self) -> bool {
+        self.dual_stack
+    }
+
+    /// Sends a frame over the tcp connection accepted from `peer_addr`.
+    pub fn send(&self, data: &[u8], peer_addr: SocketAddr) -> Result<()> {
+        let routes = self.routes.lock().unwrap();
+        let (_, tx) = routes
+            .connections
+            .get(&peer_addr)
+            .ok_or(anyhow!("no tcp connection; peer={peer_addr}"))?;
+        tx.send(data.to_vec())
+            .map_err(|_| anyhow!("tcp connection closed; peer={peer_addr}"))?;
+        debug!("Sent {} bytes to {} over tcp", data.len(), peer_addr);
+        Ok(())
+    }
+
+    /// Registers a connection identified by its local ufrag and returns its inbound channel.
+    pub fn register(&self, local_ufrag: &str) -> mpsc::UnboundedReceiver<TransportMessage> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.routes
+            .lock()
+            .unwrap()
+            .by_ufrag
+            .insert(local_ufrag.to_string(), tx);
+        rx
+    }
+
+    /// Unregisters the connection and closes its tcp connections.
+    pub fn unregister(&self, local_ufrag: &str) {
+        let mut routes = self.routes.lock().unwrap();
+        routes.by_ufrag.remove(local_ufrag);
+        routes
+            .connections
+            .retain(|_, (ufrag, _)| ufrag != local_ufrag);
+    }
+}
+
+impl Drop for TcpServer {
+    fn drop(&mut self) {
+        self.accept_loop_handle.abort();
+        // dropping the outbound channels ends the tasks of accepted tcp connections.
+        self.routes.lock().unwrap().connections.clear();
+    }
+}
+
+/// Binds a tcp listener; the unspecified IPv6 address `[::]` also accepts IPv4 connections.
+pub fn bind_tcp_listener(addr: SocketAddr) -> Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.ip() == IpAddr::V6(Ipv6Addr::UNSPECIFIED) {
+        socket.set_only_v6(false)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(LISTEN_BACKLOG)?;
+    Ok(TcpListener::from_std(socket.into())?)
+}
+
+async fn accept_loop(listener: TcpListener, routes: Arc<StdMutex<Routes>>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer_addr)) => {
+                let peer_addr = canonical_addr(peer_addr);
+                debug!("Accepted tcp connection from {}", peer_addr);
+                tokio::spawn(serve_connection(stream, peer_addr, routes.clone()));
+            }
+            Err(err) => debug!("failed to accept tcp connection: {err}"),
+        }
+    }
+}
+
+async fn serve_connection(stream: TcpStream, peer_addr: SocketAddr, routes: Arc<StdMutex<Routes>>) {
+    let _ = stream.set_nodelay(true);
+    let (mut reader, mut writer) = stream.into_split();
+
+    // https://datatracker.ietf.org/doc/html/rfc6544#section-7.1
+    // the active side starts with a connectivity check.
+    let first_frame = match timeout(FIRST_FRAME_TIMEOUT, read_frame(&mut reader)).await {
+        Ok(Ok(frame)) => frame,
+        Ok(Err(err)) => {
+            debug!("failed to read the first frame; peer={peer_addr}: {err}");
+            return;
+        }
+        Err(_) => {
+            debug!("no first frame in time; close tcp connection; peer={peer_addr}");
+            return;
+        }
+    };
+    let Some(local_ufrag) = StunMessage::is_stun_message(&first_frame)
+        .then(|| local_ufrag_of_stun_message(&first_frame))
+        .flatten()
+    else {
+        debug!("first frame is not a binding request; close tcp connection; peer={peer_addr}");
+        return;
+    };
+    let (outbound_tx, mut outbound_rx) = mpsc::unbounded_channel::<Vec<u8>>();
+    let inbound_tx = {
+        let mut routes = routes.lock().unwrap();
+        let inbound_tx = routes.by_ufrag.get(&local_ufrag).cloned();
+        if inbound_tx.is_some() {
+            routes
+                .connections
+                .insert(peer_addr, (local_ufrag.clone(), outbound_tx));
+        }
+        inbound_tx
+    };
+    let Some(inbound_tx) = inbound_tx else {
+        debug!("no route for tcp connection; peer={peer_addr}, local_ufrag={local_ufrag}");
+        return;
+    };
+
+    let write_loop = async {
+        while let Some(data) = outbound_rx.recv().await {
+            writer.write_all(&encode_frame(&data)?).await?;
+        }
+        anyhow::Ok(())
+    };
+    let read_loop = async {
+        let mut frame = first_frame;
+        loop {
+            debug!("Received {} bytes from {} over tcp", frame.len(), peer_addr);
+            let message = TransportMessage {
+                peer_addr,
+                data: frame,
+            };
+            if inbound_tx.send(message).is_err() {
+                return anyhow::Ok(());
+            }
+            frame = read_frame(&mut reader).await?;
+        }
+    };
+    let result = tokio::select! {
+        result = write_loop => result,
+        result = read_loop => result,
+    };
+    if let Err(err) = result {
+        debug!("tcp connection failed; peer={peer_addr}: {err}");
+    }
+    debug!("tcp connection closed; peer={peer_addr}");
+    let mut routes = routes.lock().unwrap();
+    if routes
+        .connections
+        .get(&peer_addr)
+        .is_some_and(|(ufrag, _)| *ufrag == local_ufrag)
+    {
+        routes.connections.remove(&peer_addr);
+    }
+}
+
+/// Prefixes `data` with its 16-bit length.
+// https://datatracker.ietf.org/doc/html/rfc4571#section-2
+pub fn encode_frame(data: &[u8]) -> Result<Vec<u8>> {
+    let length = u16::try_from(data.len())
+        .map_err(|_| anyhow!("frame is too long; length={}", data.len()))?;
+    let mut frame = length.to_be_bytes().to_vec();
+    frame.extend_from_slice(data);
+    Ok(frame)
+}
+
+pub async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> Result<Vec<u8>> {
+    let length = reader.read_u16().await?;
+    let mut data = vec![0u8; length as usize];
+    reader.read_exact(&mut data).await?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tcp_server_tests {
+    use super::*;
+    use crate::stun::{
+        AttributeType, StunMessageBuilder, StunMessageClass, StunMessageMethod, StunMessageType,
+    };
+
+    fn binding_request(username: &str) -> Vec<u8> {
+        StunMessageBuilder::new(
+            StunMessageType {
+                method: StunMessageMethod::Binding,
+                class: StunMessageClass::Request,
+            },
+            vec![1u8; 12],
+        )
+        .add_attr(AttributeType::Username, username.as_bytes())
+        .build("pwd".to_string())
+        .raw
+    }
+
+    #[tokio::test]
+    async fn test_framed_connection() -> Result<()> {
+        let server = TcpServer::bind("127.0.0.1:0".parse()?).await?;
+        let mut rx = server.register("ufrag");
+
+        // the first frame routes the tcp connection by the local ufrag
+        let mut client = TcpStream::connect(server.local_addr()?).await?;
+        let request = binding_request("ufrag:remote");
+        client.write_all(&encode_frame(&request)?).await?;
+        let message = timeout(Duration::from_secs(1), rx.recv())
+            .await?
+            .ok_or(anyhow!("inbound channel closed"))?;
+        assert_eq!(message.peer_addr, client.local_addr()?);
+        assert_eq!(message.data, request);
+
+        // later frames follow it whatever they are
+        client.write_all(&encode_frame(b"dtls")?).await?;
+        let message = timeout(Duration::from_secs(1), rx.recv()).await?.unwrap();
+        assert_eq!(message.data, b"dtls");
+
+        server.send(b"response", message.peer_addr)?;
+        let frame = timeout(Duration::from_secs(1), read_frame(&mut client)).await??;
+        assert_eq!(frame, b"response");
+
+        // tcp connections of unknown ufrags are closed
+        let mut stranger = TcpStream::connect(server.local_addr()?).await?;
+        stranger
+            .write_all(&encode_frame(&binding_request("unknown:remote"))?)
+            .await?;
+        let mut buf = [0u8; 1];
+        assert_eq!(
+            timeout(Duration::from_secs(1), stranger.read(&mut buf)).await??,
+            0
+        );
+
+        // unregistering closes the tcp connection
+        server.unregister("ufrag");
+        assert_eq!(
+            timeout(Duration::from_secs(1), client.read(&mut buf)).await??,
+            0
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,943 @@
+use anyhow::{Result, anyhow, bail};
+use md5::{Digest, Md5};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
+use tracing::{debug, info, warn};
+
+use crate::common::TransportMessage;
+use crate::common::buffer::{BufReader, BufWriter};
+use crate::stun::{
+    AttributeType, StunMessage, StunMessageBuilder, StunMessageClass, StunMessageMethod,
+    StunMessageType, decode_error_code, decode_xor_mapped_address, encode_xor_mapped_address,
+    generate_transaction_id,
+};
+
+// https://datatracker.ietf.org/doc/html/rfc8656#section-7.2
+pub const DEFAULT_LIFETIME: Duration = Duration::from_secs(600);
+// https://datatracker.ietf.org/doc/html/rfc8656#section-9
+pub const PERMISSION_LIFETIME: Duration = Duration::from_secs(300);
+// https://datatracker.ietf.org/doc/html/rfc8656#section-12
+pub const CHANNEL_LIFETIME: Duration = Duration::from_secs(600);
+// allocations, permissions and channels are refreshed this long before they expire.
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+// a failed refresh is retried after this long, well within the margin.
+const REFRESH_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+// https://datatracker.ietf.org/doc/html/rfc8489#section-6.2.1
+const INITIAL_RTO: Duration = Duration::from_millis(500);
+const MAX_TRANSMISSIONS: u32 = 4;
+// a 401 without credentials and a 438 stale nonce may each precede the accepted request.
+const MAX_AUTH_ATTEMPTS: u32 = 3;
+// https://datatracker.ietf.org/doc/html/rfc8656#section-18.7
+pub const REQUESTED_TRANSPORT_UDP: u8 = 17;
+// https://datatracker.ietf.org/doc/html/rfc8489#section-14.8
+pub const UNAUTHORIZED: u16 = 401;
+pub const STALE_NONCE: u16 = 438;
+
+// https://datatracker.ietf.org/doc/html/rfc8656#section-12
+pub const MIN_CHANNEL_NUMBER: u16 = 0x4000;
+pub const MAX_CHANNEL_NUMBER: u16 = 0x4fff;
+const CHANNEL_DATA_HEADER_BYTES: usize = 4;
+
+/// Key of MESSAGE-INTEGRITY for long-term credentials.
+// https://datatracker.ietf.org/doc/html/rfc8489#section-9.2.2
+pub fn long_term_key(username: &str, realm: &str, password: &str) -> Vec<u8> {
+    Md5::digest(format!("{username}:{realm}:{password}").as_bytes()).to_vec()
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7983#section-7
+pub fn is_channel_data(buf: &[u8]) -> bool {
+    buf.len() >= CHANNEL_DATA_HEADER_BYTES && (0x40..=0x4f).contains(&buf[0])
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8656#section-12.4
+pub fn encode_channel_data(channel_number: u16, data: &[u8]) -> Vec<u8> {
+    let mut writer = BufWriter::new();
+    writer.write_u16(channel_number);
+    writer.write_u16(data.len() as u16);
+    writer.write_bytes(data);
+    // padding is optional over udp but always sent, like stun attributes.
+    let padding = (4 - (data.len() % 4)) % 4;
+    if padding > 0 {
+        writer.write_bytes(&vec![0u8; padding]);
+    }
+    writer.buf()
+}
+
+pub fn decode_channel_data(buf: &[u8]) -> Result<(u16, Vec<u8>)> {
+    let mut reader = BufReader::new(buf);
+    let channel_number = reader.read_u16()?;
+    if !(MIN_CHANNEL_NUMBER..=MAX_CHANNEL_NUMBER).contains(&channel_number) {
+        return Err(anyhow!(
+            "invalid channel number; channel_number=0x{channel_number:04x}"
+        ));
+    }
+    let length = reader.read_u16()? as usize;
+    let mut data = vec![0u8; length];
+    reader.read_exact(&mut data)?;
+    Ok((channel_number, data))
+}
+
+// something kept alive by a request to the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Refresh {
+    Allocation,
+    Permission(IpAddr),
+    Channel(SocketAddr),
+}
+
+#[derive(Default)]
+struct Allocation {
+    realm: Option<String>,
+    nonce: Option<String>,
+    key: Option<Vec<u8>>,
+    expires_at: Option<Instant>,
+    // peer ip -> expiry of the permission
+    permissions: HashMap<IpAddr, Instant>,
+    // peer address -> (channel number, expiry of the binding)
+    channels: HashMap<SocketAddr, (u16, Instant)>,
+    // transaction id of a pending request -> response channel
+    transactions: HashMap<Vec<u8>, oneshot::Sender<StunMessage>>,
+    // requests running in the background, or waiting to be retried after failing
+    refreshing: HashSet<Refresh>,
+}
+
+impl Allocation {
+    fn channel_peer(&self, channel_number: u16) -> Option<SocketAddr> {
+        self.channels
+            .iter()
+            .find(|(_, (number, _))| *number == channel_number)
+            .map(|(peer, _)| *peer)
+    }
+
+    // expiry of everything refreshed, unless a refresh of it is already running
+    fn expirations(&self) -> impl Iterator<Item = (Refresh, Instant)> + '_ {
+        let permissions = self
+            .permissions
+            .iter()
+            .map(|(peer, expires_at)| (Refresh::Permission(*peer), *expires_at));
+        let channels = self
+            .channels
+            .iter()
+            .map(|(peer, (_, expires_at))| (Refresh::Channel(*peer), *expires_at));
+        self.expires_at
+            .map(|expires_at| (Refresh::Allocation, expires_at))
+            .into_iter()
+            .chain(permissions.filter(|_| self.expires_at.is_some()))
+            .chain(channels.filter(|_| self.expires_at.is_some()))
+            .filter(|(refresh, _)| !self.refreshing.contains(refresh))
+    }
+}
+
+/// Client of a single UDP allocation on a TURN server, on a socket of its own.
+/// Datagrams relayed from peers arrive in Data indications or ChannelData messages and
+/// are handed out by `recv` with the address of the peer.
+// https://datatracker.ietf.org/doc/html/rfc8656
+pub struct TurnClient {
+    pub server: SocketAddr,
+    relayed_address: Option<SocketAddr>,
+    mapped_address: Option<SocketAddr>,
+    requester: Requester,
+    inbound_rx: mpsc::UnboundedReceiver<TransportMessage>,
+    recv_loop_handle: JoinHandle<()>,
+}
+
+// The part of the client sending requests; cloned into the tasks that keep the allocation
+// alive, so that the event loop of the connection does not wait for the server.
+#[derive(Clone)]
+struct Requester {
+    server: SocketAddr,
+    username: String,
+    password: String,
+    socket: Arc<UdpSocket>,
+    allocation: Arc<StdMutex<Allocation>>,
+}
+
+impl TurnClient {
+    pub fn new(socket: UdpSocket, server: SocketAddr, username: &str, password: &str) -> Self {
+        let socket = Arc::new(socket);
+        let allocation = Arc::new(StdMutex::new(Allocation::default()));
+        let (inbound_tx, inbound_rx) = mpsc::unbounded_channel();
+        let recv_loop_handle = tokio::spawn(recv_loop(
+            socket.clone(),
+            server,
+            allocation.clone(),
+            inbound_tx,
+        ));
+        Self {
+            server,
+            relayed_address: None,
+            mapped_address: None,
+            requester: Requester {
+                server,
+                username: username.to_string(),
+                password: password.to_string(),
+                socket,
+                allocation,
+            },
+            inbound_rx,
+            recv_loop_handle,
+        }
+    }
+
+    /// Creates a client on an ephemeral port of the address family of `server`.
+    pub async fn bind(server: SocketAddr, username: &str, password: &str) -> Result<Self> {
+        let ip = match server {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let socket = UdpSocket::bind(SocketAddr::new(ip, 0)).await?;
+        Ok(Self::new(socket, server, username, password))
+    }
+
+    /// Address of the allocation on the server; the address of the relay candidate.
+    pub fn relayed_address(&self) -> Option<SocketAddr> {
+        self.relayed_address
+    }
+
+    /// Server reflexive address of this client as seen by the server.
+    pub fn mapped_address(&self) -> Option<SocketAddr> {
+        self.mapped_address
+    }
+
+    /// Returns the next datagram relayed from a peer; `None` when the socket is gone.
+    pub async fn recv(&mut self) -> Option<TransportMessage> {
+        self.inbound_rx.recv().await
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8656#section-7.1
+    pub async fn allocate(&mut self) -> Result<SocketAddr> {
+        let response = self
+            .requester
+            .request(
+                StunMessageMethod::Allocate,
+                None,
+                &[
+                    (
+                        AttributeType::RequestedTransport,
+                        vec![REQUESTED_TRANSPORT_UDP, 0, 0, 0],
+                    ),
+                    (
+                        AttributeType::Lifetime,
+                        (DEFAULT_LIFETIME.as_secs() as u32).to_be_bytes().to_vec(),
+                    ),
+                ],
+            )
+            .await?;
+        let relayed_address = read_address_attr(&response, AttributeType::XorRelayedAddress)?;
+        let mapped_address = read_address_attr(&response, AttributeType::XorMappedAddress)?;
+        self.requester.update_lifetime(&response);
+        info!(
+            "turn allocation created; server={}, relayed_address={relayed_address}",
+            self.server
+        );
+        self.relayed_address = Some(relayed_address);
+        self.mapped_address = Some(mapped_address);
+        Ok(relayed_address)
+    }
+
+    /// Extends the allocation; a zero `lifetime` deletes it.
+    // https://datatracker.ietf.org/doc/html/rfc8656#section-8.1
+    pub async fn refresh(&self, lifetime: Duration) -> Result<()> {
+        self.requester.refresh(lifetime).await
+    }
+
+    /// Lets datagrams from any port of `peer` through the allocation.
+    // https://datatracker.ietf.org/doc/html/rfc8656#section-10.1
+    pub async fn create_permission(&self, peer: IpAddr) -> Result<()> {
+        self.requester.create_permission(peer).await
+    }
+
+    /// Binds a channel to `peer` (or refreshes its binding) and returns the channel number.
+    // https://datatracker.ietf.org/doc/html/rfc8656#section-12.1
+    pub async fn channel_bind(&self, peer: SocketAddr) -> Result<u16> {
+        self.requester.channel_bind(peer).await
+    }
+
+    /// Binds a channel to `peer` in the background; `send_to` uses Send indications until
+    /// the binding is in place.
+    pub fn start_channel_bind(&self, peer: SocketAddr) {
+        self.spawn_refresh(Refresh::Channel(peer));
+    }
+
+    pub fn has_channel(&self, peer: SocketAddr) -> bool {
+        self.requester
+            .allocation
+            .lock()
+            .unwrap()
+            .channels
+            .contains_key(&peer)
+    }
+
+    /// Relays `data` to `peer` in a ChannelData message when a channel is bound to it,
+    /// otherwise in a Send indication; one to a peer without a permission follows in the
+    /// background once the permission is created.
+    pub async fn send_to(&self, data: &[u8], peer: SocketAddr) -> Result<()> {
+        let (channel_number, permitted) = {
+            let allocation = self.requester.allocation.lock().unwrap();
+            (
+                allocation
+                    .channels
+                    .get(&peer)
+                    .map(|(channel_number, _)| *channel_number),
+                allocation.permissions.contains_key(&peer.ip()),
+            )
+        };
+        if let Some(channel_number) = channel_number {
+            self.requester
+                .socket
+                .send_to(&encode_channel_data(channel_number, data), self.server)
+                .await?;
+            return Ok(());
+        }
+        if !permitted {
+            let requester = self.requester.clone();
+            let data = data.to_vec();
+            tokio::spawn(async move {
+                let result = match requester.create_permission(peer.ip()).await {
+                    Ok(()) => requester.send_indication(&data, peer).await,
+                    Err(err) => Err(err),
+                };
+                if let Err(err) = result {
+                    warn!("failed to relay datagram; peer={peer}, {err:?}");
+                }
+            });
+            return Ok(());
+        }
+        self.requester.send_indication(data, peer).await
+    }
+
+    /// Refreshes the allocation, permissions and channel bindings that are about to expire;
+    /// each request runs in a task of its own.
+    pub fn handle_timeout(&self, now: Instant) {
+        let due = self
+            .requester
+            .allocation
+            .lock()
+            .unwrap()
+            .expirations()
+            .filter(|(_, expires_at)| *expires_at <= now + REFRESH_MARGIN)
+            .map(|(refresh, _)| refresh)
+            .collect::<Vec<_>>();
+        for refresh in due {
+            self.spawn_refresh(refresh);
+        }
+    }
+
+    pub fn next_timeout(&self) -> Option<Instant> {
+        self.requester
+            .allocation
+            .lock()
+            .unwrap()
+            .expirations()
+            .map(|(_, expires_at)| expires_at)
+            .min()
+            .map(|at| at - REFRESH_MARGIN)
+    }
+
+    fn spawn_refresh(&self, refresh: Refresh) {
+        if !self
+            .requester
+            .allocation
+            .lock()
+            .unwrap()
+            .refreshing
+            .insert(refresh)
+        {
+            return;
+        }
+        let requester = self.requester.clone();
+        tokio::spawn(async move {
+            let result = match refresh {
+                Refresh::Allocation => requester.refresh(DEFAULT_LIFETIME).await,
+                Refresh::Permission(peer) => requester.create_permission(peer).await,
+                Refresh::Channel(peer) => requester.channel_bind(peer).await.map(|_| ()),
+            };
+            if let Err(err) = result {
+                warn!(
+                    "turn request failed; retry in {REFRESH_RETRY_INTERVAL:?}; {refresh:?}, {err:?}"
+                );
+                sleep(REFRESH_RETRY_INTERVAL).await;
+            }
+            requester
+                .allocation
+                .lock()
+                .unwrap()
+                .refreshing
+                .remove(&refresh);
+        });
+    }
+}
+
+impl Requester {
+    async fn refresh(&self, lifetime: Duration) -> Result<()> {
+        let response = self
+            .request(
+                StunMessageMethod::Refresh,
+                None,
+                &[(
+                    AttributeType::Lifetime,
+                    (lifetime.as_secs() as u32).to_be_bytes().to_vec(),
+                )],
+            )
+            .await?;
+        if lifetime.is_zero() {
+            let mut allocation = self.allocation.lock().unwrap();
+            allocation.expires_at = None;
+            allocation.permissions.clear();
+            allocation.channels.clear();
+        } else {
+            self.update_lifetime(&response);
+        }
+        Ok(())
+    }
+
+    async fn create_permission(&self, peer: IpAddr) -> Result<()> {
+        self.request(
+            StunMessageMethod::CreatePermission,
+            Some(SocketAddr::new(peer, 0)),
+            &[],
+        )
+        .await?;
+        self.allocation
+            .lock()
+            .unwrap()
+            .permissions
+            .insert(peer, Instant::now() + PERMISSION_LIFETIME);
+        Ok(())
+    }
+
+    async fn channel_bind(&self, peer: SocketAddr) -> Result<u16> {
+        let channel_number = {
+            let allocation = self.allocation.lock().unwrap();
+            match allocation.channels.get(&peer) {
+                Some((channel_number, _)) => *channel_number,
+                None => (MIN_CHANNEL_NUMBER..=MAX_CHANNEL_NUMBER)
+                    .find(|number| allocation.channel_peer(*number).is_none())
+                    .ok_or(anyhow!("no channel number left; peer={peer}"))?,
+            }
+        };
+        self.request(
+            StunMessageMethod::ChannelBind,
+            Some(peer),
+            &[(
+                AttributeType::ChannelNumber,
+                [channel_number.to_be_bytes(), [0, 0]].concat(),
+            )],
+        )
+        .await?;
+        let now = Instant::now();
+        let mut allocation = self.allocation.lock().unwrap();
+        allocation
+            .channels
+            .insert(peer, (channel_number, now + CHANNEL_LIFETIME));
+        // a channel binding also installs a permission for the peer.
+        allocation
+            .permissions
+            .insert(peer.ip(), now + PERMISSION_LIFETIME);
+        Ok(channel_number)
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8656#section-11.1
+    async fn send_indication(&self, data: &[u8], peer: SocketAddr) -> Result<()> {
+        let transaction_id = generate_transaction_id();
+        let indication = StunMessageBuilder::new(
+            StunMessageType {
+                method: StunMessageMethod::Send,
+                class: StunMessageClass::Indication,
+            },
+            transaction_id.clone(),
+        )
+        .add_attr(
+            AttributeType::XorPeerAddress,
+            &encode_xor_mapped_address(peer, &transaction_id),
+        )
+        .add_attr(AttributeType::Data, data)
+        .build_without_integrity();
+        self.socket.send_to(&indication.raw, self.server).await?;
+        Ok(())
+    }
+
+    // Sends a request, retrying with the REALM and NONCE of 401 and 438 error responses.
+    // https://datatracker.ietf.org/doc/html/rfc8489#section-9.2.5
+    async fn request(
+        &self,
+        method: StunMessageMethod,
+        peer: Option<SocketAddr>,
+        attributes: &[(AttributeType, Vec<u8>)],
+    ) -> Result<StunMessage> {
+        for _ in 0..MAX_AUTH_ATTEMPTS {
+            let transaction_id = generate_transaction_id();
+            let mut builder = StunMessageBuilder::new(
+                StunMessageType {
+                    method,
+                    class: StunMessageClass::Request,
+                },
+                transaction_id.clone(),
+            );
+            if let Some(peer) = peer {
+                builder = builder.add_attr(
+                    AttributeType::XorPeerAddress,
+                    &encode_xor_mapped_address(peer, &transaction_id),
+                );
+            }
+            for (attr_type, value) in attributes {
+                builder = builder.add_attr(*attr_type, value);
+            }
+            let credentials = self.credentials();
+            let request = match &credentials {
+                Some((realm, nonce, key)) => builder
+                    .add_attr(AttributeType::Username, self.username.as_bytes())
+                    .add_attr(AttributeType::Realm, realm.as_bytes())
+                    .add_attr(AttributeType::Nonce, nonce.as_bytes())
+                    .build_with_key(key),
+                None => builder.build_without_integrity(),
+            };
+
+            let response = self.transact(&transaction_id, &request.raw).await?;
+            let success = matches!(
+                response.message_type.class,
+                StunMessageClass::SuccessResponse
+            );
+            if let Some((_, _, key)) = &credentials {
+                // https://datatracker.ietf.org/doc/html/rfc8489#section-9.2.5
+                // a success response to an authenticated request has to be authenticated.
+                let has_integrity = response
+                    .attributes
+                    .contains_key(&AttributeType::MessageIntegrity);
+                if success && !has_integrity {
+                    bail!(
+                        "turn response without message integrity; server={}",
+                        self.server
+                    );
+                }
+                if has_integrity && !response.verify_message_integrity_with_key(key)? {
+                    bail!(
+                        "turn response message integrity mismatch; server={}",
+                        self.server
+                    );
+                }
+            }
+            if success {
+                return Ok(response);
+            }
+
+            let code = response
+                .attributes
+                .get(&AttributeType::ErrorCode)
+                .map(|attr| decode_error_code(&attr.value))
+                .transpose()?;
+            let retry = match code {
+                // the first request carries no credentials; later ones were rejected.
+                Some(UNAUTHORIZED) => credentials.is_none(),
+                Some(STALE_NONCE) => true,
+                _ => false,
+            };
+            if !retry {
+                bail!("turn request failed; server={}, code={code:?}", self.server);
+            }
+            self.update_credentials(&response)?;
+        }
+        Err(anyhow!(
+            "turn authentication failed; server={}",
+            self.server
+        ))
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8489#section-6.2.1
+    async fn transact(&self, transaction_id: &[u8], request: &[u8]) -> Result<StunMessage> {
+        let (tx, mut rx) = oneshot::channel();
+        self.allocation
+            .lock()
+            .unwrap()
+            .transactions
+            .insert(transaction_id.to_vec(), tx);
+        let mut rto = INITIAL_RTO;
+        let mut result = Err(anyhow!("turn request timed out; server={}", self.server));
+        for _ in 0..MAX_TRANSMISSIONS {
+            if let Err(err) = self.socket.send_to(request, self.server).await {
+                result = Err(err.into());
+                break;
+            }
+            if let Ok(response) = timeout(rto, &mut rx).await {
+                result = response.map_err(|err| anyhow!(err));
+                break;
+            }
+            rto *= 2;
+        }
+        self.allocation
+            .lock()
+            .unwrap()
+            .transactions
+            .remove(transaction_id);
+        result
+    }
+
+    fn credentials(&self) -> Option<(String, String, Vec<u8>)> {
+        let allocation = self.allocation.lock().unwrap();
+        Some((
+            allocation.realm.clone()?,
+            allocation.nonce.clone()?,
+            allocation.key.clone()?,
+        ))
+    }
+
+    fn update_credentials(&self, response: &StunMessage) -> Result<()> {
+        let nonce = read_string_attr(response, AttributeType::Nonce)
+            .ok_or(anyhow!("nonce attribute not found."))?;
+        let mut allocation = self.allocation.lock().unwrap();
+        if let Some(realm) = read_string_attr(response, AttributeType::Realm) {
+            allocation.key = Some(long_term_key(&self.username, &realm, &self.password));
+            allocation.realm = Some(realm);
+        }
+        allocation.nonce = Some(nonce);
+        Ok(())
+    }
+
+    fn update_lifetime(&self, response: &StunMessage) {
+        let lifetime = response
+            .attributes
+            .get(&AttributeType::Lifetime)
+            .and_then(|attr| <[u8; 4]>::try_from(attr.value.as_slice()).ok())
+            .map(|value| Duration::from_secs(u32::from_be_bytes(value) as u64))
+            .unwrap_or(DEFAULT_LIFETIME);
+        self.allocation.lock().unwrap().expires_at = Some(Instant::now() + lifetime);
+    }
+}
+
+impl Drop for TurnClient {
+    fn drop(&mut self) {
+        self.recv_loop_handle.abort();
+        // best effort deallocation; the server frees the allocation when its lifetime ends anyway.
+        // https://datatracker.ietf.org/doc/html/rfc8656#section-8
+        let requester = &self.requester;
+        let allocated = requester.allocation.lock().unwrap().expires_at.is_some();
+        if let (true, Some((realm, nonce, key))) = (allocated, requester.credentials()) {
+            let request = StunMessageBuilder::new(
+                StunMessageType {
+                    method: StunMessageMethod::Refresh,
+                    class: StunMessageClass::Request,
+                },
+                generate_transaction_id(),
+            )
+            .add_attr(AttributeType::Lifetime, &0u32.to_be_bytes())
+            .add_attr(AttributeType::Username, requester.username.as_bytes())
+            .add_attr(AttributeType::Realm, realm.as_bytes())
+            .add_attr(AttributeType::Nonce, nonce.as_bytes())
+            .build_with_key(&key);
+            let _ = requester.socket.try_send_to(&request.raw, self.server);
+        }
+    }
+}
+
+async fn recv_loop(
+    socket: Arc<UdpSocket>,
+    server: SocketAddr,
+    allocation: Arc<StdMutex<Allocation>>,
+    inbound_tx: mpsc::UnboundedSender<TransportMessage>,
+) {
+    let mut buf = vec![0u8; 65535];
+    loop {
+        let (len, from) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(err) => {
+                debug!("failed to receive turn datagram: {err}");
+                continue;
+            }
+        };
+        if from != server {
+            debug!("datagram from other than the turn server; from={from}");
+            continue;
+        }
+        let data = &buf[..len];
+
+        let message = if is_channel_data(data) {
+            match decode_channel_data(data) {
+                Ok((channel_number, data)) => {
+                    match allocation.lock().unwrap().channel_peer(channel_number) {
+                        Some(peer_addr) => TransportMessage { peer_addr, data },
+                        None => {
+                            debug!("channel data on unbound channel 0x{channel_number:04x}");
+                            continue;
+                        }
+                    }
+                }
+                Err(err) => {
+                    debug!("invalid channel data: {err}");
+                    continue;
+                }
+            }
+        } else if StunMessage::is_stun_message(data) {
+            let message = match StunMessage::decode(&mut BufReader::new(data)) {
+                Ok(message) => message,
+                Err(err) => {
+                    debug!("invalid turn message: {err}");
+                    continue;
+                }
+            };
+            match (message.message_type.class, message.message_type.method) {
+                // https://datatracker.ietf.org/doc/html/rfc8656#section-11.6
+                (StunMessageClass::Indication, StunMessageMethod::Data) => {
+                    match decode_peer_data(&message) {
+                        Ok(message) => message,
+                        Err(err) => {
+                            warn!("invalid data indication: {err}");
+                            continue;
+                        }
+                    }
+                }
+                (StunMessageClass::SuccessResponse | StunMessageClass::ErrorResponse, _) => {
+                    let tx = allocation
+                        .lock()
+                        .unwrap()
+                        .transactions
+                        .remove(&message.transaction_id);
+                    match tx {
+                        Some(tx) => {
+                            let _ = tx.send(message);
+                        }
+                        None => debug!("turn response for unknown transaction"),
+                    }
+                    continue;
+                }
+                _ => {
+                    debug!("ignored turn message from {from}");
+                    continue;
+                }
+            }
+        } else {
+            debug!("ignored unknown data from turn server; len={len}");
+            continue;
+        };
+
+        if inbound_tx.send(message).is_err() {
+            debug!("turn client dropped; stop receiving.");
+            return;
+        }
+    }
+}
+
+/// XOR-PEER-ADDRESS and DATA of Send and Data indications.
+pub fn decode_peer_data(message: &StunMessage) -> Result<TransportMessage> {
+    let peer_addr = read_address_attr(message, AttributeType::XorPeerAddress)?;
+    let data = message
+        .attributes
+        .get(&AttributeType::Data)
+        .ok_or(anyhow!("data attribute not found."))?
+        .value
+        .clone();
+    Ok(TransportMessage { peer_addr, data })
+}
+
+pub fn read_address_attr(message: &StunMessage, attr_type: AttributeType) -> Result<SocketAddr> {
+    let attr = message.attributes.get(&attr_type).ok_or(anyhow!(
+        "address attribute not found; type=0x{:04x}",
+        attr_type as u16
+    ))?;
+    decode_xor_mapped_address(&attr.value, &message.transaction_id)
+}
+
+pub fn read_string_attr(message: &StunMessage, attr_type: AttributeType) -> Option<String> {
+    let value = &message.attributes.get(&attr_type)?.value;
+    String::from_utf8(value.clone()).ok()
+}
+
+#[cfg(test)]
+mod turn_tests {
+    use super::*;
+    use crate::stun::encode_error_code;
+    use crate::stun_server::{DEFAULT_REALM, StunServer, StunServerOptions, TurnRelayOptions};
+
+    const USERNAME: &str = "user";
+    const PASSWORD: &str = "pass";
+
+    async fn spawn_turn_server() -> Result<SocketAddr> {
+        let server = StunServer::bind(StunServerOptions {
+            bind_address: "127.0.0.1:0".parse()?,
+            turn: Some(TurnRelayOptions {
+                relay_ip: "127.0.0.1".parse()?,
+                ..TurnRelayOptions::new(USERNAME, PASSWORD)
+            }),
+        })
+        .await?;
+        let server_addr = server.local_addr()?;
+        tokio::spawn(async move { server.run().await });
+        Ok(server_addr)
+    }
+
+    async fn spawn_forging_server() -> Result<SocketAddr> {
+        let socket = UdpSocket::bind("127.0.0.1:0").await?;
+        let server_addr = socket.local_addr()?;
+        tokio::spawn(forge_responses(socket));
+        Ok(server_addr)
+    }
+
+    // Answers the 401 challenge and then every request with an unauthenticated success
+    // response, as an off-path attacker forging responses would.
+    async fn forge_responses(socket: UdpSocket) -> Result<()> {
+        let mut buf = vec![0u8; 1500];
+        loop {
+            let (len, from) = socket.recv_from(&mut buf).await?;
+            let message = StunMessage::decode(&mut BufReader::new(&buf[..len]))?;
+            let transaction_id = message.transaction_id.clone();
+            let response = |class| {
+                StunMessageBuilder::new(
+                    StunMessageType {
+                        method: message.message_type.method,
+                        class,
+                    },
+                    transaction_id.clone(),
+                )
+            };
+            let response = match message.attributes.contains_key(&AttributeType::Nonce) {
+                true => response(StunMessageClass::SuccessResponse).add_attr(
+                    AttributeType::XorRelayedAddress,
+                    &encode_xor_mapped_address(from, &transaction_id),
+                ),
+                false => response(StunMessageClass::ErrorResponse)
+                    .add_attr(
+                        AttributeType::ErrorCode,
+                        &encode_error_code(UNAUTHORIZED, ""),
+                    )
+                    .add_attr(AttributeType::Realm, DEFAULT_REALM.as_bytes())
+                    .add_attr(AttributeType::Nonce, b"nonce"),
+            };
+            socket
+                .send_to(&response.build_without_integrity().raw, from)
+                .await?;
+        }
+    }
+
+    async fn recv_relayed(turn_client: &mut TurnClient) -> Result<TransportMessage> {
+        timeout(Duration::from_secs(1), turn_client.recv())
+            .await?
+            .ok_or(anyhow!("turn client closed"))
+    }
+
+    async fn recv_from_relay(peer: &UdpSocket) -> Result<(Vec<u8>, SocketAddr)> {
+        let mut buf = vec![0u8; 1500];
+        let (len, from) = timeout(Duration::from_secs(1), peer.recv_from(&mut buf)).await??;
+        Ok((buf[..len].to_vec(), from))
+    }
+
+    #[test]
+    fn test_channel_data() -> Result<()> {
+        let message = encode_channel_data(0x4001, b"hello");
+        assert_eq!(message.len(), 12);
+        assert!(is_channel_data(&message));
+        assert_eq!(decode_channel_data(&message)?, (0x4001, b"hello".to_vec()));
+        assert!(decode_channel_data(&encode_channel_data(0x3fff, b"")).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_allocate_and_relay() -> Result<()> {
+        let server_addr = spawn_turn_server().await?;
+        let mut turn_client = TurnClient::bind(server_addr, USERNAME, PASSWORD).await?;
+        // the first attempt is challenged with 401 (Unauthorized)
+        let relayed_address = turn_client.allocate().await?;
+        assert_eq!(
+            turn_client.mapped_address().map(|addr| addr.ip()),
+            Some(server_addr.ip())
+        );
+        assert!(turn_client.next_timeout().is_some());
+
+        // a Send indication creates a permission first
+        let peer = UdpSocket::bind("127.0.0.1:0").await?;
+        let peer_addr = peer.local_addr()?;
+        turn_client.send_to(b"send indication", peer_addr).await?;
+        assert_eq!(
+            recv_from_relay(&peer).await?,
+            (b"send indication".to_vec(), relayed_address)
+        );
+        peer.send_to(b"data indication", relayed_address).await?;
+        let message = recv_relayed(&mut turn_client).await?;
+        assert_eq!(message.peer_addr, peer_addr);
+        assert_eq!(message.data, b"data indication");
+
+        // once a channel is bound both directions use ChannelData
+        assert_eq!(
+            turn_client.channel_bind(peer_addr).await?,
+            MIN_CHANNEL_NUMBER
+        );
+        assert!(turn_client.has_channel(peer_addr));
+        turn_client.send_to(b"to peer", peer_addr).await?;
+        assert_eq!(
+            recv_from_relay(&peer).await?,
+            (b"to peer".to_vec(), relayed_address)
+        );
+        peer.send_to(b"to client", relayed_address).await?;
+        let message = recv_relayed(&mut turn_client).await?;
+        assert_eq!(message.peer_addr, peer_addr);
+        assert_eq!(message.data, b"to client");
+
+        // deleting the allocation stops refreshes
+        turn_client.refresh(Duration::ZERO).await?;
+        assert!(turn_client.next_timeout().is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_in_background() -> Result<()> {
+        let server_addr = spawn_turn_server().await?;
+        let mut turn_client = TurnClient::bind(server_addr, USERNAME, PASSWORD).await?;
+        let relayed_address = turn_client.allocate().await?;
+        let peer = UdpSocket::bind("127.0.0.1:0").await?;
+        let peer_addr = peer.local_addr()?;
+
+        // binding a channel does not wait for the server; datagrams use it once bound
+        turn_client.start_channel_bind(peer_addr);
+        turn_client.start_channel_bind(peer_addr);
+        assert!(!turn_client.has_channel(peer_addr));
+        timeout(Duration::from_secs(1), async {
+            while !turn_client.has_channel(peer_addr) {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        turn_client.send_to(b"to peer", peer_addr).await?;
+        assert_eq!(
+            recv_from_relay(&peer).await?,
+            (b"to peer".to_vec(), relayed_address)
+        );
+
+        // whatever is due is refreshed in the background and not due again meanwhile
+        turn_client.handle_timeout(Instant::now() + DEFAULT_LIFETIME);
+        assert_eq!(turn_client.next_timeout(), None);
+        let next_timeout = timeout(Duration::from_secs(1), async {
+            loop {
+                if let Some(next_timeout) = turn_client.next_timeout() {
+                    return next_timeout;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        assert!(next_timeout > Instant::now() + PERMISSION_LIFETIME - 2 * REFRESH_MARGIN);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_allocate_with_wrong_credential() -> Result<()> {
+        let server_addr = spawn_turn_server().await?;
+        let mut turn_client = TurnClient::bind(server_addr, USERNAME, "wrong").await?;
+        assert!(turn_client.allocate().await.is_err());
+        assert_eq!(turn_client.relayed_address(), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reject_unauthenticated_success_response() -> Result<()> {
+        let server_addr = spawn_forging_server().await?;
+        let mut turn_client = TurnClient::bind(server_addr, USERNAME, PASSWORD).await?;
+        let result = turn_client.allocate().await;
+        assert!(result.is_err_and(|err| err.to_string().contains("without message integrity")));
+        assert_eq!(turn_client.relayed_address(), None);
+        Ok(())
+    }
+}
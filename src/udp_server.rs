@@ -1,70 +1,454 @@
 use crate::common::TransportMessage;
-use crate::common::buffer::{BufReader, BufWriter};
+use crate::common::buffer::BufReader;
 use crate::dtls::is_dtls_packet;
-use crate::ice::{IceAgent, Peer};
+use crate::ice::agent::Transmit;
+use crate::ice::{IceAgent, IceCandidate, IceConnectionState, Peer};
 use crate::internal_event::{EventQueue, InternalEvent};
+use crate::sdp::{CandidateType, TransportType};
 use crate::srtp::{is_rtcp_packet, is_rtp_packet};
 use crate::stun::{
-    AttributeType, IpFamily, MAGIC_COOKIE, StunMessage, StunMessageBuilder, StunMessageClass,
-    StunMessageMethod, StunMessageType,
+    AttributeType, StunMessage, StunMessageBuilder, StunMessageClass, StunMessageMethod,
+    StunMessageType, decode_xor_mapped_address, generate_transaction_id,
 };
+use crate::tcp_server::TcpServer;
+use crate::turn::TurnClient;
 use anyhow::{Result, anyhow};
-use std::net::{IpAddr, SocketAddr};
-use std::sync::Arc;
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
 use tokio::net::UdpSocket;
-use tokio::sync::Mutex;
-use tracing::{debug, info, warn};
+use tokio::sync::{Mutex, mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tracing::{debug, info};
 
+const STUN_REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+// longer than the retransmissions of a connectivity check
+// https://datatracker.ietf.org/doc/html/rfc8489#section-6.2.1
+const CHECK_TRANSACTION_LIFETIME: Duration = Duration::from_secs(40);
+
+// Routes are removed from `UdpTransport::drop`, which cannot await; hence the std mutex.
+#[derive(Default)]
+struct Routes {
+    // local ufrag -> inbound channel of the connection
+    by_ufrag: HashMap<String, mpsc::UnboundedSender<TransportMessage>>,
+    // remote address authenticated by a stun binding request or response -> local ufrag
+    by_remote_addr: HashMap<SocketAddr, String>,
+    // transaction id of a check sent by a connection -> local ufrag and when it was sent;
+    // responses carry no USERNAME.
+    checks: HashMap<Vec<u8>, (String, Instant)>,
+    // transaction id of a pending stun request sent by the server itself -> response channel
+    transactions: HashMap<Vec<u8>, oneshot::Sender<StunMessage>>,
+}
+
+impl Routes {
+    fn route(&self, data: &[u8], peer_addr: SocketAddr) -> Option<&str> {
+        if StunMessage::is_stun_message(data) {
+            if let Some((local_ufrag, _)) = data
+                .get(8..20)
+                .and_then(|transaction_id| self.checks.get(transaction_id))
+            {
+                return Some(local_ufrag);
+            }
+            if let Some(local_ufrag) = local_ufrag_of_stun_message(data)
+                && let Some((local_ufrag, _)) = self.by_ufrag.get_key_value(&local_ufrag)
+            {
+                return Some(local_ufrag);
+            }
+        }
+        self.by_remote_addr
+            .get(&peer_addr)
+            .map(|ufrag| ufrag.as_str())
+    }
+
+    fn remove(&mut self, local_ufrag: &str) {
+        self.by_ufrag.remove(local_ufrag);
+        self.by_remote_addr.retain(|_, ufrag| ufrag != local_ufrag);
+        self.checks.retain(|_, (ufrag, _)| ufrag != local_ufrag);
+    }
+}
+
+/// UDP socket shared by any number of connections.
+/// Inbound datagrams are demultiplexed by the local ufrag in the USERNAME of stun
+/// binding requests, by the transaction id of responses to checks, and then by the remote
+/// addresses the connections have authenticated.
 pub struct UdpServer {
+    socket: Arc<UdpSocket>,
+    routes: Arc<StdMutex<Routes>>,
+    recv_loop_handle: JoinHandle<()>,
+}
+
+impl UdpServer {
+    pub fn new(socket: UdpSocket) -> Arc<Self> {
+        if let Ok(addr) = socket.local_addr() {
+            info!("Udp Server listening on {}", addr);
+        }
+
+        let socket = Arc::new(socket);
+        let routes = Arc::new(StdMutex::new(Routes::default()));
+        let recv_loop_handle = tokio::spawn(recv_loop(socket.clone(), routes.clone()));
+
+        Arc::new(UdpServer {
+            socket,
+            routes,
+            recv_loop_handle,
+        })
+    }
+
+    pub async fn bind(addr: SocketAddr) -> Result<Arc<Self>> {
+        Ok(Self::new(bind_udp_socket(addr)?))
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Whether the socket is bound to `[::]` and receives IPv4 datagrams too.
+    pub fn is_dual_stack(&self) -> bool {
+        is_dual_stack(&self.socket)
+    }
+
+    pub async fn send(&self, data: &[u8], peer_addr: SocketAddr) -> Result<()> {
+        let to = socket_peer_addr(self.local_addr()?, peer_addr);
+        self.socket.send_to(data, to).await?;
+        debug!("Sent {} bytes to {}", data.len(), peer_addr);
+        Ok(())
+    }
+
+    /// Registers a connection identified by its local ufrag and returns its inbound channel.
+    pub fn register(&self, local_ufrag: &str) -> mpsc::UnboundedReceiver<TransportMessage> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.routes
+            .lock()
+            .unwrap()
+            .by_ufrag
+            .insert(local_ufrag.to_string(), tx);
+        rx
+    }
+
+    pub fn unregister(&self, local_ufrag: &str) {
+        self.routes.lock().unwrap().remove(local_ufrag);
+    }
+
+    /// Sends a binding request to a stun server from this socket and returns the mapped address;
+    /// the base of server reflexive candidates.
+    // https://datatracker.ietf.org/doc/html/rfc8445#section-5.1.1.2
+    pub async fn binding_request(&self, stun_server: SocketAddr) -> Result<SocketAddr> {
+        let transaction_id = generate_transaction_id();
+        let request = StunMessageBuilder::new(
+            StunMessageType {
+                method: StunMessageMethod::Binding,
+                class: StunMessageClass::Request,
+            },
+            transaction_id.clone(),
+        )
+        .build_without_integrity();
+
+        let (tx, rx) = oneshot::channel();
+        self.routes
+            .lock()
+            .unwrap()
+            .transactions
+            .insert(transaction_id.clone(), tx);
+        self.send(&request.raw, stun_server).await?;
+        let response = timeout(STUN_REQUEST_TIMEOUT, rx).await;
+        self.routes
+            .lock()
+            .unwrap()
+            .transactions
+            .remove(&transaction_id);
+        let response = response
+            .map_err(|_| anyhow!("stun binding request timed out; server={stun_server}"))??;
+
+        let xor_mapped_address_attr = response
+            .attributes
+            .get(&AttributeType::XorMappedAddress)
+            .ok_or(anyhow!("xor mapped address attribute not found."))?;
+        decode_xor_mapped_address(&xor_mapped_address_attr.value, &transaction_id)
+    }
+
+    /// Routes responses to the check `transaction_id` to the connection of `local_ufrag`.
+    pub fn expect_response(&self, transaction_id: &[u8], local_ufrag: &str) {
+        let now = Instant::now();
+        let mut routes = self.routes.lock().unwrap();
+        routes
+            .checks
+            .retain(|_, (_, sent_at)| now.duration_since(*sent_at) < CHECK_TRANSACTION_LIFETIME);
+        if routes.by_ufrag.contains_key(local_ufrag) {
+            routes
+                .checks
+                .insert(transaction_id.to_vec(), (local_ufrag.to_string(), now));
+        }
+    }
+
+    /// Routes non-stun datagrams from `remote_addr` to the connection of `local_ufrag`; only
+    /// for a remote that has authenticated itself to that connection.
+    pub fn bind_remote_addr(&self, remote_addr: SocketAddr, local_ufrag: &str) {
+        let mut routes = self.routes.lock().unwrap();
+        if routes.by_ufrag.contains_key(local_ufrag) {
+            routes
+                .by_remote_addr
+                .insert(remote_addr, local_ufrag.to_string());
+        }
+    }
+}
+
+impl Drop for UdpServer {
+    fn drop(&mut self) {
+        self.recv_loop_handle.abort();
+    }
+}
+
+async fn recv_loop(socket: Arc<UdpSocket>, routes: Arc<StdMutex<Routes>>) {
+    let mut buf = vec![0u8; 65535];
+    loop {
+        let (len, peer_addr) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(err) => {
+                // e.g. ICMP port unreachable surfaces as an error on some platforms
+                debug!("failed to receive udp datagram: {err}");
+                continue;
+            }
+        };
+        let peer_addr = canonical_addr(peer_addr);
+        debug!("Received {} bytes from {}", len, peer_addr);
+        let data = &buf[..len];
+
+        let mut routes = routes.lock().unwrap();
+        if StunMessage::is_stun_message(data)
+            && let Some(tx) = data
+                .get(8..20)
+                .and_then(|transaction_id| routes.transactions.remove(transaction_id))
+        {
+            match StunMessage::decode(&mut BufReader::new(data)) {
+                Ok(message) => {
+                    let _ = tx.send(message);
+                }
+                Err(err) => debug!("invalid stun response; peer={peer_addr}: {err}"),
+            }
+            continue;
+        }
+        let Some(local_ufrag) = routes.route(data, peer_addr).map(|ufrag| ufrag.to_string()) else {
+            debug!("no route for datagram; peer={}, len={}", peer_addr, len);
+            continue;
+        };
+        let message = TransportMessage {
+            peer_addr,
+            data: data.to_vec(),
+        };
+        let closed = routes
+            .by_ufrag
+            .get(&local_ufrag)
+            .is_none_or(|tx| tx.send(message).is_err());
+        if closed {
+            debug!("connection closed; remove route; local_ufrag={local_ufrag}");
+            routes.remove(&local_ufrag);
+        }
+    }
+}
+
+/// Binds a udp socket; the unspecified IPv6 address `[::]` also accepts IPv4 datagrams.
+pub fn bind_udp_socket(addr: SocketAddr) -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    if addr.ip() == IpAddr::V6(Ipv6Addr::UNSPECIFIED) {
+        socket.set_only_v6(false)?;
+    }
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+pub fn is_dual_stack(socket: &UdpSocket) -> bool {
+    socket.local_addr().is_ok_and(|addr| addr.is_ipv6())
+        && SockRef::from(socket)
+            .only_v6()
+            .is_ok_and(|only_v6| !only_v6)
+}
+
+/// IPv4 peers of a dual-stack socket appear as IPv4-mapped IPv6 addresses; unmaps them so
+/// they match the addresses of ICE candidates.
+pub fn canonical_addr(addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(addr.ip().to_canonical(), addr.port())
+}
+
+/// Destination to pass to a socket bound to `local_addr`; IPv6 sockets reach IPv4 peers
+/// through IPv4-mapped IPv6 addresses.
+pub fn socket_peer_addr(local_addr: SocketAddr, peer_addr: SocketAddr) -> SocketAddr {
+    match (local_addr.ip(), peer_addr.ip()) {
+        (IpAddr::V6(_), IpAddr::V4(ip)) => {
+            SocketAddr::new(IpAddr::V6(ip.to_ipv6_mapped()), peer_addr.port())
+        }
+        _ => peer_addr,
+    }
+}
+
+// USERNAME of a binding request is `<receiver ufrag>:<sender ufrag>`.
+// https://datatracker.ietf.org/doc/html/rfc8445#section-7.2.2
+pub fn local_ufrag_of_stun_message(data: &[u8]) -> Option<String> {
+    let message = StunMessage::decode(&mut BufReader::new(data)).ok()?;
+    let username = message.attributes.get(&AttributeType::Username)?;
+    let username = std::str::from_utf8(&username.value).ok()?;
+    let (local_ufrag, _) = username.split_once(':')?;
+    Some(local_ufrag.to_string())
+}
+
+// Passive ICE-TCP candidates of a connection on a shared [`TcpServer`].
+struct TcpCandidates {
+    tcp_server: Arc<TcpServer>,
+    host_addrs: Vec<SocketAddr>,
+    inbound_rx: mpsc::UnboundedReceiver<TransportMessage>,
+}
+
+/// Per-connection view of a shared [`UdpServer`], plus the passive ICE-TCP candidates on a
+/// shared [`TcpServer`] and the TURN allocation of its relay candidate if any.
+pub struct UdpTransport {
     pub ice_agent: Arc<Mutex<IceAgent>>,
-    pub socket: Arc<UdpSocket>,
+    udp_server: Arc<UdpServer>,
+    local_ufrag: String,
+    // addresses of the host candidates; the local side of datagrams from the udp server
+    host_addrs: Vec<SocketAddr>,
+    inbound_rx: mpsc::UnboundedReceiver<TransportMessage>,
+    tcp: Option<TcpCandidates>,
+    relay: Option<TurnClient>,
     event_queue: Arc<Mutex<EventQueue>>,
 }
 
-impl UdpServer {
+impl UdpTransport {
+    /// Creates the transport of `host_candidates`; the relay is set once its allocation
+    /// succeeds.
     pub async fn new(
-        addr: &str,
+        udp_server: Arc<UdpServer>,
+        tcp_server: Option<Arc<TcpServer>>,
         ice_agent: Arc<Mutex<IceAgent>>,
+        host_candidates: &[IceCandidate],
         event_queue: Arc<Mutex<EventQueue>>,
     ) -> Result<Self> {
-        // Bind UDP socket
-        let socket = Arc::new(UdpSocket::bind(addr).await?);
-        info!("Udp Server listening on {}", addr);
-
-        Ok(UdpServer {
+        let local_ufrag = ice_agent.lock().await.local_peer.ufrag.clone();
+        let host_addrs_of = |transport_type| {
+            host_candidates
+                .iter()
+                .filter(|candidate| {
+                    candidate.candidate_type == CandidateType::Host
+                        && candidate.transport_type == transport_type
+                })
+                .map(|candidate| candidate.addr())
+                .collect::<Vec<_>>()
+        };
+        let mut host_addrs = host_addrs_of(TransportType::Udp);
+        let tcp_host_addrs = host_addrs_of(TransportType::Tcp);
+        if host_addrs.is_empty() {
+            host_addrs.push(udp_server.local_addr()?);
+        }
+        let inbound_rx = udp_server.register(&local_ufrag);
+        let tcp = match tcp_server {
+            Some(tcp_server) if !tcp_host_addrs.is_empty() => Some(TcpCandidates {
+                inbound_rx: tcp_server.register(&local_ufrag),
+                tcp_server,
+                host_addrs: tcp_host_addrs,
+            }),
+            _ => None,
+        };
+        Ok(Self {
             ice_agent,
-            socket,
+            udp_server,
+            local_ufrag,
+            host_addrs,
+            inbound_rx,
+            tcp,
+            relay: None,
             event_queue,
         })
     }
 
-    pub async fn recv(&mut self) -> Result<()> {
-        let mut buf = vec![0u8; 65535];
-
-        let (len, peer_addr) = self.socket.recv_from(&mut buf).await?;
-        debug!("Received {} bytes from {}", len, peer_addr);
+    /// Routes datagrams of the relay candidate through `relay`.
+    pub fn set_relay(&mut self, relay: TurnClient) {
+        self.relay = Some(relay);
+    }
 
-        self.handle_inbound_message(&buf[..len], peer_addr).await
+    /// Handles the next inbound datagram, either from the udp server, a tcp connection or
+    /// relayed by the TURN server; returns `None` when the udp server is gone.
+    pub async fn recv(&mut self) -> Option<Result<()>> {
+        let relayed_address = self
+            .relay
+            .as_ref()
+            .and_then(|relay| relay.relayed_address());
+        let relay = self.relay.as_mut();
+        let relay_recv = async {
+            match relay {
+                Some(relay) => relay.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+        let tcp = self.tcp.as_mut();
+        let tcp_recv = async {
+            match tcp {
+                Some(tcp) => {
+                    let message = tcp.inbound_rx.recv().await?;
+                    Some((host_addr_of(&tcp.host_addrs, message.peer_addr), message))
+                }
+                None => std::future::pending().await,
+            }
+        };
+        let (local, TransportMessage { peer_addr, data }) = tokio::select! {
+            message = self.inbound_rx.recv() => {
+                let message = message?;
+                (host_addr_of(&self.host_addrs, message.peer_addr), message)
+            }
+            Some(message) = tcp_recv => message,
+            Some(message) = relay_recv => (relayed_address?, message),
+        };
+        Some(self.handle_inbound_message(&data, local, peer_addr).await)
     }
 
+    /// Sends from the local candidate of the selected pair; over its tcp connection when a
+    /// passive ICE-TCP candidate is selected and through the TURN server when the relay
+    /// candidate is selected.
     pub async fn send(&self, data: &[u8], peer_addr: SocketAddr) -> Result<()> {
-        self.socket.send_to(&data, peer_addr).await?;
-        debug!("Sent {} bytes to {}", &data.len(), peer_addr);
-        Ok(())
+        let selected_local_addr = {
+            let ice_agent = self.ice_agent.lock().await;
+            if ice_agent.state == IceConnectionState::Failed {
+                return Err(anyhow!(
+                    "ice connection failed; drop outbound datagram to {peer_addr}."
+                ));
+            }
+            ice_agent.selected_local_addr()
+        };
+        if let Some(tcp) = self.tcp_of(selected_local_addr) {
+            return tcp.tcp_server.send(data, peer_addr);
+        }
+        match self.relay_of(selected_local_addr) {
+            Some(relay) => {
+                // https://datatracker.ietf.org/doc/html/rfc8656#section-12
+                // media goes over a channel to save the 36 bytes of Send indications, once
+                // the channel is bound.
+                if !relay.has_channel(peer_addr) {
+                    relay.start_channel_bind(peer_addr);
+                }
+                relay.send_to(data, peer_addr).await
+            }
+            None => self.udp_server.send(data, peer_addr).await,
+        }
     }
 
     pub async fn set_remote_peers(&mut self, peers: Vec<Peer>) {
         self.ice_agent.lock().await.remote_peers = peers;
     }
 
-    async fn handle_inbound_message(&mut self, data: &[u8], peer_addr: SocketAddr) -> Result<()> {
+    async fn handle_inbound_message(
+        &mut self,
+        data: &[u8],
+        local: SocketAddr,
+        peer_addr: SocketAddr,
+    ) -> Result<()> {
         if data.is_empty() {
             return Ok(());
         }
 
         if StunMessage::is_stun_message(data) {
             debug!("stun message received");
-            return self.handle_stun_message(data, peer_addr).await;
+            return self.handle_stun_message(data, local, peer_addr).await;
         }
 
         if is_dtls_packet(data) {
@@ -110,83 +494,239 @@ impl UdpServer {
         Ok(())
     }
 
-    async fn handle_stun_message(&mut self, data: &[u8], peer_addr: SocketAddr) -> Result<()> {
-        // respond to stun binding request of ice
-        // - decode stun message
+    async fn handle_stun_message(
+        &mut self,
+        data: &[u8],
+        local: SocketAddr,
+        peer_addr: SocketAddr,
+    ) -> Result<()> {
         let mut reader = BufReader::new(data);
         let message = StunMessage::decode(&mut reader)?;
-        // - extract server username and client username
-        let username_attr = message
-            .attributes
-            .get(&AttributeType::Username)
-            .ok_or(anyhow!("username attribute does not exists."))?;
-        let (local_ufrag, local_pwd, remote_ufrags) = {
-            let ice_agent = self.ice_agent.lock().await;
-            (
-                ice_agent.local_peer.ufrag.clone(),
-                ice_agent.local_peer.pwd.clone(),
-                ice_agent
-                    .remote_peers
-                    .iter()
-                    .map(|peer| peer.ufrag.clone())
-                    .collect::<Vec<_>>(),
-            )
+        let authenticated = {
+            let mut ice_agent = self.ice_agent.lock().await;
+            ice_agent.handle_stun_message(&message, local, peer_addr, Instant::now())?;
+            ice_agent.is_authenticated(peer_addr)
         };
+        // other datagrams from the remote come without USERNAME; route them by its address
+        // once it has proven to know the credentials.
+        if authenticated
+            && self.tcp_of(Some(local)).is_none()
+            && self.relay_of(Some(local)).is_none()
+        {
+            self.udp_server
+                .bind_remote_addr(peer_addr, &self.local_ufrag);
+        }
+        self.flush_transmits().await
+    }
 
-        if remote_ufrags.is_empty() {
-            warn!("remote ufrags not configured; ignore the message.");
-            return Ok(()); // ignore message
+    /// Drives retransmissions, connectivity checks and consent freshness of the ice agent,
+    /// and starts refreshing the TURN allocation when due.
+    pub async fn handle_timeout(&mut self) -> Result<()> {
+        let now = Instant::now();
+        self.ice_agent.lock().await.handle_timeout(now);
+        if let Some(relay) = &self.relay
+            && relay.next_timeout().is_some_and(|at| at <= now)
+        {
+            relay.handle_timeout(now);
         }
+        self.flush_transmits().await
+    }
 
-        // https://datatracker.ietf.org/doc/html/rfc8445#section-7.2.2
-        // - verify username in the message
-        let username = unsafe { String::from_utf8_unchecked(username_attr.value.clone()) };
+    pub async fn next_timeout(&self) -> Option<Instant> {
+        let relay_timeout = self.relay.as_ref().and_then(|relay| relay.next_timeout());
+        self.ice_agent
+            .lock()
+            .await
+            .next_timeout()
+            .into_iter()
+            .chain(relay_timeout)
+            .min()
+    }
 
-        let expected_usernames = remote_ufrags
-            .iter()
-            .map(|remote_ufrag| format!("{local_ufrag}:{remote_ufrag}"))
-            .collect::<Vec<_>>();
-        if !expected_usernames.contains(&username) {
-            warn!(
-                "username attribute mismatch: actual={username}, expected={expected_usernames:?}; ignore the message."
-            );
-            return Ok(()); // ignore message
-        }
-
-        // - verify message integrity
-        message.verify_message_integrity(local_pwd.clone())?;
-
-        // - send stun binding response
-        let xor_mapped_address = {
-            let mut value_buf = BufWriter::new();
-            value_buf.write_u8(0);
-            value_buf.write_u8(IpFamily::V4 as u8);
-            let x_port = peer_addr.port() ^ ((MAGIC_COOKIE >> 16) as u16);
-            value_buf.write_u16(x_port);
-            let ip_addr_bytes = match peer_addr.ip() {
-                IpAddr::V4(addr) => addr.octets(),
-                IpAddr::V6(addr) => {
-                    return Err(anyhow!("ip address is not ipv4; {addr}"));
-                }
+    async fn flush_transmits(&mut self) -> Result<()> {
+        loop {
+            let Some(Transmit { from, to, data }) = self.ice_agent.lock().await.poll_transmit()
+            else {
+                return Ok(());
             };
-            for (i, octet) in ip_addr_bytes.iter().enumerate() {
-                value_buf.write_u8(octet ^ (MAGIC_COOKIE >> (8 * (3 - i))) as u8);
+            if let Some(tcp) = self.tcp_of(Some(from)) {
+                // the remote may have closed the tcp connection; other pairs go on.
+                let _ = tcp
+                    .tcp_server
+                    .send(&data, to)
+                    .inspect_err(|err| debug!("{err:?}"));
+                continue;
             }
-            value_buf.buf()
-        };
-        let response_message = StunMessageBuilder::new(
+            match self.relay_of(Some(from)) {
+                Some(relay) => relay.send_to(&data, to).await?,
+                None => {
+                    if let Ok(message) = StunMessage::decode(&mut BufReader::new(&data))
+                        && matches!(message.message_type.class, StunMessageClass::Request)
+                    {
+                        self.udp_server
+                            .expect_response(&message.transaction_id, &self.local_ufrag);
+                    }
+                    self.udp_server.send(&data, to).await?;
+                }
+            }
+        }
+    }
+
+    fn tcp_of(&self, local: Option<SocketAddr>) -> Option<&TcpCandidates> {
+        self.tcp
+            .as_ref()
+            .filter(|tcp| local.is_some_and(|local| tcp.host_addrs.contains(&local)))
+    }
+
+    fn relay_of(&self, local: Option<SocketAddr>) -> Option<&TurnClient> {
+        self.relay
+            .as_ref()
+            .filter(|relay| local.is_some() && relay.relayed_address() == local)
+    }
+}
+
+impl Drop for UdpTransport {
+    fn drop(&mut self) {
+        self.udp_server.unregister(&self.local_ufrag);
+        if let Some(tcp) = &self.tcp {
+            tcp.tcp_server.unregister(&self.local_ufrag);
+        }
+    }
+}
+
+// Host candidates share the socket of the udp server (or the tcp listener), which does not
+// tell the destination address; the first host candidate of the peer's address family stands
+// for it.
+fn host_addr_of(host_addrs: &[SocketAddr], peer_addr: SocketAddr) -> SocketAddr {
+    host_addrs
+        .iter()
+        .find(|addr| addr.is_ipv4() == peer_addr.is_ipv4())
+        .copied()
+        .unwrap_or(host_addrs[0])
+}
+
+#[cfg(test)]
+mod udp_server_tests {
+    use super::*;
+    use crate::stun::encode_xor_mapped_address;
+
+    const DTLS_RECORD: [u8; 5] = [22, 0xfe, 0xfd, 0, 0];
+
+    fn binding_request(username: &str) -> Vec<u8> {
+        StunMessageBuilder::new(
             StunMessageType {
                 method: StunMessageMethod::Binding,
-                class: StunMessageClass::SuccessResponse,
+                class: StunMessageClass::Request,
             },
-            message.transaction_id,
+            vec![1u8; 12],
         )
-        .add_attr(AttributeType::XorMappedAddress, &xor_mapped_address)
-        .add_attr(AttributeType::Username, &username_attr.value[..])
-        .build(local_pwd);
-        self.socket
-            .send_to(&response_message.raw, peer_addr)
+        .add_attr(AttributeType::Username, username.as_bytes())
+        .build("pwd".to_string())
+        .raw
+    }
+
+    async fn next_message(
+        rx: &mut mpsc::UnboundedReceiver<TransportMessage>,
+    ) -> Result<TransportMessage> {
+        timeout(Duration::from_secs(1), rx.recv())
+            .await?
+            .ok_or(anyhow!("inbound channel closed"))
+    }
+
+    #[tokio::test]
+    async fn test_demux_by_ufrag_and_remote_addr() -> Result<()> {
+        let server = UdpServer::bind("127.0.0.1:0".parse()?).await?;
+        let server_addr = server.local_addr()?;
+        let mut a_rx = server.register("ufragA");
+        let mut b_rx = server.register("ufragB");
+
+        let client = UdpSocket::bind("127.0.0.1:0").await?;
+        let client_addr = client.local_addr()?;
+
+        // stun binding requests are routed by the local ufrag in USERNAME
+        client
+            .send_to(&binding_request("ufragB:remote"), server_addr)
             .await?;
+        let message = next_message(&mut b_rx).await?;
+        assert_eq!(message.peer_addr, client_addr);
+        assert!(a_rx.try_recv().is_err());
+
+        // other datagrams are dropped until the remote address is bound
+        client.send_to(&DTLS_RECORD, server_addr).await?;
+        client
+            .send_to(&binding_request("ufragA:remote"), server_addr)
+            .await?;
+        next_message(&mut a_rx).await?;
+        assert!(b_rx.try_recv().is_err());
+
+        server.bind_remote_addr(client_addr, "ufragB");
+        client.send_to(&DTLS_RECORD, server_addr).await?;
+        let message = next_message(&mut b_rx).await?;
+        assert_eq!(message.data, DTLS_RECORD);
+        assert!(a_rx.try_recv().is_err());
+
+        // unregistered connections no longer receive anything
+        server.unregister("ufragB");
+        client.send_to(&DTLS_RECORD, server_addr).await?;
+        assert!(b_rx.recv().await.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_binding_request_from_shared_socket() -> Result<()> {
+        let server = UdpServer::bind("127.0.0.1:0".parse()?).await?;
+        let stun_server = UdpSocket::bind("127.0.0.1:0").await?;
+        let stun_server_addr = stun_server.local_addr()?;
+
+        let responder = tokio::spawn(async move {
+            let mut buf = vec![0u8; 1500];
+            let (len, from) = stun_server.recv_from(&mut buf).await?;
+            let request = StunMessage::decode(&mut BufReader::new(&buf[..len]))?;
+            let response = StunMessageBuilder::new(
+                StunMessageType {
+                    method: StunMessageMethod::Binding,
+                    class: StunMessageClass::SuccessResponse,
+                },
+                request.transaction_id.clone(),
+            )
+            .add_attr(
+                AttributeType::XorMappedAddress,
+                &encode_xor_mapped_address(from, &request.transaction_id),
+            )
+            .build_without_integrity();
+            stun_server.send_to(&response.raw, from).await?;
+            anyhow::Ok(())
+        });
+
+        // the mapped address is the shared socket itself, not an ephemeral one
+        let mapped_address = server.binding_request(stun_server_addr).await?;
+        assert_eq!(mapped_address, server.local_addr()?);
+        responder.await??;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dual_stack() -> Result<()> {
+        let server = UdpServer::bind("[::]:0".parse()?).await?;
+        assert!(server.is_dual_stack());
+        let port = server.local_addr()?.port();
+        let mut rx = server.register("ufrag");
+
+        for (client_ip, server_ip) in [("127.0.0.1", "127.0.0.1"), ("::1", "::1")] {
+            let client = UdpSocket::bind((client_ip, 0)).await?;
+            let client_addr = client.local_addr()?;
+            client
+                .send_to(&binding_request("ufrag:remote"), (server_ip, port))
+                .await?;
+            // ipv4 peers are reported without the IPv4-mapped IPv6 prefix
+            let message = next_message(&mut rx).await?;
+            assert_eq!(message.peer_addr, client_addr);
+
+            server.send(&DTLS_RECORD, client_addr).await?;
+            let mut buf = vec![0u8; 1500];
+            let (len, _) = timeout(Duration::from_secs(1), client.recv_from(&mut buf)).await??;
+            assert_eq!(buf[..len], DTLS_RECORD);
+        }
         Ok(())
     }
 }
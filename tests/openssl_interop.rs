@@ -1,10 +1,11 @@
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use dtls::{Fingerprint, manager::DtlsManager};
-use rcgen::generate_simple_self_signed;
+use mini_webrtc_rs::dtls::{DtlsEvent, DtlsState, certificate_fingerprint, manager::DtlsManager};
+use mini_webrtc_rs::rtc_certificate::{RtcCertificate, RtcCertificateParams};
+use mini_webrtc_rs::sdp::FingerprintType;
+use mini_webrtc_rs::sdp::session_description::SdpFingerprint;
 use tokio::net::UdpSocket;
 use tokio::process::Command;
 
@@ -57,7 +58,7 @@ async fn has_openssl() -> bool {
 }
 
 #[tokio::test]
-async fn openssl_client_can_reach_dtls_server_flights() -> anyhow::Result<()> {
+async fn openssl_client_completes_dtls_handshake() -> anyhow::Result<()> {
     if !has_openssl().await {
         eprintln!("openssl not found; skipping interoperability test");
         return Ok(());
@@ -113,19 +114,29 @@ async fn openssl_client_can_reach_dtls_server_flights() -> anyhow::Result<()> {
     .await?;
 
     let client_der_bytes = std::fs::read(&client_der)?;
-    let expected_client_fingerprint = Fingerprint::new(&client_der_bytes);
+    let expected_client_fingerprint = SdpFingerprint {
+        hash_function: FingerprintType::Sha256,
+        fingerprint: certificate_fingerprint(&client_der_bytes, FingerprintType::Sha256),
+    };
+
+    // the PKCS#8 key written by `openssl req` loads as is.
+    let client_pem =
+        std::fs::read_to_string(&client_cert)? + &std::fs::read_to_string(&client_key)?;
+    let client_certificate = RtcCertificate::from_pem(&client_pem)?;
+    assert_eq!(
+        client_certificate.fingerprint(FingerprintType::Sha256),
+        expected_client_fingerprint
+    );
 
-    let server_certified_key = generate_simple_self_signed(vec!["localhost".to_string()])?;
-    let server_socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await?);
+    let server_certificate = RtcCertificate::generate(&RtcCertificateParams::default())?;
+    let server_socket = UdpSocket::bind("127.0.0.1:0").await?;
     let server_addr = server_socket.local_addr()?;
 
-    let mut manager = DtlsManager::new(
-        Arc::clone(&server_socket),
-        server_certified_key,
-        expected_client_fingerprint,
-    );
+    let mut manager = DtlsManager::new(vec![server_certificate]);
+    manager.remote_fingerprints = vec![expected_client_fingerprint];
 
     let mut received_packets = 0usize;
+    let mut srtp_keys = None;
 
     let mut openssl = Command::new("openssl");
     openssl
@@ -164,18 +175,22 @@ async fn openssl_client_can_reach_dtls_server_flights() -> anyhow::Result<()> {
         match recv {
             Ok(Ok((n, peer))) => {
                 received_packets += 1;
-                if let Err(e) = manager.handle_dtls_packet(&buf[..n], peer).await {
-                    let message = e.to_string();
-                    if message.contains("client certificate not found")
-                        || message.contains("out of index")
-                    {
-                        break;
-                    }
-                    return Err(e);
-                }
+                manager.handle_inbound_packet(&buf[..n], peer, Instant::now())?;
             }
             Ok(Err(e)) => return Err(anyhow::anyhow!("server recv error: {e}")),
-            Err(_) => {}
+            Err(_) => manager.handle_timeout(Instant::now())?,
+        }
+
+        // the endpoint does no I/O; its datagrams and events are drained after every input.
+        while let Some(event) = manager.poll_event() {
+            if let DtlsEvent::Connected(keys) = event {
+                srtp_keys = keys;
+            }
+        }
+        while let Some(message) = manager.poll_transmit() {
+            server_socket
+                .send_to(&message.data, message.peer_addr)
+                .await?;
         }
 
         if child.try_wait()?.is_some() {
@@ -212,5 +227,18 @@ async fn openssl_client_can_reach_dtls_server_flights() -> anyhow::Result<()> {
         "OpenSSL rejected ServerKeyExchange signature. output:\n{openssl_output}"
     );
 
+    assert!(
+        srtp_keys.is_some(),
+        "handshake did not complete; state={:?}. openssl output:\n{openssl_output}",
+        manager.state
+    );
+
+    // openssl closes the connection once stdin reaches EOF.
+    assert!(
+        matches!(manager.state, DtlsState::Connected | DtlsState::Closed),
+        "unexpected state {:?}. openssl output:\n{openssl_output}",
+        manager.state
+    );
+
     Ok(())
 }